@@ -7,7 +7,7 @@ use cortex::{
         New,
     },
     models::{
-        attribute_schema::{AttributeSchema, CreateAttributeSchema, Quantity},
+        attribute_schema::{AttributeSchema, Constraints, CreateAttributeSchema, Quantity},
         attribute_type::{CreateAttributeType, CreateReferenceAttribute, SimpleAttributeType},
         entity::EntityId,
         entity_schema::{CreateEntitySchema, EntitySchema},
@@ -35,6 +35,7 @@ fn db_benchmark(c: &mut Criterion) {
         &tx,
         CreateEntitySchema {
             name: "Parent".to_string(),
+            parent: None,
         },
     )
     .unwrap();
@@ -46,6 +47,9 @@ fn db_benchmark(c: &mut Criterion) {
             name: "Attr1".to_string(),
             attr_type: CreateAttributeType::Simple(SimpleAttributeType::Text),
             quantity: Quantity::Required,
+            constraints: Constraints::default(),
+            default: None,
+            unique: false,
         },
     )
     .unwrap();
@@ -54,6 +58,7 @@ fn db_benchmark(c: &mut Criterion) {
         &tx,
         CreateEntitySchema {
             name: "Child".to_string(),
+            parent: None,
         },
     )
     .unwrap();
@@ -64,8 +69,13 @@ fn db_benchmark(c: &mut Criterion) {
             entity: root_schema.id.clone(),
             name: "Attr2".to_string(),
             quantity: Quantity::Required,
+            constraints: Constraints::default(),
+            default: None,
+            unique: false,
             attr_type: CreateAttributeType::Reference(CreateReferenceAttribute {
-                id: child_schema.id.clone(),
+                targets: vec![child_schema.id.clone()],
+                inverse: None,
+                edges: Vec::new(),
             }),
         },
     )
@@ -75,6 +85,7 @@ fn db_benchmark(c: &mut Criterion) {
         &tx,
         CreateEntitySchema {
             name: "Grandchild".to_string(),
+            parent: None,
         },
     )
     .unwrap();
@@ -85,8 +96,13 @@ fn db_benchmark(c: &mut Criterion) {
             entity: child_schema.id.clone(),
             name: "Attr3".to_string(),
             quantity: Quantity::Required,
+            constraints: Constraints::default(),
+            default: None,
+            unique: false,
             attr_type: CreateAttributeType::Reference(CreateReferenceAttribute {
-                id: grandchild_schema.id.clone(),
+                targets: vec![grandchild_schema.id.clone()],
+                inverse: None,
+                edges: Vec::new(),
             }),
         },
     )
@@ -99,6 +115,9 @@ fn db_benchmark(c: &mut Criterion) {
             name: "Attr4".to_string(),
             attr_type: CreateAttributeType::Simple(SimpleAttributeType::Text),
             quantity: Quantity::Required,
+            constraints: Constraints::default(),
+            default: None,
+            unique: false,
         },
     )
     .unwrap();
@@ -161,6 +180,7 @@ fn db_benchmark(c: &mut Criterion) {
         0: vec![EntityField::Entity(EntityAttribute {
             attribute: grandchild_id,
             request: grandchild_request,
+            fragments: Vec::new(),
         })],
     };
 
@@ -168,6 +188,7 @@ fn db_benchmark(c: &mut Criterion) {
         0: vec![EntityField::Entity(EntityAttribute {
             attribute: child_id,
             request: child_request,
+            fragments: Vec::new(),
         })],
     };
 
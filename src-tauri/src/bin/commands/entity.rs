@@ -1,6 +1,15 @@
 use cortex::{
-    database::entity::{add_entity, get, EntityRequest, EntityResponse},
-    models::{entity::EntityId, entity_schema::EntitySchemaId},
+    database::{
+        attribute::{insert_at, remove_at},
+        entity::{
+            add_entity, add_named_entity, get, get_named, EntityRequest, EntityResponse,
+            NamedRequest,
+        },
+    },
+    models::{
+        attribute::GenericAttributeId, attribute_schema::AttributeSchemaId, entity::EntityId,
+        entity_schema::EntitySchemaId,
+    },
     setup::PoolWrapper,
 };
 
@@ -35,3 +44,76 @@ pub fn get_entity(
     tx.commit()?;
     Ok(entity)
 }
+
+#[tauri::command]
+#[specta::specta]
+pub fn create_named_entity(
+    pool_wrapper: State<'_, PoolWrapper>,
+    schema: EntitySchemaId,
+    data: Value,
+) -> Result<EntityId, Error> {
+    let mut conn = pool_wrapper.pool.get()?;
+    let tx = conn.transaction()?;
+    let new = add_named_entity(&tx, &schema, data)?;
+    tx.commit()?;
+    Ok(new)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn get_named_entity(
+    pool_wrapper: State<'_, PoolWrapper>,
+    entity: EntityId,
+    request: NamedRequest,
+) -> Result<EntityResponse, Error> {
+    let mut conn = pool_wrapper.pool.get()?;
+    let tx = conn.transaction()?;
+    let entity = get_named(&tx, &entity, &request)?;
+    tx.commit()?;
+    Ok(entity)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn insert_list_item(
+    pool_wrapper: State<'_, PoolWrapper>,
+    entity: EntityId,
+    attribute: AttributeSchemaId,
+    index: usize,
+    value: Value,
+) -> Result<(), Error> {
+    let mut conn = pool_wrapper.pool.get()?;
+    let tx = conn.transaction()?;
+    insert_at(&tx, &entity, &attribute, index, &value)?;
+    tx.commit()?;
+    Ok(())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn move_list_item(
+    pool_wrapper: State<'_, PoolWrapper>,
+    item: GenericAttributeId,
+    index: usize,
+) -> Result<(), Error> {
+    let mut conn = pool_wrapper.pool.get()?;
+    let tx = conn.transaction()?;
+    item.move_to(&tx, index)?;
+    tx.commit()?;
+    Ok(())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn remove_list_item(
+    pool_wrapper: State<'_, PoolWrapper>,
+    entity: EntityId,
+    attribute: AttributeSchemaId,
+    index: usize,
+) -> Result<(), Error> {
+    let mut conn = pool_wrapper.pool.get()?;
+    let tx = conn.transaction()?;
+    remove_at(&tx, &entity, &attribute, index)?;
+    tx.commit()?;
+    Ok(())
+}
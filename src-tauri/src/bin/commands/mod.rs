@@ -1,3 +1,5 @@
+use cortex::database::attribute_schema::UniqueViolation;
+
 pub mod entity;
 pub mod schema;
 
@@ -5,11 +7,9 @@ pub mod schema;
 #[serde(tag = "type", content = "data")]
 pub enum Error {
     #[error("{0}")]
-    Rusqlite(
-        #[serde(skip)]
-        #[from]
-        rusqlite::Error,
-    ),
+    Rusqlite(#[serde(skip)] rusqlite::Error),
+    #[error("{0}")]
+    UniqueViolation(#[serde(skip)] UniqueViolation),
     #[error("{0}")]
     R2D2(
         #[serde(skip)]
@@ -18,6 +18,15 @@ pub enum Error {
     ),
 }
 
+impl From<rusqlite::Error> for Error {
+    fn from(err: rusqlite::Error) -> Self {
+        match UniqueViolation::take(err) {
+            Ok(violation) => Error::UniqueViolation(violation),
+            Err(err) => Error::Rusqlite(err),
+        }
+    }
+}
+
 impl serde::Serialize for Error {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
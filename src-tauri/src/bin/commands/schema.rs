@@ -1,11 +1,25 @@
 use cortex::{
-    database::{Get, New},
+    database::{
+        entity_schema::{display_names, json_schemas},
+        schema_document::{export_schemas, import_schemas},
+        Get, New,
+    },
     models::{
-        attribute_schema::{AttributeSchema, CreateAttributeSchema},
-        entity_schema::{CreateEntitySchema, EntitySchema, EntitySchemaId},
+        attribute_schema::{
+            AttributeSchema, AttributeSchemaId, CreateAttributeSchema, MigrationReport,
+            UpdateAttributeSchema,
+        },
+        entity::EntityId,
+        entity_schema::{
+            CreateEntitySchema, DeleteMode, DeletePreview, EntityDisplay, EntitySchema,
+            EntitySchemaId, ParentPreview,
+        },
+        schema_document::{DocumentFormat, ImportReport, SchemaDocument},
     },
     setup::PoolWrapper,
 };
+use serde_json::{Map, Value};
+use std::collections::HashMap;
 use tauri::State;
 
 use super::Error;
@@ -15,10 +29,68 @@ use super::Error;
 pub fn create_entity_schema(
     pool_wrapper: State<'_, PoolWrapper>,
     name: String,
+    parent: Option<EntitySchemaId>,
+) -> Result<EntitySchema, Error> {
+    let mut conn = pool_wrapper.pool.get()?;
+    let tx = conn.transaction()?;
+    let res = EntitySchema::new(&tx, CreateEntitySchema { name, parent })?;
+    tx.commit()?;
+    Ok(res)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn preview_entity_schema_deletion(
+    pool_wrapper: State<'_, PoolWrapper>,
+    id: EntitySchemaId,
+) -> Result<DeletePreview, Error> {
+    let mut conn = pool_wrapper.pool.get()?;
+    let tx = conn.transaction()?;
+    let res = id.delete_preview(&tx)?;
+    tx.commit()?;
+    Ok(res)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn delete_entity_schema(
+    pool_wrapper: State<'_, PoolWrapper>,
+    id: EntitySchemaId,
+    mode: DeleteMode,
+) -> Result<(), Error> {
+    let mut conn = pool_wrapper.pool.get()?;
+    let tx = conn.transaction()?;
+    id.delete_with(&tx, mode)?;
+    tx.commit()?;
+    Ok(())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn preview_entity_schema_parent(
+    pool_wrapper: State<'_, PoolWrapper>,
+    id: EntitySchemaId,
+    parent: Option<EntitySchemaId>,
+) -> Result<ParentPreview, Error> {
+    let mut conn = pool_wrapper.pool.get()?;
+    let tx = conn.transaction()?;
+    let res = id.parent_preview(&tx, parent.as_ref())?;
+    tx.commit()?;
+    Ok(res)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn set_entity_schema_parent(
+    pool_wrapper: State<'_, PoolWrapper>,
+    id: EntitySchemaId,
+    parent: Option<EntitySchemaId>,
+    mode: DeleteMode,
 ) -> Result<EntitySchema, Error> {
     let mut conn = pool_wrapper.pool.get()?;
     let tx = conn.transaction()?;
-    let res = EntitySchema::new(&tx, CreateEntitySchema { name })?;
+    id.set_parent(&tx, parent.as_ref(), mode)?;
+    let res = EntitySchema::get(&tx, &id)?;
     tx.commit()?;
     Ok(res)
 }
@@ -36,6 +108,46 @@ pub fn get_entity_schema(
     Ok(res)
 }
 
+#[tauri::command]
+#[specta::specta]
+pub fn set_entity_schema_display(
+    pool_wrapper: State<'_, PoolWrapper>,
+    id: EntitySchemaId,
+    display: Option<EntityDisplay>,
+) -> Result<EntitySchema, Error> {
+    let mut conn = pool_wrapper.pool.get()?;
+    let tx = conn.transaction()?;
+    id.set_display(&tx, display)?;
+    let res = EntitySchema::get(&tx, &id)?;
+    tx.commit()?;
+    Ok(res)
+}
+
+// Labels for rendering links and lists without fetching the entities
+#[tauri::command]
+#[specta::specta]
+pub fn get_display_names(
+    pool_wrapper: State<'_, PoolWrapper>,
+    entities: Vec<EntityId>,
+) -> Result<HashMap<EntityId, String>, Error> {
+    let mut conn = pool_wrapper.pool.get()?;
+    let tx = conn.transaction()?;
+    let res = display_names(&tx, &entities)?;
+    tx.commit()?;
+    Ok(res)
+}
+
+// JSON Schemas describing the payload create_entity takes, keyed by schema name
+#[tauri::command]
+#[specta::specta]
+pub fn get_json_schemas(pool_wrapper: State<'_, PoolWrapper>) -> Result<Map<String, Value>, Error> {
+    let mut conn = pool_wrapper.pool.get()?;
+    let tx = conn.transaction()?;
+    let res = json_schemas(&tx)?;
+    tx.commit()?;
+    Ok(res)
+}
+
 #[tauri::command]
 #[specta::specta]
 pub fn add_attribute(
@@ -48,3 +160,72 @@ pub fn add_attribute(
     tx.commit()?;
     Ok(res)
 }
+
+#[tauri::command]
+#[specta::specta]
+pub fn update_attribute(
+    pool_wrapper: State<'_, PoolWrapper>,
+    id: AttributeSchemaId,
+    data: UpdateAttributeSchema,
+    dry_run: bool,
+) -> Result<MigrationReport, Error> {
+    let mut conn = pool_wrapper.pool.get()?;
+    let tx = conn.transaction()?;
+    let res = id.update(&tx, data, dry_run)?;
+    tx.commit()?;
+    Ok(res)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn preview_attribute_deletion(
+    pool_wrapper: State<'_, PoolWrapper>,
+    id: AttributeSchemaId,
+) -> Result<DeletePreview, Error> {
+    let mut conn = pool_wrapper.pool.get()?;
+    let tx = conn.transaction()?;
+    let res = id.delete_preview(&tx)?;
+    tx.commit()?;
+    Ok(res)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn delete_attribute(
+    pool_wrapper: State<'_, PoolWrapper>,
+    id: AttributeSchemaId,
+    mode: DeleteMode,
+) -> Result<(), Error> {
+    let mut conn = pool_wrapper.pool.get()?;
+    let tx = conn.transaction()?;
+    id.delete_with(&tx, mode)?;
+    tx.commit()?;
+    Ok(())
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn export_schema_document(
+    pool_wrapper: State<'_, PoolWrapper>,
+    format: DocumentFormat,
+) -> Result<String, Error> {
+    let mut conn = pool_wrapper.pool.get()?;
+    let tx = conn.transaction()?;
+    let res = export_schemas(&tx)?.to_text(format)?;
+    tx.commit()?;
+    Ok(res)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub fn import_schema_document(
+    pool_wrapper: State<'_, PoolWrapper>,
+    text: String,
+    format: DocumentFormat,
+) -> Result<ImportReport, Error> {
+    let mut conn = pool_wrapper.pool.get()?;
+    let tx = conn.transaction()?;
+    let res = import_schemas(&tx, &SchemaDocument::from_text(&text, format)?)?;
+    tx.commit()?;
+    Ok(res)
+}
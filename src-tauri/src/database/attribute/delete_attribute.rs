@@ -1,6 +1,13 @@
 use rusqlite::{params, Error, Transaction};
 
-use crate::{database::Delete, models::attribute::GenericAttributeId};
+use crate::{
+    database::{
+        attribute_schema::RawAttributeSchema, attribute_type::ATTRIBUTE_TABLES, Delete, Get,
+    },
+    models::{
+        attribute::GenericAttributeId, attribute_schema::AttributeSchemaId, entity::EntityId,
+    },
+};
 
 fn check_exists(tx: &Transaction, table: &str, id: &GenericAttributeId) -> rusqlite::Result<bool> {
     let mut stmt = tx.prepare(&format!("SELECT id FROM {table} WHERE id = ?"))?;
@@ -8,26 +15,36 @@ fn check_exists(tx: &Transaction, table: &str, id: &GenericAttributeId) -> rusql
 }
 
 fn delete(tx: &Transaction, table: &str, id: &GenericAttributeId) -> rusqlite::Result<()> {
+    let (schema, entity): (AttributeSchemaId, EntityId) = tx.query_row(
+        &format!("SELECT schema, entity FROM {table} WHERE id = ?"),
+        params![id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    RawAttributeSchema::get(tx, &schema)?.check_item_change(tx, &entity, -1)?;
+
     let mut stmt = tx.prepare(&format!("DELETE FROM {table} WHERE id = ?"))?;
     stmt.execute(params![id])?;
     return Ok(());
 }
 
-impl Delete for GenericAttributeId {
-    fn delete(self, tx: &Transaction) -> rusqlite::Result<()> {
-        let is_text = check_exists(tx, "text_attribute", &self)?;
-
-        if is_text {
-            return delete(tx, "text_attribute", &self);
+// The table holding a value, as only its id is known
+pub(super) fn find_table(
+    tx: &Transaction,
+    id: &GenericAttributeId,
+) -> rusqlite::Result<&'static str> {
+    for table in ATTRIBUTE_TABLES {
+        if check_exists(tx, table, id)? {
+            return Ok(table);
         }
+    }
 
-        let is_ref = check_exists(tx, "reference_attribute", &self)?;
-
-        if is_ref {
-            return delete(tx, "reference_attribute", &self);
-        }
+    Err(Error::QueryReturnedNoRows)
+}
 
-        Err(Error::QueryReturnedNoRows)
+impl Delete for GenericAttributeId {
+    fn delete(self, tx: &Transaction) -> rusqlite::Result<()> {
+        let table = find_table(tx, &self)?;
+        delete(tx, table, &self)
     }
 }
 
@@ -41,7 +58,10 @@ mod tests {
             test::test_util::{setup, ASD, ESD, RSD},
             Delete,
         },
-        models::{attribute::GenericAttributeId, attribute_schema::Quantity},
+        models::{
+            attribute::GenericAttributeId, attribute_schema::Quantity,
+            attribute_type::SimpleAttributeType,
+        },
     };
 
     use super::check_exists;
@@ -79,6 +99,42 @@ mod tests {
         assert_eq!(exists, false);
     }
 
+    #[test]
+    fn delete_integer() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let es = ESD::create_default(&tx);
+        let attr = ASD::default()
+            .quantity(Quantity::Optional)
+            .attr_type(SimpleAttributeType::Integer)
+            .create(&tx, &es);
+
+        let data = serde_json::from_str(&format!(
+            r#"
+            {{
+              "{attr}": 42
+            }}
+            "#
+        ))
+        .unwrap();
+
+        let entity_id = add_entity(&tx, &es, data).unwrap();
+
+        let id: GenericAttributeId = tx
+            .query_row(
+                "SELECT id FROM integer_attribute WHERE entity = ?",
+                params![entity_id],
+                |r| r.get(0),
+            )
+            .unwrap();
+
+        id.clone().delete(&tx).unwrap();
+
+        let exists = check_exists(&tx, "integer_attribute", &id).unwrap();
+        assert_eq!(exists, false);
+    }
+
     #[test]
     fn delete_ref() {
         let mut conn = setup();
@@ -1,14 +1,110 @@
 use std::collections::HashMap;
 
-use rusqlite::{params_from_iter, ParamsFromIter, Statement, ToSql, Transaction};
-use serde_json::Value;
+use rusqlite::{
+    params_from_iter, types::ValueRef, Error, ParamsFromIter, Row, Statement, ToSql, Transaction,
+};
+use serde_json::{json, Map, Value};
 
 use crate::{
     database::response_map::ResponseMap,
-    models::{attribute_schema::AttributeSchemaId, entity::EntityId},
+    models::{
+        attribute_schema::AttributeSchemaId,
+        attribute_type::{Aggregate, RollupAttribute},
+        entity::EntityId,
+    },
 };
 
 pub fn get_text_attrs(
+    tx: &Transaction,
+    map: Option<ResponseMap>,
+    entities: &Vec<&EntityId>,
+    attrs: &Vec<&AttributeSchemaId>,
+) -> rusqlite::Result<Option<ResponseMap>> {
+    get_simple_attrs(tx, map, "text_attribute", entities, attrs, |row| {
+        Ok(Value::String(row.get(2)?))
+    })
+}
+
+pub fn get_integer_attrs(
+    tx: &Transaction,
+    map: Option<ResponseMap>,
+    entities: &Vec<&EntityId>,
+    attrs: &Vec<&AttributeSchemaId>,
+) -> rusqlite::Result<Option<ResponseMap>> {
+    get_simple_attrs(tx, map, "integer_attribute", entities, attrs, |row| {
+        let value: i64 = row.get(2)?;
+        Ok(Value::from(value))
+    })
+}
+
+pub fn get_number_attrs(
+    tx: &Transaction,
+    map: Option<ResponseMap>,
+    entities: &Vec<&EntityId>,
+    attrs: &Vec<&AttributeSchemaId>,
+) -> rusqlite::Result<Option<ResponseMap>> {
+    get_simple_attrs(tx, map, "number_attribute", entities, attrs, |row| {
+        let value: f64 = row.get(2)?;
+        Ok(Value::from(value))
+    })
+}
+
+pub fn get_date_attrs(
+    tx: &Transaction,
+    map: Option<ResponseMap>,
+    entities: &Vec<&EntityId>,
+    attrs: &Vec<&AttributeSchemaId>,
+) -> rusqlite::Result<Option<ResponseMap>> {
+    get_simple_attrs(tx, map, "date_attribute", entities, attrs, |row| {
+        Ok(Value::String(row.get(2)?))
+    })
+}
+
+pub fn get_datetime_attrs(
+    tx: &Transaction,
+    map: Option<ResponseMap>,
+    entities: &Vec<&EntityId>,
+    attrs: &Vec<&AttributeSchemaId>,
+) -> rusqlite::Result<Option<ResponseMap>> {
+    get_simple_attrs(tx, map, "datetime_attribute", entities, attrs, |row| {
+        Ok(Value::String(row.get(2)?))
+    })
+}
+
+pub fn get_boolean_attrs(
+    tx: &Transaction,
+    map: Option<ResponseMap>,
+    entities: &Vec<&EntityId>,
+    attrs: &Vec<&AttributeSchemaId>,
+) -> rusqlite::Result<Option<ResponseMap>> {
+    get_simple_attrs(tx, map, "boolean_attribute", entities, attrs, |row| {
+        Ok(Value::Bool(row.get(2)?))
+    })
+}
+
+pub fn get_select_attrs(
+    tx: &Transaction,
+    map: Option<ResponseMap>,
+    entities: &Vec<&EntityId>,
+    attrs: &Vec<&AttributeSchemaId>,
+) -> rusqlite::Result<Option<ResponseMap>> {
+    get_simple_attrs(tx, map, "select_attribute", entities, attrs, |row| {
+        Ok(Value::String(row.get(2)?))
+    })
+}
+
+pub fn get_url_attrs(
+    tx: &Transaction,
+    map: Option<ResponseMap>,
+    entities: &Vec<&EntityId>,
+    attrs: &Vec<&AttributeSchemaId>,
+) -> rusqlite::Result<Option<ResponseMap>> {
+    get_simple_attrs(tx, map, "url_attribute", entities, attrs, |row| {
+        Ok(Value::String(row.get(2)?))
+    })
+}
+
+pub fn get_attachment_attrs(
     tx: &Transaction,
     mut map: Option<ResponseMap>,
     entities: &Vec<&EntityId>,
@@ -16,11 +112,90 @@ pub fn get_text_attrs(
 ) -> rusqlite::Result<Option<ResponseMap>> {
     assert_ne!(entities.len(), 0);
 
-    if attrs.len() == 0 {
+    if attrs.is_empty() {
+        return Ok(map);
+    }
+
+    let mut statement = tx.prepare(&build_attachment_request(entities.len(), attrs.len()))?;
+    let params = get_params(entities, attrs);
+
+    let mut rows = statement.query(params)?;
+
+    while let Some(row) = rows.next()? {
+        let entity: EntityId = row.get(0)?;
+        let attribute = row.get(1)?;
+        let hash: String = row.get(2)?;
+        let filename: String = row.get(3)?;
+        let mime: String = row.get(4)?;
+        let size: u64 = row.get(5)?;
+
+        let value = json!({
+            "hash": hash,
+            "filename": filename,
+            "mime": mime,
+            "size": size,
+        });
+
+        map = ResponseMap::add(map, entity, attribute, value);
+    }
+
+    Ok(map)
+}
+
+pub fn get_location_attrs(
+    tx: &Transaction,
+    mut map: Option<ResponseMap>,
+    entities: &Vec<&EntityId>,
+    attrs: &Vec<&AttributeSchemaId>,
+) -> rusqlite::Result<Option<ResponseMap>> {
+    assert_ne!(entities.len(), 0);
+
+    if attrs.is_empty() {
+        return Ok(map);
+    }
+
+    let mut statement = tx.prepare(&build_location_request(entities.len(), attrs.len()))?;
+    let params = get_params(entities, attrs);
+
+    let mut rows = statement.query(params)?;
+
+    while let Some(row) = rows.next()? {
+        let entity: EntityId = row.get(0)?;
+        let attribute = row.get(1)?;
+        let latitude: f64 = row.get(2)?;
+        let longitude: f64 = row.get(3)?;
+        let label: Option<String> = row.get(4)?;
+
+        let value = json!({
+            "latitude": latitude,
+            "longitude": longitude,
+            "label": label,
+        });
+
+        map = ResponseMap::add(map, entity, attribute, value);
+    }
+
+    Ok(map)
+}
+
+fn get_simple_attrs<F>(
+    tx: &Transaction,
+    mut map: Option<ResponseMap>,
+    attr_table: &str,
+    entities: &Vec<&EntityId>,
+    attrs: &Vec<&AttributeSchemaId>,
+    to_value: F,
+) -> rusqlite::Result<Option<ResponseMap>>
+where
+    F: Fn(&Row) -> rusqlite::Result<Value>,
+{
+    assert_ne!(entities.len(), 0);
+
+    if attrs.is_empty() {
         return Ok(map);
     }
 
-    let mut statement = prepare(tx, "text_attribute", entities.len(), attrs.len())?;
+    let mut statement = prepare(tx, attr_table, entities.len(), attrs.len())?;
     let params = get_params(entities, attrs);
 
     let mut rows = statement.query(params)?;
@@ -28,7 +203,7 @@ pub fn get_text_attrs(
     while let Some(row) = rows.next()? {
         let entity: EntityId = row.get(0)?;
         let attribute = row.get(1)?;
-        let value = Value::String(row.get(2)?);
+        let value = to_value(row)?;
 
         map = ResponseMap::add(map, entity, attribute, value);
     }
@@ -36,11 +211,25 @@ pub fn get_text_attrs(
     Ok(map)
 }
 
+// A linked entity along with the properties stored on the link
+#[derive(Debug, PartialEq)]
+pub struct Edge {
+    pub entity: EntityId,
+    pub properties: Map<String, Value>,
+}
+
+fn edge_properties(row: &Row, index: usize) -> rusqlite::Result<Map<String, Value>> {
+    match row.get(index)? {
+        Some(Value::Object(properties)) => Ok(properties),
+        _ => Ok(Map::new()),
+    }
+}
+
 pub fn get_reference_attrs(
     tx: &Transaction,
     entities: &Vec<&EntityId>,
     attr: &AttributeSchemaId,
-) -> rusqlite::Result<HashMap<EntityId, Vec<EntityId>>> {
+) -> rusqlite::Result<HashMap<EntityId, Vec<Edge>>> {
     assert_ne!(entities.len(), 0);
 
     let mut statement = tx.prepare(&build_ref_request(entities.len()))?;
@@ -54,19 +243,165 @@ pub fn get_reference_attrs(
         let parent_entity: EntityId = row.get(0)?;
         let value: EntityId = row.get(1)?;
 
-        let parent: &mut Vec<EntityId> = map.entry(parent_entity).or_default();
+        let parent: &mut Vec<Edge> = map.entry(parent_entity).or_default();
+
+        parent.push(Edge {
+            entity: value,
+            properties: edge_properties(row, 2)?,
+        });
+    }
+
+    Ok(map)
+}
+
+// The entities pointing at each of the given entities through the reference
+pub fn get_backlink_attrs(
+    tx: &Transaction,
+    entities: &Vec<&EntityId>,
+    reference: &AttributeSchemaId,
+) -> rusqlite::Result<HashMap<EntityId, Vec<Edge>>> {
+    assert_ne!(entities.len(), 0);
+
+    let mut statement = tx.prepare(&build_backlink_request(entities.len()))?;
+
+    let params = get_ref_params(entities, reference);
+    let mut rows = statement.query(params)?;
+
+    let mut map = HashMap::new();
+
+    while let Some(row) = rows.next()? {
+        let target: EntityId = row.get(0)?;
+        let source: EntityId = row.get(1)?;
+
+        let target: &mut Vec<Edge> = map.entry(target).or_default();
+
+        target.push(Edge {
+            entity: source,
+            properties: edge_properties(row, 2)?,
+        });
+    }
+
+    Ok(map)
+}
+
+// Aggregates the rollup for every entity in a single query. Entities without
+// any linked values get the aggregate of nothing
+pub fn get_rollup_attrs(
+    tx: &Transaction,
+    entities: &Vec<&EntityId>,
+    rollup: &RollupAttribute,
+) -> rusqlite::Result<HashMap<EntityId, Value>> {
+    assert_ne!(entities.len(), 0);
+
+    let table = rollup.value_table(tx)?;
+
+    let mut statement = tx.prepare(&build_rollup_request(
+        table,
+        rollup.aggregate,
+        entities.len(),
+    ))?;
+
+    let mut params: Vec<&dyn ToSql> = Vec::new();
+    if let (Some(_), Some(attribute)) = (table, &rollup.attribute) {
+        params.push(attribute);
+    }
+    for entity in entities {
+        params.push(entity);
+    }
+    params.push(&rollup.reference);
+
+    let mut rows = statement.query(params_from_iter(params))?;
+
+    let mut map = HashMap::new();
+
+    while let Some(row) = rows.next()? {
+        let entity: EntityId = row.get(0)?;
+        let value = rollup_value(row.get_ref(1)?, rollup.aggregate)?;
+
+        map.insert(entity, value);
+    }
 
-        parent.push(value);
+    for entity in entities {
+        if !map.contains_key(*entity) {
+            map.insert((*entity).clone(), empty_rollup(rollup.aggregate));
+        }
     }
 
     Ok(map)
 }
 
+fn build_rollup_request(table: Option<&str>, aggregate: Aggregate, num_entities: usize) -> String {
+    let entity_part = build_question_marks(num_entities);
+
+    let join = match table {
+        Some(table) => format!("LEFT JOIN {table} v ON v.entity = r.value AND v.schema = ?"),
+        None => String::new(),
+    };
+
+    let value = match table {
+        Some(_) => "v.value",
+        None => "r.value",
+    };
+
+    let aggregate = match aggregate {
+        Aggregate::Count => format!("COUNT({value})"),
+        Aggregate::Sum => format!("TOTAL({value})"),
+        Aggregate::Min => format!("MIN({value})"),
+        Aggregate::Max => format!("MAX({value})"),
+        Aggregate::Average => format!("AVG({value})"),
+        Aggregate::Distinct => format!(
+            "json_group_array(DISTINCT {value} ORDER BY {value}) FILTER (WHERE {value} IS NOT NULL)"
+        ),
+    };
+
+    format!(
+        "SELECT r.entity, {aggregate} FROM reference_attribute r {join} WHERE r.entity IN ({entity_part}) AND r.schema = ? GROUP BY r.entity"
+    )
+}
+
+fn rollup_value(value: ValueRef, aggregate: Aggregate) -> rusqlite::Result<Value> {
+    match value {
+        ValueRef::Null => Ok(Value::Null),
+        ValueRef::Integer(value) => Ok(Value::from(value)),
+        ValueRef::Real(value) => Ok(Value::from(value)),
+        ValueRef::Text(..) if aggregate == Aggregate::Distinct => {
+            serde_json::from_str(value.as_str()?).map_err(|_| Error::InvalidQuery)
+        }
+        ValueRef::Text(..) => Ok(Value::String(value.as_str()?.to_string())),
+        ValueRef::Blob(..) => Err(Error::InvalidQuery),
+    }
+}
+
+fn empty_rollup(aggregate: Aggregate) -> Value {
+    match aggregate {
+        Aggregate::Count => Value::from(0),
+        Aggregate::Sum => Value::from(0.0),
+        Aggregate::Distinct => Value::Array(vec![]),
+        Aggregate::Min | Aggregate::Max | Aggregate::Average => Value::Null,
+    }
+}
+
 fn build_request(attr_table: &str, num_entities: usize, num_attrs: usize) -> String {
     let entity_part = build_question_marks(num_entities);
     let attr_part = build_question_marks(num_attrs);
     format!(
-        "SELECT a.entity, a.schema, a.value FROM {attr_table} a LEFT JOIN entity e on a.entity = e.id WHERE e.id IN ({entity_part}) AND a.schema IN ({attr_part}) ORDER BY a.entity, a.schema, a.value"
+        "SELECT a.entity, a.schema, a.value FROM {attr_table} a LEFT JOIN entity e on a.entity = e.id WHERE e.id IN ({entity_part}) AND a.schema IN ({attr_part}) ORDER BY a.entity, a.schema, a.position"
+    )
+}
+
+fn build_attachment_request(num_entities: usize, num_attrs: usize) -> String {
+    let entity_part = build_question_marks(num_entities);
+    let attr_part = build_question_marks(num_attrs);
+    format!(
+        "SELECT a.entity, a.schema, a.value, a.filename, a.mime, b.size FROM attachment_attribute a INNER JOIN blob b ON a.value = b.hash WHERE a.entity IN ({entity_part}) AND a.schema IN ({attr_part}) ORDER BY a.entity, a.schema, a.position"
+    )
+}
+
+fn build_location_request(num_entities: usize, num_attrs: usize) -> String {
+    let entity_part = build_question_marks(num_entities);
+    let attr_part = build_question_marks(num_attrs);
+    format!(
+        "SELECT a.entity, a.schema, a.value, a.longitude, a.label FROM location_attribute a WHERE a.entity IN ({entity_part}) AND a.schema IN ({attr_part}) ORDER BY a.entity, a.schema, a.position"
     )
 }
 
@@ -82,11 +417,11 @@ fn prepare<'a>(
 fn get_params<'a>(
     entities: &'a Vec<&EntityId>,
     attrs: &'a Vec<&AttributeSchemaId>,
-) -> ParamsFromIter<Vec<&'a (dyn ToSql)>> {
+) -> ParamsFromIter<Vec<&'a dyn ToSql>> {
     assert_ne!(entities.len(), 0);
     assert_ne!(attrs.len(), 0);
 
-    let mut params: Vec<&(dyn ToSql)> = Vec::new();
+    let mut params: Vec<&dyn ToSql> = Vec::new();
 
     for entity in entities {
         params.push(entity);
@@ -102,17 +437,24 @@ fn get_params<'a>(
 fn build_ref_request(num_entities: usize) -> String {
     let entity_part = build_question_marks(num_entities);
     format!(
-        "SELECT entity, value FROM reference_attribute WHERE entity IN ({entity_part}) AND schema=? ORDER BY entity"
+        "SELECT entity, value, properties FROM reference_attribute WHERE entity IN ({entity_part}) AND schema=? ORDER BY entity, position"
+    )
+}
+
+fn build_backlink_request(num_entities: usize) -> String {
+    let entity_part = build_question_marks(num_entities);
+    format!(
+        "SELECT value, entity, properties FROM reference_attribute WHERE value IN ({entity_part}) AND schema=? ORDER BY value, created"
     )
 }
 
 fn get_ref_params<'a>(
     entities: &'a Vec<&EntityId>,
     attr: &'a AttributeSchemaId,
-) -> ParamsFromIter<Vec<&'a (dyn ToSql)>> {
+) -> ParamsFromIter<Vec<&'a dyn ToSql>> {
     assert_ne!(entities.len(), 0);
 
-    let mut params: Vec<&(dyn ToSql)> = Vec::new();
+    let mut params: Vec<&dyn ToSql> = Vec::new();
 
     for entity in entities {
         params.push(entity);
@@ -135,7 +477,7 @@ mod tests {
     use crate::{
         database::{entity::add_entity, test::test_util::setup, New},
         models::{
-            attribute_schema::{AttributeSchema, CreateAttributeSchema, Quantity},
+            attribute_schema::{AttributeSchema, Constraints, CreateAttributeSchema, Quantity},
             attribute_type::{CreateAttributeType, CreateReferenceAttribute},
             entity_schema::{CreateEntitySchema, EntitySchema},
         },
@@ -147,14 +489,14 @@ mod tests {
     fn build_simple_request() {
         let result = build_request("text_attribute", 1, 1);
 
-        assert_eq!(result, "SELECT a.entity, a.schema, a.value FROM text_attribute a LEFT JOIN entity e on a.entity = e.id WHERE e.id IN (?) AND a.schema IN (?) ORDER BY a.entity, a.schema, a.value");
+        assert_eq!(result, "SELECT a.entity, a.schema, a.value FROM text_attribute a LEFT JOIN entity e on a.entity = e.id WHERE e.id IN (?) AND a.schema IN (?) ORDER BY a.entity, a.schema, a.position");
     }
 
     #[test]
     fn build_multi_request() {
         let result = build_request("text_attribute", 3, 2);
 
-        assert_eq!(result, "SELECT a.entity, a.schema, a.value FROM text_attribute a LEFT JOIN entity e on a.entity = e.id WHERE e.id IN (?,?,?) AND a.schema IN (?,?) ORDER BY a.entity, a.schema, a.value");
+        assert_eq!(result, "SELECT a.entity, a.schema, a.value FROM text_attribute a LEFT JOIN entity e on a.entity = e.id WHERE e.id IN (?,?,?) AND a.schema IN (?,?) ORDER BY a.entity, a.schema, a.position");
     }
 
     #[test]
@@ -166,6 +508,7 @@ mod tests {
             &tx,
             CreateEntitySchema {
                 name: "Parent".to_string(),
+                parent: None,
             },
         )
         .unwrap()
@@ -175,6 +518,7 @@ mod tests {
             &tx,
             CreateEntitySchema {
                 name: "Child".to_string(),
+                parent: None,
             },
         )
         .unwrap()
@@ -186,8 +530,13 @@ mod tests {
                 entity: parent_schema.clone(),
                 name: "Child".to_string(),
                 quantity: Quantity::Required,
+                constraints: Constraints::default(),
+                default: None,
+                unique: false,
                 attr_type: CreateAttributeType::Reference(CreateReferenceAttribute {
-                    id: child_schema.clone(),
+                    targets: vec![child_schema.clone()],
+                    inverse: None,
+                    edges: Vec::new(),
                 }),
             },
         )
@@ -221,6 +570,6 @@ mod tests {
 
         assert_eq!(children.len(), 1);
         let child = children.get(0).unwrap();
-        assert_eq!(child, &child_id);
+        assert_eq!(child.entity, child_id);
     }
 }
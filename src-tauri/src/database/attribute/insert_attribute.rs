@@ -1,22 +1,134 @@
-use rusqlite::Transaction;
-use serde_json::Value;
+use rusqlite::{Error, Transaction};
+use serde_json::{Map, Number, Value};
 
 use crate::{
     database::{attribute_schema::RawAttributeSchema, Insert},
     models::{
+        attribute_schema::Quantity,
         attribute_type::{AttributeType, SimpleAttributeType},
         entity::EntityId,
     },
+    units::parse_measure,
 };
 
+fn computed_error() -> Error {
+    Error::ModuleError("Cannot set a computed field".to_string())
+}
+
+fn rollup_error() -> Error {
+    Error::ModuleError("Cannot set a rollup field".to_string())
+}
+
+fn backlink_error() -> Error {
+    Error::ModuleError("Cannot set a backlink field".to_string())
+}
+
+impl RawAttributeSchema {
+    fn check_string(&self, val: &str) -> rusqlite::Result<()> {
+        match &self.attr_type {
+            AttributeType::Measure(measure) => self
+                .constraints
+                .check_number(&self.name, parse_measure(val, &measure.unit)?),
+            _ => self.constraints.check_text(&self.name, val),
+        }
+    }
+
+    fn check_number(&self, val: &Number) -> rusqlite::Result<()> {
+        match val.as_f64() {
+            Some(val) => self.constraints.check_number(&self.name, val),
+            None => Ok(()),
+        }
+    }
+}
+
 impl Insert<EntityId, String> for RawAttributeSchema {
     fn insert(&self, tx: &Transaction, entity: &EntityId, val: &String) -> rusqlite::Result<()> {
+        self.check_string(val)?;
+        self.check_item_change(tx, entity, 1)?;
+
         match &self.attr_type {
             AttributeType::Reference(reference) => {
-                let target: EntityId = val.try_into().unwrap(); // TODO
-                reference.insert_reference(tx, entity, &self.id, &target)
+                let target: EntityId = val.try_into().map_err(|_| {
+                    Error::ModuleError("Provided value is not a valid entity id".to_string())
+                })?;
+                reference.insert_reference(tx, entity, &self.id, &target, &Map::new())
             }
             AttributeType::Simple(simple) => simple.insert_string(tx, entity, &self.id, val),
+            AttributeType::Select(select) => select.insert_option(tx, entity, &self.id, val),
+            AttributeType::Measure(measure) => measure.insert_string(tx, entity, &self.id, val),
+            AttributeType::Computed(..) => Err(computed_error()),
+            AttributeType::Rollup(..) => Err(rollup_error()),
+            AttributeType::Backlink(..) => Err(backlink_error()),
+        }
+    }
+}
+
+impl Insert<EntityId, Number> for RawAttributeSchema {
+    fn insert(&self, tx: &Transaction, entity: &EntityId, val: &Number) -> rusqlite::Result<()> {
+        self.check_number(val)?;
+        self.check_item_change(tx, entity, 1)?;
+
+        match &self.attr_type {
+            AttributeType::Reference(..) => Err(Error::ModuleError(
+                "Provided a number to a reference field".to_string(),
+            )),
+            AttributeType::Select(..) => Err(Error::ModuleError(
+                "Provided a number to a select field".to_string(),
+            )),
+            AttributeType::Measure(measure) => measure.insert_number(tx, entity, &self.id, val),
+            AttributeType::Computed(..) => Err(computed_error()),
+            AttributeType::Rollup(..) => Err(rollup_error()),
+            AttributeType::Backlink(..) => Err(backlink_error()),
+            AttributeType::Simple(simple) => simple.insert_number(tx, entity, &self.id, val),
+        }
+    }
+}
+
+impl Insert<EntityId, bool> for RawAttributeSchema {
+    fn insert(&self, tx: &Transaction, entity: &EntityId, val: &bool) -> rusqlite::Result<()> {
+        self.check_item_change(tx, entity, 1)?;
+
+        match &self.attr_type {
+            AttributeType::Reference(..) => Err(Error::ModuleError(
+                "Provided a boolean to a reference field".to_string(),
+            )),
+            AttributeType::Select(..) => Err(Error::ModuleError(
+                "Provided a boolean to a select field".to_string(),
+            )),
+            AttributeType::Measure(..) => Err(Error::ModuleError(
+                "Provided a boolean to a measure field".to_string(),
+            )),
+            AttributeType::Computed(..) => Err(computed_error()),
+            AttributeType::Rollup(..) => Err(rollup_error()),
+            AttributeType::Backlink(..) => Err(backlink_error()),
+            AttributeType::Simple(simple) => simple.insert_bool(tx, entity, &self.id, *val),
+        }
+    }
+}
+
+impl Insert<EntityId, Map<String, Value>> for RawAttributeSchema {
+    fn insert(
+        &self,
+        tx: &Transaction,
+        entity: &EntityId,
+        val: &Map<String, Value>,
+    ) -> rusqlite::Result<()> {
+        self.check_item_change(tx, entity, 1)?;
+
+        match &self.attr_type {
+            AttributeType::Reference(reference) => {
+                reference.insert_link(tx, entity, &self.id, &Value::Object(val.clone()))
+            }
+            AttributeType::Select(..) => Err(Error::ModuleError(
+                "Provided an object to a select field".to_string(),
+            )),
+            AttributeType::Measure(..) => Err(Error::ModuleError(
+                "Provided an object to a measure field".to_string(),
+            )),
+            AttributeType::Computed(..) => Err(computed_error()),
+            AttributeType::Rollup(..) => Err(rollup_error()),
+            AttributeType::Backlink(..) => Err(backlink_error()),
+            AttributeType::Simple(simple) => simple.insert_object(tx, entity, &self.id, val),
         }
     }
 }
@@ -28,18 +140,63 @@ impl Insert<EntityId, Vec<Value>> for RawAttributeSchema {
         entity: &EntityId,
         vals: &Vec<Value>,
     ) -> rusqlite::Result<()> {
-        match self.attr_type {
-            AttributeType::Reference(..) => todo!(),
+        for val in vals {
+            match val {
+                Value::String(val) => self.check_string(val),
+                Value::Number(val) => self.check_number(val),
+                _ => Ok(()),
+            }?;
+        }
+        self.check_item_change(tx, entity, vals.len() as i64)?;
+
+        match &self.attr_type {
+            AttributeType::Reference(reference) => {
+                reference.insert_reference_vec(tx, entity, &self.id, vals)
+            }
+            AttributeType::Select(select) => select.insert_option_vec(tx, entity, &self.id, vals),
+            AttributeType::Measure(measure) => measure.insert_vec(tx, entity, &self.id, vals),
+            AttributeType::Computed(..) => Err(computed_error()),
+            AttributeType::Rollup(..) => Err(rollup_error()),
+            AttributeType::Backlink(..) => Err(backlink_error()),
             AttributeType::Simple(simple) => match simple {
                 SimpleAttributeType::Longform => todo!(),
                 SimpleAttributeType::Text | SimpleAttributeType::RichText => {
                     simple.insert_string_vec(tx, entity, &self.id, vals)
                 }
+                SimpleAttributeType::Integer
+                | SimpleAttributeType::Number
+                | SimpleAttributeType::Date
+                | SimpleAttributeType::DateTime
+                | SimpleAttributeType::Boolean
+                | SimpleAttributeType::Url
+                | SimpleAttributeType::Attachment
+                | SimpleAttributeType::Location => simple.insert_vec(tx, entity, &self.id, vals),
             },
         }
     }
 }
 
+// Inserts a value of any shape, checking it fits the quantity of the field
+impl Insert<EntityId, Value> for RawAttributeSchema {
+    fn insert(&self, tx: &Transaction, entity: &EntityId, val: &Value) -> rusqlite::Result<()> {
+        match (&self.quantity, val) {
+            // An explicit null leaves an optional field unset
+            (Quantity::Optional, Value::Null) => Ok(()),
+            (Quantity::Required | Quantity::List, Value::Null) => Err(Error::ModuleError(
+                "Provided null to a non-optional field".to_string(),
+            )),
+            (Quantity::Required | Quantity::Optional, Value::Array(..)) => Err(Error::ModuleError(
+                "Provided a list to a non-list field".to_string(),
+            )),
+            (_, Value::String(val)) => self.insert(tx, entity, val),
+            (_, Value::Number(val)) => self.insert(tx, entity, val),
+            (_, Value::Bool(val)) => self.insert(tx, entity, val),
+            (_, Value::Object(val)) => self.insert(tx, entity, val),
+            (Quantity::List, Value::Array(vals)) => self.insert(tx, entity, vals),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
@@ -0,0 +1,341 @@
+use std::cmp::Ordering;
+
+use rusqlite::{params, Error, Result, Transaction};
+use serde_json::Value;
+
+use crate::{
+    database::{
+        attribute_schema::RawAttributeSchema, attribute_type::value_table, Delete, Get, Insert,
+    },
+    models::{
+        attribute::GenericAttributeId,
+        attribute_schema::{AttributeSchemaId, Quantity},
+        entity::EntityId,
+        entity_schema::EntitySchemaId,
+    },
+};
+
+use super::delete_attribute::find_table;
+
+fn not_list() -> Error {
+    Error::ModuleError("Attribute is not a list field".to_string())
+}
+
+// The table of a list attribute's values
+fn list_table(schema: &RawAttributeSchema) -> Result<&'static str> {
+    if schema.quantity != Quantity::List {
+        return Err(not_list());
+    }
+
+    value_table(&schema.attr_type).ok_or_else(not_list)
+}
+
+// Checks the attribute belongs to the entity's schema or one it extends
+fn check_owner(tx: &Transaction, entity: &EntityId, schema: &AttributeSchemaId) -> Result<()> {
+    let entity_schema: EntitySchemaId = tx.query_row(
+        "SELECT schema FROM entity WHERE id = ?1",
+        params![entity],
+        |row| row.get(0),
+    )?;
+    let owner: EntitySchemaId = tx.query_row(
+        "SELECT entity FROM attribute_schema WHERE id = ?1",
+        params![schema],
+        |row| row.get(0),
+    )?;
+
+    if !entity_schema.ancestors(tx)?.contains(&owner) {
+        return Err(Error::ModuleError(
+            "Attribute is not a field of the entity's schema".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+// The ids and positions of an entity's values for the attribute, in list
+// order. Positions can have gaps left by removed items
+fn list_items(
+    tx: &Transaction,
+    table: &str,
+    entity: &EntityId,
+    schema: &AttributeSchemaId,
+) -> Result<Vec<(GenericAttributeId, i64)>> {
+    let mut statement = tx.prepare(&format!(
+        "SELECT id, position FROM {table} WHERE entity = ?1 AND schema = ?2 ORDER BY position, created"
+    ))?;
+
+    let items = statement
+        .query_map(params![entity, schema], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?
+        .collect::<Result<_>>()?;
+
+    Ok(items)
+}
+
+// Moves the item to the position, shifting the items from there up to where
+// it was by one so only the affected range is rewritten
+fn place(
+    tx: &Transaction,
+    table: &str,
+    entity: &EntityId,
+    schema: &AttributeSchemaId,
+    (item, from): &(GenericAttributeId, i64),
+    to: i64,
+) -> Result<()> {
+    let (shift, low, high) = match to.cmp(from) {
+        Ordering::Equal => return Ok(()),
+        Ordering::Less => (1, to, from - 1),
+        Ordering::Greater => (-1, from + 1, to),
+    };
+
+    tx.execute(
+        &format!(
+            "UPDATE {table} SET position = position + ?1
+              WHERE entity = ?2 AND schema = ?3 AND position BETWEEN ?4 AND ?5"
+        ),
+        params![shift, entity, schema, low, high],
+    )?;
+    tx.execute(
+        &format!("UPDATE {table} SET position = ?1 WHERE id = ?2"),
+        params![to, item],
+    )?;
+
+    Ok(())
+}
+
+// Adds a value to an entity's list at the index, or at the end when the index
+// is past it
+pub fn insert_at(
+    tx: &Transaction,
+    entity: &EntityId,
+    schema: &AttributeSchemaId,
+    index: usize,
+    value: &Value,
+) -> Result<()> {
+    let schema = RawAttributeSchema::get(tx, schema)?;
+    let table = list_table(&schema)?;
+    check_owner(tx, entity, &schema.id)?;
+
+    if let Value::Array(..) = value {
+        return Err(Error::ModuleError(
+            "List items are inserted one at a time".to_string(),
+        ));
+    }
+
+    schema.insert(tx, entity, value)?;
+
+    // The new value was appended, so it's the last item
+    let mut items = list_items(tx, table, entity, &schema.id)?;
+    let Some(item) = items.pop() else {
+        return Ok(());
+    };
+
+    match items.get(index) {
+        Some((_, position)) => place(tx, table, entity, &schema.id, &item, *position),
+        None => Ok(()),
+    }
+}
+
+pub fn remove_at(
+    tx: &Transaction,
+    entity: &EntityId,
+    schema: &AttributeSchemaId,
+    index: usize,
+) -> Result<()> {
+    let schema = RawAttributeSchema::get(tx, schema)?;
+    let table = list_table(&schema)?;
+    check_owner(tx, entity, &schema.id)?;
+
+    match list_items(tx, table, entity, &schema.id)?
+        .into_iter()
+        .nth(index)
+    {
+        Some((item, _)) => item.delete(tx),
+        None => Err(Error::ModuleError("List index out of range".to_string())),
+    }
+}
+
+impl GenericAttributeId {
+    // Moves the value to the index in its list, shifting the items between
+    pub fn move_to(&self, tx: &Transaction, index: usize) -> Result<()> {
+        let table = find_table(tx, self)?;
+
+        let (entity, schema): (EntityId, AttributeSchemaId) = tx.query_row(
+            &format!("SELECT entity, schema FROM {table} WHERE id = ?1"),
+            params![self],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+
+        list_table(&RawAttributeSchema::get(tx, &schema)?)?;
+
+        let items = list_items(tx, table, &entity, &schema)?;
+        let Some(item) = items.iter().find(|(item, _)| item == self) else {
+            return Err(Error::QueryReturnedNoRows);
+        };
+
+        // The item ends up where the item now at the index is
+        let (_, to) = &items[index.min(items.len() - 1)];
+        place(tx, table, &entity, &schema, item, *to)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::{params, Error, Transaction};
+    use serde_json::{json, Value};
+
+    use crate::{
+        database::{
+            entity::add_entity,
+            test::test_util::{setup, ASD, ESD},
+        },
+        models::{
+            attribute::GenericAttributeId,
+            attribute_schema::{AttributeSchemaId, Quantity},
+            entity::EntityId,
+            entity_schema::EntitySchemaId,
+        },
+    };
+
+    use super::{insert_at, remove_at};
+
+    fn values(tx: &Transaction, entity: &EntityId) -> Vec<String> {
+        let mut statement = tx
+            .prepare("SELECT value FROM text_attribute WHERE entity = ?1 ORDER BY position")
+            .unwrap();
+        statement
+            .query_map(params![entity], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap()
+    }
+
+    fn id_of(tx: &Transaction, value: &str) -> GenericAttributeId {
+        tx.query_row(
+            "SELECT id FROM text_attribute WHERE value = ?1",
+            params![value],
+            |row| row.get(0),
+        )
+        .unwrap()
+    }
+
+    fn position(tx: &Transaction, value: &str) -> i64 {
+        tx.query_row(
+            "SELECT position FROM text_attribute WHERE value = ?1",
+            params![value],
+            |row| row.get(0),
+        )
+        .unwrap()
+    }
+
+    fn create(tx: &Transaction) -> (AttributeSchemaId, EntityId) {
+        let es = ESD::create_default(tx);
+        let attr = ASD::default().quantity(Quantity::List).create(tx, &es);
+        let entity = add_entity(tx, &es, json!({ attr.to_string(): ["A", "B", "C"] })).unwrap();
+
+        (attr, entity)
+    }
+
+    #[test]
+    fn reorder() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let (attr, entity) = create(&tx);
+        assert_eq!(values(&tx, &entity), vec!["A", "B", "C"]);
+
+        insert_at(&tx, &entity, &attr, 1, &Value::from("D")).unwrap();
+        assert_eq!(values(&tx, &entity), vec!["A", "D", "B", "C"]);
+
+        // Past the end appends
+        insert_at(&tx, &entity, &attr, 10, &Value::from("E")).unwrap();
+        assert_eq!(values(&tx, &entity), vec!["A", "D", "B", "C", "E"]);
+
+        id_of(&tx, "C").move_to(&tx, 0).unwrap();
+        assert_eq!(values(&tx, &entity), vec!["C", "A", "D", "B", "E"]);
+
+        id_of(&tx, "A").move_to(&tx, 3).unwrap();
+        assert_eq!(values(&tx, &entity), vec!["C", "D", "B", "A", "E"]);
+
+        remove_at(&tx, &entity, &attr, 1).unwrap();
+        assert_eq!(values(&tx, &entity), vec!["C", "B", "A", "E"]);
+
+        // Moves across the gap left by the removal only shift the items
+        // between, leaving the rest where they are
+        id_of(&tx, "C").move_to(&tx, 1).unwrap();
+        assert_eq!(values(&tx, &entity), vec!["B", "C", "A", "E"]);
+        assert_eq!(position(&tx, "A"), 3);
+        assert_eq!(position(&tx, "E"), 4);
+
+        insert_at(&tx, &entity, &attr, 1, &Value::from("F")).unwrap();
+        assert_eq!(values(&tx, &entity), vec!["B", "F", "C", "A", "E"]);
+
+        assert_eq!(
+            remove_at(&tx, &entity, &attr, 5),
+            Err(Error::ModuleError("List index out of range".to_string()))
+        );
+    }
+
+    #[test]
+    fn not_list_error() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let es = ESD::create_default(&tx);
+        let attr = ASD::default().quantity(Quantity::Optional).create(&tx, &es);
+        let entity = add_entity(&tx, &es, json!({})).unwrap();
+
+        assert_eq!(
+            insert_at(&tx, &entity, &attr, 0, &Value::from("A")),
+            Err(Error::ModuleError(
+                "Attribute is not a list field".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn wrong_schema_error() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let (attr, _) = create(&tx);
+        let other = ESD::default().name("Bar").create(&tx);
+        let entity = add_entity(&tx, &other, json!({})).unwrap();
+
+        let error = Err(Error::ModuleError(
+            "Attribute is not a field of the entity's schema".to_string(),
+        ));
+        assert_eq!(insert_at(&tx, &entity, &attr, 0, &Value::from("A")), error);
+        assert_eq!(remove_at(&tx, &entity, &attr, 0), error);
+
+        let count: usize = tx
+            .query_row(
+                "SELECT COUNT(*) FROM text_attribute WHERE entity = ?1",
+                params![entity],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(count, 0);
+    }
+
+    #[test]
+    fn inherited_list() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let (attr, parent_entity) = create(&tx);
+        let parent: EntitySchemaId = tx
+            .query_row(
+                "SELECT schema FROM entity WHERE id = ?1",
+                params![parent_entity],
+                |row| row.get(0),
+            )
+            .unwrap();
+        let child = ESD::default().name("Bar").parent(&parent).create(&tx);
+        let entity = add_entity(&tx, &child, json!({ attr.to_string(): ["X"] })).unwrap();
+
+        insert_at(&tx, &entity, &attr, 0, &Value::from("Y")).unwrap();
+        assert_eq!(values(&tx, &entity), vec!["Y", "X"]);
+    }
+}
@@ -1,6 +1,8 @@
 mod delete_attribute;
 mod get_attribute;
 mod insert_attribute;
+mod list_order;
 pub mod longform;
 
 pub use get_attribute::*;
+pub use list_order::{insert_at, remove_at};
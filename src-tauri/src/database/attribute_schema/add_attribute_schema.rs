@@ -1,37 +1,186 @@
-use rusqlite::Transaction;
+use rusqlite::{Error, Transaction};
 
 use crate::{
-    database::New,
-    models::attribute_schema::{AttributeSchema, AttributeSchemaId, CreateAttributeSchema},
+    database::{
+        attribute_schema::{GetSchemaMap, RawAttributeSchema},
+        Get, New,
+    },
+    models::{
+        attribute_schema::{
+            AttributeSchema, AttributeSchemaId, Constraints, CreateAttributeSchema, Quantity,
+        },
+        attribute_type::{BacklinkAttribute, CreateAttributeType, CreateReferenceAttribute},
+        entity::EntityId,
+        entity_schema::EntitySchemaId,
+    },
     utils::get_timestamp,
 };
 
 impl New<CreateAttributeSchema> for AttributeSchema {
     fn new(tx: &Transaction, data: CreateAttributeSchema) -> rusqlite::Result<Self> {
         let reference = data.attr_type.get_ref();
+        let rollup = data.attr_type.get_rollup();
+        let backlink = data.attr_type.get_backlink();
+
+        data.entity.check_attribute_name(tx, &data.name)?;
+
+        if let CreateAttributeType::Computed(computed) = &data.attr_type {
+            if data.quantity == Quantity::List {
+                return Err(Error::ModuleError(
+                    "Computed fields cannot be lists".to_string(),
+                ));
+            }
+
+            computed.check(&RawAttributeSchema::get_map(tx, &data.entity)?)?;
+        }
+
+        if let Some(rollup) = rollup {
+            rollup.check(tx, &data.entity)?;
+        }
+
+        if let Some(backlink) = backlink {
+            if data.quantity != Quantity::List {
+                return Err(Error::ModuleError(
+                    "Backlink fields must be lists".to_string(),
+                ));
+            }
+
+            backlink.check(tx, &data.entity)?;
+        }
+
+        if let Some(CreateReferenceAttribute {
+            targets,
+            inverse: Some(..),
+            ..
+        }) = reference
+        {
+            if targets.is_empty() {
+                return Err(Error::ModuleError(
+                    "Inverse attributes need a target schema".to_string(),
+                ));
+            }
+        }
 
         let new_attribute = Self {
             id: AttributeSchemaId::new(),
             name: data.name,
             quantity: data.quantity,
             attr_type: data.attr_type.get_full(tx)?,
+            constraints: data.constraints,
+            default: data.default,
+            unique: data.unique,
         };
 
-        let created_at = get_timestamp();
+        new_attribute
+            .constraints
+            .check_schema(&new_attribute.attr_type, &new_attribute.quantity)?;
 
-        tx.execute(
-            "INSERT INTO attribute_schema (id, entity, name, type, reference, quantity, created, updated) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7)",
-            (
-                &new_attribute.id,
-                data.entity,
-                &new_attribute.name,
-                &data.attr_type,
-                &reference,
+        if let Some(default) = &new_attribute.default {
+            default.check_schema(
+                &new_attribute.attr_type,
                 &new_attribute.quantity,
-                created_at
-            ),
-        )?;
+                new_attribute.unique,
+            )?;
+        }
+
+        if new_attribute.unique {
+            new_attribute.check_unique()?;
+        }
+
+        let backfill = new_attribute.backfill_entities(tx, &data.entity)?;
+
+        // Options and targets are written before the default can be checked
+        // against them, so a failure takes the partly created attribute out
+        tx.execute_batch("SAVEPOINT attribute_creation")?;
+
+        let result = write(tx, &new_attribute, &data.entity, &data.attr_type, &backfill);
+
+        if result.is_err() {
+            tx.execute_batch("ROLLBACK TO attribute_creation")?;
+        }
+        tx.execute_batch("RELEASE attribute_creation")?;
+        result?;
 
         Ok(new_attribute)
     }
 }
+
+// Writes the attribute with its targets, edges, inverse and options, then
+// checks the default against them and fills it in for existing entities
+fn write(
+    tx: &Transaction,
+    new_attribute: &AttributeSchema,
+    entity: &EntitySchemaId,
+    attr_type: &CreateAttributeType,
+    backfill: &[EntityId],
+) -> rusqlite::Result<()> {
+    let reference = attr_type.get_ref();
+    let unit = attr_type.get_unit();
+    let expression = attr_type.get_expression();
+    let rollup = attr_type.get_rollup();
+    let backlink = attr_type.get_backlink();
+
+    let created_at = get_timestamp();
+
+    tx.execute(
+        "INSERT INTO attribute_schema (id, entity, name, type, unit, expression, rollup_reference, rollup_attribute, aggregate, backlink_reference, quantity, constraints, default_value, is_unique, created, updated) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?15)",
+        (
+            &new_attribute.id,
+            entity,
+            &new_attribute.name,
+            attr_type,
+            &unit,
+            &expression,
+            rollup.map(|rollup| &rollup.reference),
+            rollup.and_then(|rollup| rollup.attribute.as_ref()),
+            rollup.map(|rollup| rollup.aggregate),
+            backlink.map(|backlink| &backlink.reference),
+            &new_attribute.quantity,
+            &new_attribute.constraints,
+            &new_attribute.default,
+            new_attribute.unique,
+            created_at
+        ),
+    )?;
+
+    if let Some(reference) = reference {
+        for target in &reference.targets {
+            new_attribute.id.add_target(tx, target)?;
+        }
+
+        for edge in &reference.edges {
+            new_attribute.id.add_edge(tx, edge)?;
+        }
+
+        if let Some(inverse) = &reference.inverse {
+            for target in &reference.targets {
+                AttributeSchema::new(
+                    tx,
+                    CreateAttributeSchema {
+                        entity: target.clone(),
+                        name: inverse.clone(),
+                        attr_type: CreateAttributeType::Backlink(BacklinkAttribute {
+                            reference: new_attribute.id.clone(),
+                        }),
+                        quantity: Quantity::List,
+                        constraints: Constraints::default(),
+                        default: None,
+                        unique: false,
+                    },
+                )?;
+            }
+        }
+    }
+
+    if let CreateAttributeType::Select { options } = attr_type {
+        for option in options {
+            new_attribute.id.add_option(tx, option)?;
+        }
+    }
+
+    let raw = RawAttributeSchema::get(tx, &new_attribute.id)?;
+    raw.check_default(tx, entity)?;
+    raw.backfill(tx, backfill)?;
+
+    Ok(())
+}
@@ -0,0 +1,479 @@
+use regex::Regex;
+use rusqlite::{params, Error, Result, Transaction};
+
+use crate::{
+    database::attribute_type::value_table,
+    models::{
+        attribute_schema::{Constraints, Quantity},
+        attribute_type::{AttributeType, SimpleAttributeType},
+        entity::EntityId,
+    },
+};
+
+use super::RawAttributeSchema;
+
+fn failed(name: &str, rule: &str, detail: String) -> Error {
+    Error::ModuleError(format!("Attribute {name} failed {rule}: {detail}"))
+}
+
+impl Constraints {
+    // Checks the rules can apply to the type and quantity they're set on
+    pub fn check_schema(&self, attr_type: &AttributeType, quantity: &Quantity) -> Result<()> {
+        let is_text = matches!(
+            attr_type,
+            AttributeType::Simple(
+                SimpleAttributeType::Text
+                    | SimpleAttributeType::RichText
+                    | SimpleAttributeType::Longform
+                    | SimpleAttributeType::Url
+            )
+        );
+
+        let is_numeric = matches!(
+            attr_type,
+            AttributeType::Simple(SimpleAttributeType::Integer | SimpleAttributeType::Number)
+                | AttributeType::Measure(..)
+        );
+
+        let has_length =
+            self.min_length.is_some() || self.max_length.is_some() || self.pattern.is_some();
+        if has_length && !is_text {
+            return Err(Error::ModuleError(
+                "Length and pattern constraints only apply to text fields".to_string(),
+            ));
+        }
+
+        if (self.min.is_some() || self.max.is_some()) && !is_numeric {
+            return Err(Error::ModuleError(
+                "Range constraints only apply to numeric fields".to_string(),
+            ));
+        }
+
+        if (self.min_items.is_some() || self.max_items.is_some()) && quantity != &Quantity::List {
+            return Err(Error::ModuleError(
+                "Item count constraints only apply to list fields".to_string(),
+            ));
+        }
+
+        let inverted = matches!((self.min_length, self.max_length), (Some(min), Some(max)) if min > max)
+            || matches!((self.min, self.max), (Some(min), Some(max)) if min > max)
+            || matches!((self.min_items, self.max_items), (Some(min), Some(max)) if min > max);
+        if inverted {
+            return Err(Error::ModuleError(
+                "Constraint minimum is greater than its maximum".to_string(),
+            ));
+        }
+
+        self.pattern()?;
+
+        Ok(())
+    }
+
+    fn pattern(&self) -> Result<Option<Regex>> {
+        match &self.pattern {
+            Some(pattern) => match Regex::new(pattern) {
+                Ok(regex) => Ok(Some(regex)),
+                Err(err) => Err(Error::ModuleError(format!("Invalid pattern: {err}"))),
+            },
+            None => Ok(None),
+        }
+    }
+
+    pub fn check_text(&self, name: &str, value: &str) -> Result<()> {
+        let length = value.chars().count();
+
+        if let Some(min) = self.min_length {
+            if length < min {
+                let detail = format!("must be at least {min} characters");
+                return Err(failed(name, "min_length", detail));
+            }
+        }
+
+        if let Some(max) = self.max_length {
+            if length > max {
+                let detail = format!("must be at most {max} characters");
+                return Err(failed(name, "max_length", detail));
+            }
+        }
+
+        if let Some(regex) = self.pattern()? {
+            if !regex.is_match(value) {
+                let detail = format!("must match {}", regex.as_str());
+                return Err(failed(name, "pattern", detail));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn check_number(&self, name: &str, value: f64) -> Result<()> {
+        if let Some(min) = self.min {
+            if value < min {
+                return Err(failed(name, "min", format!("must be at least {min}")));
+            }
+        }
+
+        if let Some(max) = self.max {
+            if value > max {
+                return Err(failed(name, "max", format!("must be at most {max}")));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn check_items(&self, name: &str, count: usize) -> Result<()> {
+        if let Some(min) = self.min_items {
+            if count < min {
+                let detail = format!("must have at least {min} items");
+                return Err(failed(name, "min_items", detail));
+            }
+        }
+
+        if let Some(max) = self.max_items {
+            if count > max {
+                let detail = format!("must have at most {max} items");
+                return Err(failed(name, "max_items", detail));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl RawAttributeSchema {
+    // Checks the item count of a list once the given number of values are
+    // added to or removed from the entity
+    pub fn check_item_change(
+        &self,
+        tx: &Transaction,
+        entity: &EntityId,
+        change: i64,
+    ) -> Result<()> {
+        let constraints = &self.constraints;
+        if constraints.min_items.is_none() && constraints.max_items.is_none() {
+            return Ok(());
+        }
+
+        let Some(table) = value_table(&self.attr_type) else {
+            return Ok(());
+        };
+
+        let count: i64 = tx.query_row(
+            &format!("SELECT COUNT(*) FROM {table} WHERE entity = ?1 AND schema = ?2"),
+            params![entity, self.id],
+            |row| row.get(0),
+        )?;
+
+        constraints.check_items(&self.name, (count + change).max(0) as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::{params, Error};
+
+    use crate::{
+        database::{
+            entity::add_entity,
+            test::test_util::{setup, ASD, ESD},
+            Delete, New,
+        },
+        models::{
+            attribute::GenericAttributeId,
+            attribute_schema::{AttributeSchema, Constraints, CreateAttributeSchema, Quantity},
+            attribute_type::{CreateAttributeType, SimpleAttributeType},
+        },
+    };
+
+    #[test]
+    fn text_constraints_error() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let es = ESD::create_default(&tx);
+        let attr = ASD::default()
+            .constraints(Constraints {
+                max_length: Some(5),
+                pattern: Some("^[A-Z]".to_string()),
+                ..Default::default()
+            })
+            .create(&tx, &es);
+
+        let data = serde_json::from_str(&format!(r#"{{ "{attr}": "Too long" }}"#)).unwrap();
+        let result = add_entity(&tx, &es, data);
+        assert_eq!(
+            result,
+            Err(Error::ModuleError(
+                "Attribute Foo failed max_length: must be at most 5 characters".to_string()
+            ))
+        );
+
+        let data = serde_json::from_str(&format!(r#"{{ "{attr}": "lower" }}"#)).unwrap();
+        let result = add_entity(&tx, &es, data);
+        assert_eq!(
+            result,
+            Err(Error::ModuleError(
+                "Attribute Foo failed pattern: must match ^[A-Z]".to_string()
+            ))
+        );
+
+        let data = serde_json::from_str(&format!(r#"{{ "{attr}": "Upper" }}"#)).unwrap();
+        add_entity(&tx, &es, data).unwrap();
+    }
+
+    #[test]
+    fn number_constraints_error() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let es = ESD::create_default(&tx);
+        let attr = ASD::default()
+            .attr_type(SimpleAttributeType::Integer)
+            .constraints(Constraints {
+                min: Some(0.0),
+                max: Some(10.0),
+                ..Default::default()
+            })
+            .create(&tx, &es);
+
+        let data = serde_json::from_str(&format!(r#"{{ "{attr}": -1 }}"#)).unwrap();
+        let result = add_entity(&tx, &es, data);
+        assert_eq!(
+            result,
+            Err(Error::ModuleError(
+                "Attribute Foo failed min: must be at least 0".to_string()
+            ))
+        );
+
+        let data = serde_json::from_str(&format!(r#"{{ "{attr}": 10 }}"#)).unwrap();
+        add_entity(&tx, &es, data).unwrap();
+    }
+
+    #[test]
+    fn item_constraints_error() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let es = ESD::create_default(&tx);
+        let attr = ASD::default()
+            .quantity(Quantity::List)
+            .constraints(Constraints {
+                min_items: Some(1),
+                max_items: Some(2),
+                ..Default::default()
+            })
+            .create(&tx, &es);
+
+        let data = serde_json::from_str("{}").unwrap();
+        let result = add_entity(&tx, &es, data);
+        assert_eq!(
+            result,
+            Err(Error::ModuleError(
+                "Attribute Foo failed min_items: must have at least 1 items".to_string()
+            ))
+        );
+
+        let data = serde_json::from_str(&format!(r#"{{ "{attr}": ["A", "B", "C"] }}"#)).unwrap();
+        let result = add_entity(&tx, &es, data);
+        assert_eq!(
+            result,
+            Err(Error::ModuleError(
+                "Attribute Foo failed max_items: must have at most 2 items".to_string()
+            ))
+        );
+
+        let data = serde_json::from_str(&format!(r#"{{ "{attr}": ["A"] }}"#)).unwrap();
+        let entity = add_entity(&tx, &es, data).unwrap();
+
+        let id: GenericAttributeId = tx
+            .query_row(
+                "SELECT id FROM text_attribute WHERE entity = ?",
+                params![entity],
+                |r| r.get(0),
+            )
+            .unwrap();
+
+        let result = id.delete(&tx);
+        assert_eq!(
+            result,
+            Err(Error::ModuleError(
+                "Attribute Foo failed min_items: must have at least 1 items".to_string()
+            ))
+        );
+    }
+
+    // Writes that skip the checks are still held to the bounds, while deleting
+    // the entity removes its values regardless
+    #[test]
+    fn item_constraints_triggers() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let es = ESD::create_default(&tx);
+        let attr = ASD::default()
+            .quantity(Quantity::List)
+            .constraints(Constraints {
+                min_items: Some(1),
+                max_items: Some(1),
+                ..Default::default()
+            })
+            .create(&tx, &es);
+
+        let data = serde_json::from_str(&format!(r#"{{ "{attr}": ["A"] }}"#)).unwrap();
+        let entity = add_entity(&tx, &es, data).unwrap();
+
+        let result = tx.execute(
+            "INSERT INTO text_attribute (id, entity, schema, value, created, updated) VALUES (?1, ?2, ?3, 'B', 0, 0)",
+            params![GenericAttributeId::new(), entity, attr],
+        );
+        assert_eq!(
+            result,
+            Err(Error::SqliteFailure(
+                libsqlite3_sys::Error {
+                    code: libsqlite3_sys::ErrorCode::ConstraintViolation,
+                    extended_code: 1811
+                },
+                Some("Attempted to add more entries than the list allows".to_string())
+            ))
+        );
+
+        let result = tx.execute(
+            "DELETE FROM text_attribute WHERE entity = ?1",
+            params![entity],
+        );
+        assert_eq!(
+            result,
+            Err(Error::SqliteFailure(
+                libsqlite3_sys::Error {
+                    code: libsqlite3_sys::ErrorCode::ConstraintViolation,
+                    extended_code: 1811
+                },
+                Some("Cannot delete below the minimum number of entries".to_string())
+            ))
+        );
+
+        tx.execute("DELETE FROM entity WHERE id = ?1", params![entity])
+            .unwrap();
+    }
+
+    // A statement stopped part way by a bound leaves none of its rows changed
+    #[test]
+    fn item_constraints_whole_statement() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let es = ESD::create_default(&tx);
+        let attr = ASD::default()
+            .quantity(Quantity::List)
+            .constraints(Constraints {
+                min_items: Some(2),
+                max_items: Some(3),
+                ..Default::default()
+            })
+            .create(&tx, &es);
+
+        let data = serde_json::from_str(&format!(r#"{{ "{attr}": ["A", "B"] }}"#)).unwrap();
+        let entity = add_entity(&tx, &es, data).unwrap();
+
+        let count = || -> usize {
+            tx.query_row(
+                "SELECT COUNT(*) FROM text_attribute WHERE entity = ?1",
+                params![entity],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+
+        // The first copied row fits, the second goes over the maximum
+        let result = tx.execute(
+            "INSERT INTO text_attribute (id, entity, schema, value, created, updated)
+              SELECT randomblob(16), entity, schema, value || '2', 0, 0 FROM text_attribute WHERE entity = ?1",
+            params![entity],
+        );
+        assert!(result.is_err());
+        assert_eq!(count(), 2);
+
+        let id = GenericAttributeId::new();
+        tx.execute(
+            "INSERT INTO text_attribute (id, entity, schema, value, created, updated) VALUES (?1, ?2, ?3, 'C', 0, 0)",
+            params![id, entity, attr],
+        )
+        .unwrap();
+
+        // The first row can go, the second would drop below the minimum
+        let result = tx.execute(
+            "DELETE FROM text_attribute WHERE entity = ?1",
+            params![entity],
+        );
+        assert!(result.is_err());
+        assert_eq!(count(), 3);
+    }
+
+    #[test]
+    fn invalid_constraints_error() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let es = ESD::create_default(&tx);
+
+        let create = |attr_type, constraints| {
+            AttributeSchema::new(
+                &tx,
+                CreateAttributeSchema {
+                    entity: es.clone(),
+                    name: "Foo".to_string(),
+                    quantity: Quantity::Required,
+                    constraints,
+                    default: None,
+                    unique: false,
+                    attr_type: CreateAttributeType::Simple(attr_type),
+                },
+            )
+            .map(|_| ())
+        };
+
+        let result = create(
+            SimpleAttributeType::Integer,
+            Constraints {
+                max_length: Some(3),
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            result,
+            Err(Error::ModuleError(
+                "Length and pattern constraints only apply to text fields".to_string()
+            ))
+        );
+
+        let result = create(
+            SimpleAttributeType::Number,
+            Constraints {
+                min: Some(5.0),
+                max: Some(1.0),
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            result,
+            Err(Error::ModuleError(
+                "Constraint minimum is greater than its maximum".to_string()
+            ))
+        );
+
+        let result = create(
+            SimpleAttributeType::Text,
+            Constraints {
+                min_items: Some(1),
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            result,
+            Err(Error::ModuleError(
+                "Item count constraints only apply to list fields".to_string()
+            ))
+        );
+    }
+}
@@ -0,0 +1,545 @@
+use chrono::Utc;
+use rusqlite::{params, Error, Result, Transaction};
+use serde_json::Value;
+
+use crate::{
+    database::Insert,
+    models::{
+        attribute_schema::{AttributeSchema, Constraints, DefaultValue, Generator, Quantity},
+        attribute_type::{AttributeType, SimpleAttributeType},
+        entity::EntityId,
+        entity_schema::EntitySchemaId,
+    },
+    utils::get_timestamp,
+};
+
+use super::RawAttributeSchema;
+
+impl DefaultValue {
+    // Checks the default can be stored in a field of the given type and
+    // quantity. Unique fields would get the same value for every entity from
+    // anything but a generated id
+    pub fn check_schema(
+        &self,
+        attr_type: &AttributeType,
+        quantity: &Quantity,
+        unique: bool,
+    ) -> Result<()> {
+        if let AttributeType::Computed(..)
+        | AttributeType::Rollup(..)
+        | AttributeType::Backlink(..) = attr_type
+        {
+            return Err(Error::ModuleError(
+                "Computed, rollup and backlink fields cannot have defaults".to_string(),
+            ));
+        }
+
+        let error = |message: &str| Err(Error::ModuleError(message.to_string()));
+
+        match self {
+            DefaultValue::Value(Value::Null) => error("Default value cannot be null"),
+            DefaultValue::Value(..)
+            | DefaultValue::Generator(Generator::Now | Generator::EmptyList)
+                if unique =>
+            {
+                error("Unique fields can only default to a generated Uuid")
+            }
+            DefaultValue::Value(Value::Array(..))
+            | DefaultValue::Generator(Generator::EmptyList)
+                if quantity != &Quantity::List =>
+            {
+                error("List defaults only apply to list fields")
+            }
+            DefaultValue::Generator(Generator::Now)
+                if !matches!(
+                    attr_type,
+                    AttributeType::Simple(
+                        SimpleAttributeType::Date | SimpleAttributeType::DateTime
+                    )
+                ) =>
+            {
+                error("Now defaults only apply to date fields")
+            }
+            DefaultValue::Generator(Generator::Uuid)
+                if attr_type != &AttributeType::Simple(SimpleAttributeType::Text) =>
+            {
+                error("Uuid defaults only apply to text fields")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Generators produce a fresh value on every call
+    pub fn resolve(&self, attr_type: &AttributeType) -> Value {
+        match self {
+            DefaultValue::Value(value) => value.clone(),
+            DefaultValue::Generator(Generator::EmptyList) => Value::Array(Vec::new()),
+            DefaultValue::Generator(Generator::Uuid) => {
+                Value::String(uuid::Uuid::new_v4().to_string())
+            }
+            DefaultValue::Generator(Generator::Now) => match attr_type {
+                AttributeType::Simple(SimpleAttributeType::Date) => {
+                    Value::String(Utc::now().date_naive().to_string())
+                }
+                _ => Value::String(Utc::now().to_rfc3339()),
+            },
+        }
+    }
+}
+
+// Required fields and lists with a minimum number of items can't be left
+// empty on existing entities
+fn needs_value(quantity: &Quantity, constraints: &Constraints) -> bool {
+    match quantity {
+        Quantity::Required => true,
+        Quantity::List => constraints.min_items.is_some_and(|min| min > 0),
+        Quantity::Optional => false,
+    }
+}
+
+fn missing_default(quantity: &Quantity) -> Error {
+    match quantity {
+        Quantity::List => Error::ModuleError(
+            "List field with a minimum added to a schema with entities must have a default"
+                .to_string(),
+        ),
+        _ => Error::ModuleError(
+            "Required field added to a schema with entities must have a default".to_string(),
+        ),
+    }
+}
+
+impl AttributeSchema {
+    // Entities created before a required attribute was added, including those
+    // of schemas extending it, which need the default filled in so they don't
+    // violate the schema
+    pub fn backfill_entities(
+        &self,
+        tx: &Transaction,
+        entity_schema: &EntitySchemaId,
+    ) -> Result<Vec<EntityId>> {
+        if !needs_value(&self.quantity, &self.constraints) {
+            return Ok(Vec::new());
+        }
+
+        if let AttributeType::Computed(..)
+        | AttributeType::Rollup(..)
+        | AttributeType::Backlink(..) = self.attr_type
+        {
+            return Ok(Vec::new());
+        }
+
+        let entities = entity_schema.entities(tx)?;
+
+        if !entities.is_empty() && self.default.is_none() {
+            return Err(missing_default(&self.quantity));
+        }
+
+        Ok(entities)
+    }
+}
+
+impl RawAttributeSchema {
+    pub fn needs_value(&self) -> bool {
+        needs_value(&self.quantity, &self.constraints)
+    }
+
+    // Runs the default through the same type, option and constraint checks
+    // as a provided value by writing it for a scratch entity of the owning
+    // schema, which is rolled back either way
+    pub fn check_default(&self, tx: &Transaction, owner: &EntitySchemaId) -> Result<()> {
+        let Some(default) = &self.default else {
+            return Ok(());
+        };
+
+        tx.execute_batch("SAVEPOINT default_check")?;
+
+        let entity = EntityId::new();
+        let result = tx
+            .execute(
+                "INSERT INTO entity (id, schema, created, updated) VALUES (?1, ?2, ?3, ?3)",
+                params![entity, owner, get_timestamp()],
+            )
+            .and_then(|_| self.insert(tx, &entity, &default.resolve(&self.attr_type)));
+
+        tx.execute_batch("ROLLBACK TO default_check; RELEASE default_check")?;
+
+        result.map_err(|err| match err {
+            Error::ModuleError(message) => {
+                Error::ModuleError(format!("Invalid default for {}: {message}", self.name))
+            }
+            err => err,
+        })
+    }
+
+    pub fn backfill(&self, tx: &Transaction, entities: &[EntityId]) -> Result<()> {
+        if let AttributeType::Computed(..)
+        | AttributeType::Rollup(..)
+        | AttributeType::Backlink(..) = self.attr_type
+        {
+            return Ok(());
+        }
+
+        if entities.is_empty() {
+            return Ok(());
+        }
+
+        let Some(default) = &self.default else {
+            return Err(missing_default(&self.quantity));
+        };
+
+        for entity in entities {
+            self.insert(tx, entity, &default.resolve(&self.attr_type))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::{params, Error, Transaction};
+    use serde_json::json;
+
+    use crate::{
+        database::{
+            entity::add_entity,
+            test::test_util::{setup, ASD, ESD},
+            New,
+        },
+        models::{
+            attribute_schema::{
+                AttributeSchema, Constraints, CreateAttributeSchema, DefaultValue, Generator,
+                Quantity,
+            },
+            attribute_type::{CreateAttributeType, SimpleAttributeType},
+            entity::EntityId,
+            entity_schema::EntitySchemaId,
+        },
+    };
+
+    fn create(
+        tx: &Transaction,
+        es: &EntitySchemaId,
+        attr_type: SimpleAttributeType,
+        quantity: Quantity,
+        default: Option<DefaultValue>,
+    ) -> rusqlite::Result<AttributeSchema> {
+        AttributeSchema::new(
+            tx,
+            CreateAttributeSchema {
+                entity: es.clone(),
+                name: "Bar".to_string(),
+                quantity,
+                constraints: Constraints::default(),
+                default,
+                unique: false,
+                attr_type: CreateAttributeType::Simple(attr_type),
+            },
+        )
+    }
+
+    fn stored(tx: &Transaction, table: &str, entity: &EntityId) -> Vec<String> {
+        let mut statement = tx
+            .prepare(&format!("SELECT value FROM {table} WHERE entity = ?1"))
+            .unwrap();
+        statement
+            .query_map(params![entity], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn default_value() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let es = ESD::create_default(&tx);
+        let attr = ASD::default()
+            .default_value(DefaultValue::Value(json!("Bar")))
+            .create(&tx, &es);
+
+        let entity = add_entity(&tx, &es, json!({})).unwrap();
+        assert_eq!(stored(&tx, "text_attribute", &entity), vec!["Bar"]);
+
+        // A provided value takes precedence
+        let entity = add_entity(&tx, &es, json!({ attr.to_string(): "Baz" })).unwrap();
+        assert_eq!(stored(&tx, "text_attribute", &entity), vec!["Baz"]);
+    }
+
+    #[test]
+    fn default_generators() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let es = ESD::create_default(&tx);
+        let now = Some(DefaultValue::Generator(Generator::Now));
+        let uuid = Some(DefaultValue::Generator(Generator::Uuid));
+        let empty = Some(DefaultValue::Generator(Generator::EmptyList));
+
+        create(
+            &tx,
+            &es,
+            SimpleAttributeType::DateTime,
+            Quantity::Required,
+            now,
+        )
+        .unwrap();
+        AttributeSchema::new(
+            &tx,
+            CreateAttributeSchema {
+                entity: es.clone(),
+                name: "Id".to_string(),
+                quantity: Quantity::Required,
+                constraints: Constraints::default(),
+                default: uuid,
+                unique: false,
+                attr_type: CreateAttributeType::Simple(SimpleAttributeType::Text),
+            },
+        )
+        .unwrap();
+        AttributeSchema::new(
+            &tx,
+            CreateAttributeSchema {
+                entity: es.clone(),
+                name: "Tags".to_string(),
+                quantity: Quantity::List,
+                constraints: Constraints::default(),
+                default: empty,
+                unique: false,
+                attr_type: CreateAttributeType::Simple(SimpleAttributeType::Text),
+            },
+        )
+        .unwrap();
+
+        let first = add_entity(&tx, &es, json!({})).unwrap();
+        let second = add_entity(&tx, &es, json!({})).unwrap();
+
+        let datetime = stored(&tx, "datetime_attribute", &first);
+        assert!(chrono::DateTime::parse_from_rfc3339(&datetime[0]).is_ok());
+
+        let first_id = stored(&tx, "text_attribute", &first);
+        let second_id = stored(&tx, "text_attribute", &second);
+        assert_eq!(first_id.len(), 1);
+        assert!(uuid::Uuid::parse_str(&first_id[0]).is_ok());
+        assert_ne!(first_id, second_id);
+    }
+
+    #[test]
+    fn backfill() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let es = ESD::create_default(&tx);
+        let entity = add_entity(&tx, &es, json!({})).unwrap();
+
+        let result = create(
+            &tx,
+            &es,
+            SimpleAttributeType::Integer,
+            Quantity::Required,
+            None,
+        );
+        assert_eq!(
+            result.map(|_| ()),
+            Err(Error::ModuleError(
+                "Required field added to a schema with entities must have a default".to_string()
+            ))
+        );
+
+        let default = Some(DefaultValue::Value(json!(7)));
+        create(
+            &tx,
+            &es,
+            SimpleAttributeType::Integer,
+            Quantity::Required,
+            default,
+        )
+        .unwrap();
+
+        let value: i64 = tx
+            .query_row(
+                "SELECT value FROM integer_attribute WHERE entity = ?1",
+                params![entity],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(value, 7);
+    }
+
+    #[test]
+    fn backfill_list_minimum() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let es = ESD::create_default(&tx);
+        let entity = add_entity(&tx, &es, json!({})).unwrap();
+
+        let tags = ASD::default().name("Tags").quantity(Quantity::List);
+        let minimum = Constraints {
+            min_items: Some(1),
+            ..Default::default()
+        };
+
+        let result = AttributeSchema::new(
+            &tx,
+            CreateAttributeSchema {
+                entity: es.clone(),
+                name: "Tags".to_string(),
+                quantity: Quantity::List,
+                constraints: minimum.clone(),
+                default: None,
+                unique: false,
+                attr_type: CreateAttributeType::Simple(SimpleAttributeType::Text),
+            },
+        );
+        assert_eq!(
+            result.map(|_| ()),
+            Err(Error::ModuleError(
+                "List field with a minimum added to a schema with entities must have a default"
+                    .to_string()
+            ))
+        );
+
+        tags.constraints(minimum)
+            .default_value(DefaultValue::Value(json!(["Untagged"])))
+            .create(&tx, &es);
+        assert_eq!(stored(&tx, "text_attribute", &entity), vec!["Untagged"]);
+    }
+
+    #[test]
+    fn invalid_default_error() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let es = ESD::create_default(&tx);
+
+        let now = Some(DefaultValue::Generator(Generator::Now));
+        let result = create(&tx, &es, SimpleAttributeType::Text, Quantity::Required, now);
+        assert_eq!(
+            result.map(|_| ()),
+            Err(Error::ModuleError(
+                "Now defaults only apply to date fields".to_string()
+            ))
+        );
+
+        let list = Some(DefaultValue::Value(json!(["A"])));
+        let result = create(
+            &tx,
+            &es,
+            SimpleAttributeType::Text,
+            Quantity::Optional,
+            list,
+        );
+        assert_eq!(
+            result.map(|_| ()),
+            Err(Error::ModuleError(
+                "List defaults only apply to list fields".to_string()
+            ))
+        );
+    }
+
+    // Defaults go through the same checks as provided values
+    #[test]
+    fn default_value_checks() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let es = ESD::create_default(&tx);
+
+        let text = Some(DefaultValue::Value(json!("abc")));
+        let result = create(
+            &tx,
+            &es,
+            SimpleAttributeType::Integer,
+            Quantity::Required,
+            text,
+        );
+        assert_eq!(
+            result.map(|_| ()),
+            Err(Error::ModuleError(
+                "Invalid default for Bar: Provided a string to a numeric field".to_string()
+            ))
+        );
+
+        let select = |name: &str, default: &str| {
+            AttributeSchema::new(
+                &tx,
+                CreateAttributeSchema {
+                    entity: es.clone(),
+                    name: name.to_string(),
+                    quantity: Quantity::Required,
+                    constraints: Constraints::default(),
+                    default: Some(DefaultValue::Value(json!(default))),
+                    unique: false,
+                    attr_type: CreateAttributeType::Select {
+                        options: vec!["Fiction".to_string()],
+                    },
+                },
+            )
+        };
+        assert!(select("Genre", "Poetry").is_err());
+
+        let short = AttributeSchema::new(
+            &tx,
+            CreateAttributeSchema {
+                entity: es.clone(),
+                name: "Code".to_string(),
+                quantity: Quantity::Required,
+                constraints: Constraints {
+                    max_length: Some(2),
+                    ..Default::default()
+                },
+                default: Some(DefaultValue::Value(json!("abc"))),
+                unique: false,
+                attr_type: CreateAttributeType::Simple(SimpleAttributeType::Text),
+            },
+        );
+        assert!(short.is_err());
+
+        // The scratch entities used by the checks are not kept
+        select("Category", "Fiction").unwrap();
+        let entities: usize = tx
+            .query_row("SELECT COUNT(*) FROM entity", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(entities, 0);
+        add_entity(&tx, &es, json!({})).unwrap();
+    }
+
+    #[test]
+    fn unique_defaults() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let es = ESD::create_default(&tx);
+        add_entity(&tx, &es, json!({})).unwrap();
+        add_entity(&tx, &es, json!({})).unwrap();
+
+        let unique = |name: &str, default: DefaultValue| {
+            AttributeSchema::new(
+                &tx,
+                CreateAttributeSchema {
+                    entity: es.clone(),
+                    name: name.to_string(),
+                    quantity: Quantity::Required,
+                    constraints: Constraints::default(),
+                    default: Some(default),
+                    unique: true,
+                    attr_type: CreateAttributeType::Simple(SimpleAttributeType::Text),
+                },
+            )
+            .map(|_| ())
+        };
+
+        assert_eq!(
+            unique("Isbn", DefaultValue::Value(json!("978-0"))),
+            Err(Error::ModuleError(
+                "Unique fields can only default to a generated Uuid".to_string()
+            ))
+        );
+
+        // Each existing entity is backfilled with its own id
+        unique("Key", DefaultValue::Generator(Generator::Uuid)).unwrap();
+    }
+}
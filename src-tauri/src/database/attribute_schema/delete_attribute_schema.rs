@@ -0,0 +1,145 @@
+use rusqlite::{params, Error, Result, Transaction};
+
+use crate::{
+    database::{attribute_schema::RawAttributeSchema, attribute_type::value_table, Delete, Get},
+    models::{
+        attribute_schema::AttributeSchemaId,
+        entity_schema::{DeleteMode, DeletePreview, EntitySchemaId},
+    },
+};
+
+use super::update_attribute_schema::check_computed;
+
+impl AttributeSchemaId {
+    pub fn delete_preview(&self, tx: &Transaction) -> Result<DeletePreview> {
+        let schema = RawAttributeSchema::get(tx, self)?;
+
+        let (entities, values) = match value_table(&schema.attr_type) {
+            Some(table) => tx.query_row(
+                &format!("SELECT COUNT(DISTINCT entity), COUNT(*) FROM {table} WHERE schema = ?1"),
+                params![self],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )?,
+            None => (0, 0),
+        };
+
+        // Rollups and backlinks built on the attribute are removed by the
+        // cascades on attribute_schema
+        let mut statement = tx.prepare(
+            "SELECT name FROM attribute_schema
+              WHERE rollup_reference = ?1 OR rollup_attribute = ?1 OR backlink_reference = ?1
+              ORDER BY name",
+        )?;
+        let dependents = statement
+            .query_map(params![self], |row| row.get(0))?
+            .collect::<Result<_>>()?;
+
+        Ok(DeletePreview {
+            entities,
+            values,
+            dependents,
+            ..Default::default()
+        })
+    }
+
+    pub fn delete_with(self, tx: &Transaction, mode: DeleteMode) -> Result<()> {
+        let preview = self.delete_preview(tx)?;
+
+        if mode == DeleteMode::Restrict && preview.values > 0 {
+            return Err(Error::ModuleError(format!(
+                "Attribute holds {} values",
+                preview.values
+            )));
+        }
+
+        let schema = RawAttributeSchema::get(tx, &self)?;
+        let owner: EntitySchemaId = tx.query_row(
+            "SELECT entity FROM attribute_schema WHERE id = ?1",
+            params![self],
+            |row| row.get(0),
+        )?;
+
+        if let Some(table) = value_table(&schema.attr_type) {
+            tx.execute(
+                "INSERT INTO attribute_migration (schema) VALUES (?1)",
+                params![self],
+            )?;
+            tx.execute(
+                &format!("DELETE FROM {table} WHERE schema = ?1"),
+                params![self],
+            )?;
+            tx.execute(
+                "DELETE FROM attribute_migration WHERE schema = ?1",
+                params![self],
+            )?;
+        }
+
+        // Displays naming the attribute are dropped, so entities fall back to
+        // the display of a parent schema
+        tx.execute(
+            "UPDATE entity_schema SET display = NULL WHERE instr(display, ?1) > 0",
+            params![self.to_string()],
+        )?;
+
+        tx.execute("DELETE FROM attribute_schema WHERE id = ?1", params![self])?;
+
+        // Computed fields naming the attribute can no longer be evaluated
+        check_computed(tx, &owner)
+    }
+}
+
+impl Delete for AttributeSchemaId {
+    fn delete(self, tx: &Transaction) -> Result<()> {
+        self.delete_with(tx, DeleteMode::Restrict)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Error;
+    use serde_json::json;
+
+    use crate::{
+        database::{
+            entity::add_entity,
+            test::test_util::{setup, ASD, ESD},
+            Delete, Get,
+        },
+        models::{
+            attribute_schema::AttributeSchema,
+            entity_schema::{DeleteMode, DeletePreview},
+        },
+    };
+
+    #[test]
+    fn delete_modes() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let es = ESD::create_default(&tx);
+        let attr = ASD::default().create(&tx, &es);
+        for value in ["A", "B"] {
+            add_entity(&tx, &es, json!({ attr.to_string(): value })).unwrap();
+        }
+
+        assert_eq!(
+            attr.delete_preview(&tx),
+            Ok(DeletePreview {
+                entities: 2,
+                values: 2,
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            attr.clone().delete(&tx),
+            Err(Error::ModuleError("Attribute holds 2 values".to_string()))
+        );
+
+        // Required values are removed along with the attribute
+        attr.clone().delete_with(&tx, DeleteMode::Cascade).unwrap();
+        assert_eq!(
+            AttributeSchema::get(&tx, &attr).err(),
+            Some(Error::QueryReturnedNoRows)
+        );
+    }
+}
@@ -3,7 +3,7 @@ use std::collections::HashMap;
 use rusqlite::{params, Transaction};
 
 use crate::{
-    database::{Get, GetMany},
+    database::{entity_schema::ancestors, Get, GetMany},
     models::{
         attribute_schema::{AttributeSchema, AttributeSchemaId},
         attribute_type::AttributeType,
@@ -14,23 +14,36 @@ use crate::{
 
 use super::{RawAttributeSchema, SchemaMap};
 
+// Columns read by AttributeType::columns_result
+const TYPE_COLUMNS: &str = "a.type,
+                    (SELECT json_group_array(json_array(hex(t.target), s.name) ORDER BY t.position)
+                      FROM reference_target t INNER JOIN entity_schema s ON t.target = s.id WHERE t.schema = a.id),
+                    a.unit,
+                    (SELECT json_group_array(o.value ORDER BY o.position) FROM select_option o WHERE o.schema = a.id),
+                    a.expression, a.rollup_reference, a.rollup_attribute, a.aggregate,
+                    a.backlink_reference,
+                    (SELECT json_group_array(json_object('name', e.name, 'attr_type', e.type) ORDER BY e.position)
+                      FROM reference_edge e WHERE e.schema = a.id)";
+
 impl Get<AttributeSchemaId> for RawAttributeSchema {
     fn get(tx: &Transaction, id: &AttributeSchemaId) -> rusqlite::Result<Self> {
         tx.query_row(
-            "SELECT 
-                    a.id, a.quantity, a.type, e.id, e.name 
-                  FROM attribute_schema a LEFT JOIN entity_schema e ON a.reference = e.id 
-                  WHERE a.id=?1",
+            &format!(
+                "SELECT 
+                    a.id, a.name, a.quantity, a.constraints, a.default_value, a.is_unique, {TYPE_COLUMNS}
+                  FROM attribute_schema a
+                  WHERE a.id=?1"
+            ),
             params![id],
             |row| {
                 Ok(Self {
                     id: row.get(0)?,
-                    quantity: row.get(1)?,
-                    attr_type: AttributeType::columns_result(
-                        row.get_ref(2)?,
-                        row.get_ref(3)?,
-                        row.get_ref(4)?,
-                    )?,
+                    name: row.get(1)?,
+                    quantity: row.get(2)?,
+                    constraints: row.get(3)?,
+                    default: row.get(4)?,
+                    unique: row.get(5)?,
+                    attr_type: AttributeType::columns_result(row, 6)?,
                 })
             },
         )
@@ -40,35 +53,39 @@ impl Get<AttributeSchemaId> for RawAttributeSchema {
 impl Get<AttributeSchemaId> for AttributeSchema {
     fn get(tx: &Transaction, id: &AttributeSchemaId) -> rusqlite::Result<Self> {
         tx.query_row(
-            "SELECT 
-                    a.id, a.name, a.quantity, a.type, e.id, e.name 
-                  FROM attribute_schema a LEFT JOIN entity_schema e ON a.reference = e.id 
-                  WHERE a.id=?1",
+            &format!(
+                "SELECT 
+                    a.id, a.name, a.quantity, a.constraints, a.default_value, a.is_unique, {TYPE_COLUMNS}
+                  FROM attribute_schema a
+                  WHERE a.id=?1"
+            ),
             params![id],
             |row| {
                 Ok(Self {
                     id: row.get(0)?,
                     name: row.get(1)?,
                     quantity: row.get(2)?,
-                    attr_type: AttributeType::columns_result(
-                        row.get_ref(3)?,
-                        row.get_ref(4)?,
-                        row.get_ref(5)?,
-                    )?,
+                    constraints: row.get(3)?,
+                    default: row.get(4)?,
+                    unique: row.get(5)?,
+                    attr_type: AttributeType::columns_result(row, 6)?,
                 })
             },
         )
     }
 }
 
+// Attributes of the schema include those inherited from the schemas it extends
 impl GetMany<EntitySchemaId> for AttributeSchema {
     fn get_many(tx: &Transaction, id: &EntitySchemaId) -> rusqlite::Result<Vec<Self>> {
-        let mut statement = tx.prepare(
-            "SELECT 
-                    a.id, a.name, a.quantity, a.type, e.id, e.name 
-                  FROM attribute_schema a LEFT JOIN entity_schema e ON a.reference = e.id 
-                  WHERE a.entity=?1",
-        )?;
+        let mut statement = tx.prepare(&format!(
+            "WITH RECURSIVE {}
+                SELECT 
+                    a.id, a.name, a.quantity, a.constraints, a.default_value, a.is_unique, {TYPE_COLUMNS}
+                  FROM attribute_schema a
+                  WHERE a.entity IN (SELECT id FROM ancestors)",
+            ancestors("SELECT ?1")
+        ))?;
         let mut rows = statement.query(params![id])?;
 
         let mut results = Vec::new();
@@ -77,11 +94,10 @@ impl GetMany<EntitySchemaId> for AttributeSchema {
                 id: row.get(0)?,
                 name: row.get(1)?,
                 quantity: row.get(2)?,
-                attr_type: AttributeType::columns_result(
-                    row.get_ref(3)?,
-                    row.get_ref(4)?,
-                    row.get_ref(5)?,
-                )?,
+                constraints: row.get(3)?,
+                default: row.get(4)?,
+                unique: row.get(5)?,
+                attr_type: AttributeType::columns_result(row, 6)?,
             });
         }
 
@@ -97,12 +113,14 @@ pub trait GetSchemaMap<T> {
 
 impl GetSchemaMap<EntitySchemaId> for RawAttributeSchema {
     fn get_map(tx: &Transaction, id: &EntitySchemaId) -> rusqlite::Result<SchemaMap> {
-        let mut statement = tx.prepare(
-            "SELECT 
-                    a.id, a.quantity, a.type, e.id, e.name 
-                  FROM attribute_schema a LEFT JOIN entity_schema e ON a.reference = e.id 
-                  WHERE a.entity=?1",
-        )?;
+        let mut statement = tx.prepare(&format!(
+            "WITH RECURSIVE {}
+                SELECT 
+                    a.id, a.name, a.quantity, a.constraints, a.default_value, a.is_unique, {TYPE_COLUMNS}
+                  FROM attribute_schema a
+                  WHERE a.entity IN (SELECT id FROM ancestors)",
+            ancestors("SELECT ?1")
+        ))?;
         let mut rows = statement.query(params![id])?;
 
         let mut results = HashMap::new();
@@ -111,12 +129,12 @@ impl GetSchemaMap<EntitySchemaId> for RawAttributeSchema {
                 row.get(0)?,
                 RawAttributeSchema {
                     id: row.get(0)?,
-                    quantity: row.get(1)?,
-                    attr_type: AttributeType::columns_result(
-                        row.get_ref(2)?,
-                        row.get_ref(3)?,
-                        row.get_ref(4)?,
-                    )?,
+                    name: row.get(1)?,
+                    quantity: row.get(2)?,
+                    constraints: row.get(3)?,
+                    default: row.get(4)?,
+                    unique: row.get(5)?,
+                    attr_type: AttributeType::columns_result(row, 6)?,
                 },
             );
         }
@@ -130,14 +148,14 @@ impl GetSchemaMap<EntityId> for RawAttributeSchema {
         tx: &Transaction,
         id: &EntityId,
     ) -> rusqlite::Result<HashMap<AttributeSchemaId, Self>> {
-        let mut statement = tx.prepare(
-            "SELECT 
-                    a.id, a.quantity, a.type, e.id, e.name 
-                  FROM entity ent
-                  RIGHT JOIN entity_schema e on ent.schema = e.id
-                  RIGHT JOIN attribute_schema a ON a.entity = e.id 
-                  WHERE ent.id=?1",
-        )?;
+        let mut statement = tx.prepare(&format!(
+            "WITH RECURSIVE {}
+                SELECT 
+                    a.id, a.name, a.quantity, a.constraints, a.default_value, a.is_unique, {TYPE_COLUMNS}
+                  FROM attribute_schema a
+                  WHERE a.entity IN (SELECT id FROM ancestors)",
+            ancestors("SELECT schema FROM entity WHERE id = ?1")
+        ))?;
         let mut rows = statement.query(params![id])?;
 
         let mut results = HashMap::new();
@@ -146,12 +164,12 @@ impl GetSchemaMap<EntityId> for RawAttributeSchema {
                 row.get(0)?,
                 RawAttributeSchema {
                     id: row.get(0)?,
-                    quantity: row.get(1)?,
-                    attr_type: AttributeType::columns_result(
-                        row.get_ref(2)?,
-                        row.get_ref(3)?,
-                        row.get_ref(4)?,
-                    )?,
+                    name: row.get(1)?,
+                    quantity: row.get(2)?,
+                    constraints: row.get(3)?,
+                    default: row.get(4)?,
+                    unique: row.get(5)?,
+                    attr_type: AttributeType::columns_result(row, 6)?,
                 },
             );
         }
@@ -1,20 +1,34 @@
 mod add_attribute_schema;
+mod constraints;
+mod default_value;
+mod delete_attribute_schema;
 mod get_attribute_schema;
+mod reference_target;
+mod select_option;
+mod unique;
+mod update_attribute_schema;
 mod utils;
 
 use std::collections::HashMap;
 
 pub use get_attribute_schema::GetSchemaMap;
+pub(crate) use unique::check_unique_value;
+pub use unique::UniqueViolation;
+pub(crate) use update_attribute_schema::check_computed;
 
 use crate::models::{
-    attribute_schema::{AttributeSchemaId, Quantity},
+    attribute_schema::{AttributeSchemaId, Constraints, DefaultValue, Quantity},
     attribute_type::AttributeType,
 };
 
 pub struct RawAttributeSchema {
     pub id: AttributeSchemaId,
+    pub name: String,
     pub attr_type: AttributeType,
     pub quantity: Quantity,
+    pub constraints: Constraints,
+    pub default: Option<DefaultValue>,
+    pub unique: bool,
 }
 
 pub type SchemaMap = HashMap<AttributeSchemaId, RawAttributeSchema>;
@@ -28,7 +42,7 @@ mod tests {
         },
         models::{
             attribute_schema::AttributeSchema,
-            attribute_type::{ReferenceAttribute, SimpleAttributeType},
+            attribute_type::{ReferenceAttribute, ReferenceTarget, SimpleAttributeType},
         },
     };
 
@@ -70,8 +84,11 @@ mod tests {
         assert_eq!(
             stored.attr_type,
             AttributeType::Reference(ReferenceAttribute {
-                id: entity_id,
-                name: entity_name.to_string()
+                targets: vec![ReferenceTarget {
+                    id: entity_id,
+                    name: entity_name.to_string()
+                }],
+                edges: Vec::new(),
             })
         );
     }
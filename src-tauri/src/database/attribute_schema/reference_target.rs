@@ -0,0 +1,57 @@
+use rusqlite::{params, Error, Transaction};
+
+use crate::{
+    models::{
+        attribute_schema::AttributeSchemaId,
+        attribute_type::{EdgeProperty, ReferenceEdgeId, ReferenceTargetId},
+        entity_schema::EntitySchemaId,
+    },
+    utils::get_timestamp,
+};
+
+impl AttributeSchemaId {
+    pub fn add_target(&self, tx: &Transaction, target: &EntitySchemaId) -> rusqlite::Result<()> {
+        self.check_reference(tx)?;
+
+        let id = ReferenceTargetId::new();
+        let created_at = get_timestamp();
+
+        tx.execute(
+            "INSERT INTO reference_target (id, schema, target, position, created, updated)
+              VALUES (?1, ?2, ?3, (SELECT COALESCE(MAX(position) + 1, 0) FROM reference_target WHERE schema = ?2), ?4, ?4)",
+            params![id, self, target, created_at],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn add_edge(&self, tx: &Transaction, edge: &EdgeProperty) -> rusqlite::Result<()> {
+        self.check_reference(tx)?;
+
+        let id = ReferenceEdgeId::new();
+        let created_at = get_timestamp();
+
+        tx.execute(
+            "INSERT INTO reference_edge (id, schema, name, type, position, created, updated)
+              VALUES (?1, ?2, ?3, ?4, (SELECT COALESCE(MAX(position) + 1, 0) FROM reference_edge WHERE schema = ?2), ?5, ?5)",
+            params![id, self, edge.name, edge.attr_type, created_at],
+        )?;
+
+        Ok(())
+    }
+
+    fn check_reference(&self, tx: &Transaction) -> rusqlite::Result<()> {
+        let attr_type: String = tx.query_row(
+            "SELECT type FROM attribute_schema WHERE id = ?1",
+            params![self],
+            |row| row.get(0),
+        )?;
+
+        match attr_type.as_str() {
+            "Reference" => Ok(()),
+            _ => Err(Error::ModuleError(
+                "Attribute is not a reference field".to_string(),
+            )),
+        }
+    }
+}
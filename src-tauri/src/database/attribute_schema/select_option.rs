@@ -0,0 +1,216 @@
+use rusqlite::{params, Error, Transaction};
+
+use crate::{
+    models::{attribute_schema::AttributeSchemaId, attribute_type::SelectOptionId},
+    utils::get_timestamp,
+};
+
+impl AttributeSchemaId {
+    pub fn add_option(&self, tx: &Transaction, value: &str) -> rusqlite::Result<()> {
+        self.check_select(tx)?;
+
+        let id = SelectOptionId::new();
+        let created_at = get_timestamp();
+
+        tx.execute(
+            "INSERT INTO select_option (id, schema, value, position, created, updated)
+              VALUES (?1, ?2, ?3, (SELECT COALESCE(MAX(position) + 1, 0) FROM select_option WHERE schema = ?2), ?4, ?4)",
+            params![id, self, value, created_at],
+        )?;
+
+        Ok(())
+    }
+
+    // Stored values reference the option by name and are updated by the
+    // ON UPDATE CASCADE on select_attribute
+    pub fn rename_option(&self, tx: &Transaction, from: &str, to: &str) -> rusqlite::Result<()> {
+        let updated = get_timestamp();
+
+        let result = tx.execute(
+            "UPDATE select_option SET value = ?1, updated = ?2 WHERE schema = ?3 AND value = ?4",
+            params![to, updated, self, from],
+        )?;
+
+        if result == 0 {
+            Err(Error::QueryReturnedNoRows)
+        } else {
+            Ok(())
+        }
+    }
+
+    // Places the options in the given order, which must name each of them
+    pub fn reorder_options(&self, tx: &Transaction, options: &[String]) -> rusqlite::Result<()> {
+        self.check_select(tx)?;
+
+        let count: usize = tx.query_row(
+            "SELECT COUNT(*) FROM select_option WHERE schema = ?1",
+            params![self],
+            |r| r.get(0),
+        )?;
+        if count != options.len() {
+            return Err(Error::ModuleError(
+                "Option order must name every option".to_string(),
+            ));
+        }
+
+        let updated = get_timestamp();
+        for (position, value) in options.iter().enumerate() {
+            let result = tx.execute(
+                "UPDATE select_option SET position = ?1, updated = ?2 WHERE schema = ?3 AND value = ?4",
+                params![position, updated, self, value],
+            )?;
+
+            if result == 0 {
+                return Err(Error::ModuleError(format!("Unknown option {value}")));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_select(&self, tx: &Transaction) -> rusqlite::Result<()> {
+        let attr_type: String = tx.query_row(
+            "SELECT type FROM attribute_schema WHERE id = ?",
+            params![self],
+            |r| r.get(0),
+        )?;
+
+        match attr_type.as_str() {
+            "Select" => Ok(()),
+            _ => Err(Error::ModuleError(
+                "Attribute is not a select field".to_string(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::{Error, Transaction};
+
+    use crate::{
+        database::{
+            entity::{add_entity, get, EntityField, EntityRequest},
+            test::test_util::{setup, ASD, ESD},
+            Get, New,
+        },
+        models::{
+            attribute_schema::{
+                AttributeSchema, AttributeSchemaId, Constraints, CreateAttributeSchema, Quantity,
+            },
+            attribute_type::{AttributeType, CreateAttributeType, SelectAttribute},
+            entity_schema::EntitySchemaId,
+        },
+    };
+    use serde_json::Value;
+
+    fn create_select(
+        tx: &Transaction,
+        entity: &EntitySchemaId,
+        quantity: Quantity,
+    ) -> AttributeSchemaId {
+        AttributeSchema::new(
+            tx,
+            CreateAttributeSchema {
+                entity: entity.clone(),
+                name: "Status".to_string(),
+                quantity,
+                constraints: Constraints::default(),
+                default: None,
+                unique: false,
+                attr_type: CreateAttributeType::Select {
+                    options: vec!["Todo".to_string(), "Doing".to_string()],
+                },
+            },
+        )
+        .unwrap()
+        .id
+    }
+
+    #[test]
+    fn add_option() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let schema = ESD::create_default(&tx);
+        let attr = create_select(&tx, &schema, Quantity::Required);
+
+        attr.add_option(&tx, "Done").unwrap();
+
+        let stored = AttributeSchema::get(&tx, &attr).unwrap();
+
+        assert_eq!(
+            stored.attr_type,
+            AttributeType::Select(SelectAttribute {
+                options: vec!["Todo".to_string(), "Doing".to_string(), "Done".to_string()]
+            })
+        );
+    }
+
+    #[test]
+    fn add_option_not_select_error() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let schema = ESD::create_default(&tx);
+        let attr = ASD::create_default(&tx, &schema);
+
+        let result = attr.add_option(&tx, "Done");
+
+        assert_eq!(
+            result,
+            Err(Error::ModuleError(
+                "Attribute is not a select field".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn rename_option() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let schema = ESD::create_default(&tx);
+        let attr = create_select(&tx, &schema, Quantity::List);
+
+        let data = serde_json::from_str(&format!(
+            r#"
+            {{
+              "{attr}": ["Todo", "Doing"]
+            }}
+            "#
+        ))
+        .unwrap();
+
+        let entity = add_entity(&tx, &schema, data).unwrap();
+
+        attr.rename_option(&tx, "Todo", "Backlog").unwrap();
+
+        let request = EntityRequest {
+            0: vec![EntityField::Attribute(attr.clone())],
+        };
+
+        let result = get(&tx, &entity, &request).unwrap();
+        let val = result.get(&attr.to_string()).unwrap();
+
+        let expected = Value::Array(vec![
+            Value::String("Backlog".to_string()),
+            Value::String("Doing".to_string()),
+        ]);
+
+        assert_eq!(val, &expected);
+    }
+
+    #[test]
+    fn rename_missing_option_error() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let schema = ESD::create_default(&tx);
+        let attr = create_select(&tx, &schema, Quantity::Required);
+
+        let result = attr.rename_option(&tx, "Done", "Finished");
+
+        assert_eq!(result, Err(Error::QueryReturnedNoRows));
+    }
+}
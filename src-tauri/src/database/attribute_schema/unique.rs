@@ -0,0 +1,213 @@
+use rusqlite::{params, Error, OptionalExtension, Result, ToSql, Transaction};
+
+use crate::models::{
+    attribute_schema::{AttributeSchema, AttributeSchemaId},
+    attribute_type::{AttributeType, SimpleAttributeType},
+    entity::EntityId,
+};
+
+#[cfg(test)]
+const UNIQUE_MESSAGE: &str = "Attempted to add a duplicate value to a unique field";
+
+// Returned when a value is already held by another entity. The database
+// layer only returns rusqlite errors, so it travels boxed inside
+// rusqlite::Error::UserFunctionError until the commands unwrap it into their
+// own error variant
+#[derive(Debug, PartialEq, thiserror::Error)]
+#[error("Value of {name} is already held by entity {entity}")]
+pub struct UniqueViolation {
+    pub attribute: AttributeSchemaId,
+    pub name: String,
+    pub entity: EntityId,
+}
+
+impl UniqueViolation {
+    pub fn from_error(err: &Error) -> Option<&Self> {
+        match err {
+            Error::UserFunctionError(err) => err.downcast_ref(),
+            _ => None,
+        }
+    }
+
+    // Takes the violation out of the error, giving the error back otherwise
+    pub fn take(err: Error) -> std::result::Result<Self, Error> {
+        match err {
+            Error::UserFunctionError(err) => match err.downcast() {
+                Ok(violation) => Ok(*violation),
+                Err(err) => Err(Error::UserFunctionError(err)),
+            },
+            err => Err(err),
+        }
+    }
+}
+
+impl From<UniqueViolation> for Error {
+    fn from(violation: UniqueViolation) -> Self {
+        Error::UserFunctionError(Box::new(violation))
+    }
+}
+
+impl AttributeSchema {
+    pub fn check_unique(&self) -> Result<()> {
+        check_unique_type(&self.attr_type)
+    }
+}
+
+pub fn check_unique_type(attr_type: &AttributeType) -> Result<()> {
+    match attr_type {
+        AttributeType::Computed(..)
+        | AttributeType::Rollup(..)
+        | AttributeType::Backlink(..)
+        | AttributeType::Simple(
+            SimpleAttributeType::Boolean
+            | SimpleAttributeType::Longform
+            | SimpleAttributeType::Location,
+        ) => Err(Error::ModuleError(
+            "Unique values cannot be used on this field".to_string(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+// Checks no other entity holds the value, in the form it is about to be
+// stored in, for the attribute when it is unique. The unique triggers
+// generated in build_attr back this up for writes made elsewhere
+pub fn check_unique_value(
+    tx: &Transaction,
+    table: &str,
+    schema: &AttributeSchemaId,
+    entity: &EntityId,
+    value: &dyn ToSql,
+) -> Result<()> {
+    let holder = tx
+        .query_row(
+            &format!(
+                "SELECT v.entity, a.name FROM {table} v INNER JOIN attribute_schema a ON a.id = v.schema
+                  WHERE v.schema = ?1 AND a.is_unique = 1 AND v.value = ?2 AND v.entity != ?3
+                  LIMIT 1"
+            ),
+            params![schema, value, entity],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+
+    match holder {
+        Some((entity, name)) => Err(UniqueViolation {
+            attribute: schema.clone(),
+            name,
+            entity,
+        }
+        .into()),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::{params, Error};
+    use serde_json::json;
+
+    use crate::{
+        database::{
+            entity::add_entity,
+            test::test_util::{setup, ASD, ESD},
+            New,
+        },
+        models::{
+            attribute::GenericAttributeId,
+            attribute_schema::{AttributeSchema, Constraints, CreateAttributeSchema, Quantity},
+            attribute_type::{CreateAttributeType, SimpleAttributeType},
+            entity::EntityId,
+        },
+        utils::get_timestamp,
+    };
+
+    use super::*;
+
+    #[test]
+    fn unique_violation() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let es = ESD::create_default(&tx);
+        let attr = ASD::default().unique().create(&tx, &es);
+
+        let first = add_entity(&tx, &es, json!({ attr.to_string(): "978-0" })).unwrap();
+        add_entity(&tx, &es, json!({ attr.to_string(): "978-1" })).unwrap();
+
+        let err = add_entity(&tx, &es, json!({ attr.to_string(): "978-0" })).unwrap_err();
+        assert_eq!(
+            UniqueViolation::from_error(&err),
+            Some(&UniqueViolation {
+                attribute: attr,
+                name: "Foo".to_string(),
+                entity: first.clone(),
+            })
+        );
+        assert_eq!(
+            err.to_string(),
+            format!("Value of Foo is already held by entity {first}")
+        );
+
+        let violation = UniqueViolation::take(err).unwrap();
+        assert_eq!(violation.entity, first);
+    }
+
+    #[test]
+    fn unique_enforced_in_sql() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let es = ESD::create_default(&tx);
+        let attr = ASD::default().unique().create(&tx, &es);
+        add_entity(&tx, &es, json!({ attr.to_string(): "Taken" })).unwrap();
+
+        let entity = EntityId::new();
+        tx.execute(
+            "INSERT INTO entity (id, schema, created, updated) VALUES (?1, ?2, ?3, ?3)",
+            params![entity, es, get_timestamp()],
+        )
+        .unwrap();
+
+        let result = tx.execute(
+            "INSERT INTO text_attribute (id, entity, schema, value, created, updated) VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+            params![GenericAttributeId::new(), entity, attr, "Taken", get_timestamp()],
+        );
+        assert_eq!(
+            result,
+            Err(Error::SqliteFailure(
+                libsqlite3_sys::Error {
+                    code: libsqlite3_sys::ErrorCode::ConstraintViolation,
+                    extended_code: 1811
+                },
+                Some(UNIQUE_MESSAGE.to_string())
+            ))
+        );
+    }
+
+    #[test]
+    fn unique_invalid_type_error() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let es = ESD::create_default(&tx);
+        let result = AttributeSchema::new(
+            &tx,
+            CreateAttributeSchema {
+                entity: es,
+                name: "Foo".to_string(),
+                quantity: Quantity::Required,
+                constraints: Constraints::default(),
+                default: None,
+                unique: true,
+                attr_type: CreateAttributeType::Simple(SimpleAttributeType::Boolean),
+            },
+        );
+        assert_eq!(
+            result.map(|_| ()),
+            Err(Error::ModuleError(
+                "Unique values cannot be used on this field".to_string()
+            ))
+        );
+    }
+}
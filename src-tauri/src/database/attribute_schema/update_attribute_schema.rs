@@ -0,0 +1,870 @@
+use std::collections::HashMap;
+
+use rusqlite::{params, types::ValueRef, Error, OptionalExtension, Result, Transaction};
+use serde_json::Value;
+
+use crate::{
+    database::{
+        attribute_schema::{GetSchemaMap, RawAttributeSchema},
+        attribute_type::value_table,
+        Get, Insert,
+    },
+    models::{
+        attribute_schema::{
+            AttributeSchemaId, MigrationFailure, MigrationReport, Quantity, UpdateAttributeSchema,
+        },
+        attribute_type::{
+            AttributeType, CreateAttributeType, CreateReferenceAttribute, SimpleAttributeType,
+        },
+        entity::EntityId,
+        entity_schema::EntitySchemaId,
+    },
+    utils::get_timestamp,
+};
+
+use super::unique::check_unique_type;
+
+impl MigrationReport {
+    fn fail(&mut self, entity: &EntityId, value: Value, reason: String) {
+        self.failures.push(MigrationFailure {
+            entity: entity.clone(),
+            value,
+            reason,
+        });
+    }
+}
+
+fn type_name(attr_type: &AttributeType) -> String {
+    match attr_type {
+        AttributeType::Simple(simple) => format!("{simple:?}"),
+        AttributeType::Reference(..) => "Reference".to_string(),
+        AttributeType::Select(..) => "Select".to_string(),
+        AttributeType::Measure(..) => "Measure".to_string(),
+        AttributeType::Computed(..) => "Computed".to_string(),
+        AttributeType::Rollup(..) => "Rollup".to_string(),
+        AttributeType::Backlink(..) => "Backlink".to_string(),
+    }
+}
+
+// Types whose values can be read back as plain values and converted
+fn convertible(attr_type: &AttributeType) -> bool {
+    matches!(
+        attr_type,
+        AttributeType::Simple(
+            SimpleAttributeType::Text
+                | SimpleAttributeType::RichText
+                | SimpleAttributeType::Integer
+                | SimpleAttributeType::Number
+                | SimpleAttributeType::Date
+                | SimpleAttributeType::DateTime
+                | SimpleAttributeType::Boolean
+                | SimpleAttributeType::Url
+        ) | AttributeType::Reference(..)
+            | AttributeType::Select(..)
+            | AttributeType::Measure(..)
+    )
+}
+
+fn is_text(attr_type: &AttributeType) -> bool {
+    matches!(
+        attr_type,
+        AttributeType::Simple(SimpleAttributeType::Text | SimpleAttributeType::RichText)
+    )
+}
+
+fn check_conversion(
+    tx: &Transaction,
+    from: &AttributeType,
+    to: &AttributeType,
+    lookup: Option<&AttributeSchemaId>,
+) -> Result<()> {
+    let error = || {
+        Err(Error::ModuleError(format!(
+            "Cannot convert {} fields to {}",
+            type_name(from),
+            type_name(to)
+        )))
+    };
+
+    if !convertible(from) || !convertible(to) {
+        return error();
+    }
+
+    match (from, to) {
+        (AttributeType::Reference(..), AttributeType::Reference(..)) => Ok(()),
+        (AttributeType::Reference(..), _) => error(),
+        (_, AttributeType::Reference(..)) if is_text(from) => {
+            let Some(lookup) = lookup else {
+                return Err(Error::ModuleError(
+                    "Converting text to references needs a lookup attribute".to_string(),
+                ));
+            };
+
+            if is_text(&RawAttributeSchema::get(tx, lookup)?.attr_type) {
+                Ok(())
+            } else {
+                Err(Error::ModuleError(
+                    "Lookup attribute must be a text field".to_string(),
+                ))
+            }
+        }
+        (_, AttributeType::Reference(..)) => error(),
+        _ => Ok(()),
+    }
+}
+
+// Finds the single entity whose lookup attribute holds the text
+fn lookup_title(
+    tx: &Transaction,
+    lookup: &AttributeSchemaId,
+    title: &str,
+) -> std::result::Result<Value, String> {
+    let ids = tx
+        .prepare("SELECT entity FROM text_attribute WHERE schema = ?1 AND value = ?2 LIMIT 2")
+        .and_then(|mut statement| {
+            statement
+                .query_map(params![lookup, title], |row| row.get(0))?
+                .collect::<Result<Vec<EntityId>>>()
+        })
+        .map_err(|err| err.to_string())?;
+
+    match ids.as_slice() {
+        [id] => Ok(Value::String(id.to_string())),
+        [] => Err(format!("No entity is titled {title}")),
+        _ => Err(format!("Several entities are titled {title}")),
+    }
+}
+
+// Measures carry their unit when written out as text
+fn as_text(from: &AttributeType, value: &Value) -> String {
+    match (from, value) {
+        (AttributeType::Measure(measure), Value::Number(number)) => {
+            format!("{number} {}", measure.unit)
+        }
+        (_, Value::String(text)) => text.clone(),
+        _ => value.to_string(),
+    }
+}
+
+// Converts a stored value to one accepted by the new type, or gives the
+// reason it cannot be
+fn convert(
+    tx: &Transaction,
+    value: &Value,
+    from: &AttributeType,
+    to: &AttributeType,
+    lookup: Option<&AttributeSchemaId>,
+) -> std::result::Result<Value, String> {
+    let text = as_text(from, value);
+
+    let converted = match to {
+        AttributeType::Simple(SimpleAttributeType::Integer) => match value {
+            Value::Number(number) if number.is_i64() => Some(value.clone()),
+            Value::Number(number) => number
+                .as_f64()
+                .filter(|number| number.fract() == 0.0)
+                .map(|number| Value::from(number as i64)),
+            Value::String(text) => text.trim().parse::<i64>().ok().map(Value::from),
+            Value::Bool(bool) => Some(Value::from(*bool as i64)),
+            _ => None,
+        }
+        .ok_or_else(|| format!("{text} is not an integer"))?,
+        AttributeType::Simple(SimpleAttributeType::Number) => match value {
+            Value::Number(..) => Some(value.clone()),
+            Value::String(text) => text.trim().parse::<f64>().ok().map(Value::from),
+            _ => None,
+        }
+        .ok_or_else(|| format!("{text} is not a number"))?,
+        AttributeType::Simple(SimpleAttributeType::Boolean) => match value {
+            Value::Bool(..) => Some(value.clone()),
+            Value::String(text) if text.eq_ignore_ascii_case("true") => Some(Value::Bool(true)),
+            Value::String(text) if text.eq_ignore_ascii_case("false") => Some(Value::Bool(false)),
+            Value::Number(number) => match number.as_f64() {
+                Some(1.0) => Some(Value::Bool(true)),
+                Some(0.0) => Some(Value::Bool(false)),
+                _ => None,
+            },
+            _ => None,
+        }
+        .ok_or_else(|| format!("{text} is not a boolean"))?,
+        // Measures parse units from strings, and take plain numbers as they are
+        AttributeType::Measure(..) => match (from, value) {
+            (AttributeType::Measure(..), _) | (_, Value::String(..)) => Value::String(text),
+            _ => value.clone(),
+        },
+        AttributeType::Reference(..) => match (from, lookup) {
+            (AttributeType::Reference(..), _) => value.clone(),
+            (_, Some(lookup)) => lookup_title(tx, lookup, &text)?,
+            (_, None) => return Err("No lookup attribute to find references by".to_string()),
+        },
+        _ => Value::String(text),
+    };
+
+    Ok(converted)
+}
+
+// An attribute's stored values in the shape they would be inserted in,
+// grouped by entity and in list order
+fn read_values(
+    tx: &Transaction,
+    schema: &RawAttributeSchema,
+    table: &str,
+) -> Result<HashMap<EntityId, Vec<Value>>> {
+    let reference = matches!(schema.attr_type, AttributeType::Reference(..));
+    let mut statement = tx.prepare(&format!(
+        "SELECT entity, value{} FROM {table} WHERE schema = ?1 ORDER BY entity, position",
+        if reference { ", properties" } else { "" }
+    ))?;
+    let mut rows = statement.query(params![schema.id])?;
+
+    let mut values: HashMap<EntityId, Vec<Value>> = HashMap::new();
+    while let Some(row) = rows.next()? {
+        let value = match &schema.attr_type {
+            AttributeType::Reference(..) => {
+                let target: EntityId = row.get(1)?;
+                match row.get::<_, Option<Value>>(2)? {
+                    Some(Value::Object(mut properties)) => {
+                        properties.insert("target".to_string(), Value::String(target.to_string()));
+                        Value::Object(properties)
+                    }
+                    _ => Value::String(target.to_string()),
+                }
+            }
+            AttributeType::Simple(SimpleAttributeType::Boolean) => Value::Bool(row.get(1)?),
+            _ => match row.get_ref(1)? {
+                ValueRef::Integer(value) => Value::from(value),
+                ValueRef::Real(value) => Value::from(value),
+                ValueRef::Text(..) => Value::String(row.get(1)?),
+                _ => Value::Null,
+            },
+        };
+
+        values.entry(row.get(0)?).or_default().push(value);
+    }
+
+    Ok(values)
+}
+
+// Options for a select field converted from another type, taken from the
+// distinct values in sorted order
+fn derive_options(from: &AttributeType, values: &HashMap<EntityId, Vec<Value>>) -> Vec<String> {
+    let mut options = Vec::new();
+    for value in values.values().flatten() {
+        let option = as_text(from, value);
+
+        if !options.contains(&option) {
+            options.push(option);
+        }
+    }
+
+    options.sort();
+    options
+}
+
+// Computed fields read other attributes by name and type, so every schema
+// seeing the attribute is checked again after it changes
+pub(crate) fn check_computed(tx: &Transaction, owner: &EntitySchemaId) -> Result<()> {
+    for schema in owner.descendants(tx)? {
+        let map = RawAttributeSchema::get_map(tx, &schema)?;
+
+        for attribute in map.values() {
+            if let AttributeType::Computed(computed) = &attribute.attr_type {
+                computed.check(&map)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+impl AttributeSchemaId {
+    // Renames the attribute or changes its type or quantity, converting the
+    // stored values. A dry run reports the values that would fail to convert
+    // and leaves everything as it was
+    pub fn update(
+        &self,
+        tx: &Transaction,
+        data: UpdateAttributeSchema,
+        dry_run: bool,
+    ) -> Result<MigrationReport> {
+        let old = RawAttributeSchema::get(tx, self)?;
+        let owner: EntitySchemaId = tx.query_row(
+            "SELECT entity FROM attribute_schema WHERE id = ?1",
+            params![self],
+            |row| row.get(0),
+        )?;
+
+        if let Some(name) = &data.name {
+            owner.check_attribute_name(tx, name)?;
+        }
+
+        let quantity = data.quantity.unwrap_or(old.quantity.clone());
+        if data.attr_type.is_some() || quantity != old.quantity {
+            self.check_update(
+                tx,
+                &old,
+                data.attr_type.as_ref(),
+                &quantity,
+                data.lookup.as_ref(),
+            )?;
+        }
+
+        tx.execute_batch("SAVEPOINT attribute_migration")?;
+
+        let result = self.apply_update(
+            tx,
+            &old,
+            &owner,
+            data.name,
+            quantity,
+            data.attr_type,
+            data.lookup.as_ref(),
+        );
+
+        let failed = !matches!(&result, Ok(report) if report.failures.is_empty());
+        if dry_run || failed {
+            tx.execute_batch("ROLLBACK TO attribute_migration; RELEASE attribute_migration")?;
+        } else {
+            tx.execute_batch("RELEASE attribute_migration")?;
+        }
+
+        match result {
+            Ok(report) if !dry_run && !report.failures.is_empty() => Err(Error::ModuleError(
+                format!("{} values failed to migrate", report.failures.len()),
+            )),
+            result => result,
+        }
+    }
+
+    fn check_update(
+        &self,
+        tx: &Transaction,
+        old: &RawAttributeSchema,
+        attr_type: Option<&CreateAttributeType>,
+        quantity: &Quantity,
+        lookup: Option<&AttributeSchemaId>,
+    ) -> Result<()> {
+        if value_table(&old.attr_type).is_none() {
+            return Err(Error::ModuleError(
+                "Computed, rollup and backlink fields can only be renamed".to_string(),
+            ));
+        }
+
+        let full = attr_type
+            .map(|attr_type| attr_type.get_full(tx))
+            .transpose()?;
+        let new_type = full.as_ref().unwrap_or(&old.attr_type);
+
+        if let Some(attr_type) = attr_type {
+            if let CreateAttributeType::Reference(CreateReferenceAttribute {
+                inverse: Some(..),
+                ..
+            }) = attr_type
+            {
+                return Err(Error::ModuleError(
+                    "Inverse attributes can only be added with a new reference".to_string(),
+                ));
+            }
+
+            let dependent: Option<String> = tx
+                .query_row(
+                    "SELECT name FROM attribute_schema
+                      WHERE rollup_reference = ?1 OR rollup_attribute = ?1 OR backlink_reference = ?1",
+                    params![self],
+                    |row| row.get(0),
+                )
+                .optional()?;
+
+            if let Some(dependent) = dependent {
+                return Err(Error::ModuleError(format!(
+                    "Attribute is used by {dependent}"
+                )));
+            }
+
+            check_conversion(tx, &old.attr_type, new_type, lookup)?;
+        }
+
+        old.constraints.check_schema(new_type, quantity)?;
+
+        if let Some(default) = &old.default {
+            default.check_schema(new_type, quantity, old.unique)?;
+        }
+
+        if old.unique {
+            check_unique_type(new_type)?;
+        }
+
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn apply_update(
+        &self,
+        tx: &Transaction,
+        old: &RawAttributeSchema,
+        owner: &EntitySchemaId,
+        name: Option<String>,
+        quantity: Quantity,
+        attr_type: Option<CreateAttributeType>,
+        lookup: Option<&AttributeSchemaId>,
+    ) -> Result<MigrationReport> {
+        if let Some(name) = name {
+            tx.execute(
+                "UPDATE attribute_schema SET name = ?1, updated = ?2 WHERE id = ?3",
+                params![name, get_timestamp(), self],
+            )?;
+        }
+
+        let report = match attr_type {
+            Some(attr_type) => self.move_values(tx, old, owner, quantity, attr_type, lookup)?,
+            None if quantity != old.quantity => self.change_quantity(tx, old, owner, quantity)?,
+            None => MigrationReport::default(),
+        };
+
+        check_computed(tx, owner)?;
+
+        // The default has to suit the field it has become
+        RawAttributeSchema::get(tx, self)?.check_default(tx, owner)?;
+
+        Ok(report)
+    }
+
+    // The values stay where they are, so only the count held by each entity
+    // is checked against the new quantity
+    fn change_quantity(
+        &self,
+        tx: &Transaction,
+        old: &RawAttributeSchema,
+        owner: &EntitySchemaId,
+        quantity: Quantity,
+    ) -> Result<MigrationReport> {
+        let table = value_table(&old.attr_type).ok_or(Error::InvalidQuery)?;
+
+        tx.execute(
+            "UPDATE attribute_schema SET quantity = ?1, updated = ?2 WHERE id = ?3",
+            params![quantity, get_timestamp(), self],
+        )?;
+
+        let new = RawAttributeSchema::get(tx, self)?;
+
+        let mut report = MigrationReport::default();
+        let mut missing = Vec::new();
+        for entity in owner.entities(tx)? {
+            let count: usize = tx.query_row(
+                &format!("SELECT COUNT(*) FROM {table} WHERE entity = ?1 AND schema = ?2"),
+                params![entity, self],
+                |row| row.get(0),
+            )?;
+
+            match (&new.quantity, count) {
+                (Quantity::List, _) => {}
+                (Quantity::Required, 0) if new.default.is_some() => missing.push(entity),
+                (Quantity::Required, 0) => report.fail(
+                    &entity,
+                    Value::Null,
+                    "Missing a value for a required field".to_string(),
+                ),
+                (_, 0 | 1) => {}
+                (_, count) => report.fail(
+                    &entity,
+                    Value::Null,
+                    format!("Has {count} values but the field holds one"),
+                ),
+            }
+        }
+
+        new.backfill(tx, &missing)?;
+
+        Ok(report)
+    }
+
+    // Moves the values to the table of the new type, converting each one
+    fn move_values(
+        &self,
+        tx: &Transaction,
+        old: &RawAttributeSchema,
+        owner: &EntitySchemaId,
+        quantity: Quantity,
+        attr_type: CreateAttributeType,
+        lookup: Option<&AttributeSchemaId>,
+    ) -> Result<MigrationReport> {
+        let table = value_table(&old.attr_type).ok_or(Error::InvalidQuery)?;
+        let mut values = read_values(tx, old, table)?;
+
+        let attr_type = match attr_type {
+            CreateAttributeType::Select { options } if options.is_empty() => {
+                CreateAttributeType::Select {
+                    options: derive_options(&old.attr_type, &values),
+                }
+            }
+            attr_type => attr_type,
+        };
+
+        tx.execute(
+            "INSERT INTO attribute_migration (schema) VALUES (?1)",
+            params![self],
+        )?;
+        tx.execute(
+            &format!("DELETE FROM {table} WHERE schema = ?1"),
+            params![self],
+        )?;
+        tx.execute(
+            "DELETE FROM attribute_migration WHERE schema = ?1",
+            params![self],
+        )?;
+
+        self.set_type(tx, &attr_type, &quantity)?;
+
+        let new = RawAttributeSchema::get(tx, self)?;
+
+        let mut report = MigrationReport::default();
+        for entity in owner.entities(tx)? {
+            let mut converted = Vec::new();
+            let mut failed = false;
+            for value in values.remove(&entity).unwrap_or_default() {
+                match convert(tx, &value, &old.attr_type, &new.attr_type, lookup) {
+                    Ok(value) => converted.push(value),
+                    Err(reason) => {
+                        report.fail(&entity, value, reason);
+                        failed = true;
+                    }
+                }
+            }
+
+            if failed {
+                continue;
+            }
+
+            let value = match (&new.quantity, converted.len()) {
+                (Quantity::List, _) => Value::Array(converted),
+                (Quantity::Required, 0) => match &new.default {
+                    Some(default) => default.resolve(&new.attr_type),
+                    None => {
+                        report.fail(
+                            &entity,
+                            Value::Null,
+                            "Missing a value for a required field".to_string(),
+                        );
+                        continue;
+                    }
+                },
+                (_, 0) => continue,
+                (_, 1) => converted.remove(0),
+                (_, count) => {
+                    report.fail(
+                        &entity,
+                        Value::Null,
+                        format!("Has {count} values but the field holds one"),
+                    );
+                    continue;
+                }
+            };
+
+            if let Err(err) = new.insert(tx, &entity, &value) {
+                report.fail(&entity, value, err.to_string());
+            }
+        }
+
+        Ok(report)
+    }
+
+    // Replaces the type columns and the options, targets and edges that
+    // belong to the type
+    fn set_type(
+        &self,
+        tx: &Transaction,
+        attr_type: &CreateAttributeType,
+        quantity: &Quantity,
+    ) -> Result<()> {
+        tx.execute(
+            "UPDATE attribute_schema
+              SET type = ?1, unit = ?2, quantity = ?3, expression = NULL, rollup_reference = NULL,
+                rollup_attribute = NULL, aggregate = NULL, backlink_reference = NULL, updated = ?4
+              WHERE id = ?5",
+            params![
+                attr_type,
+                attr_type.get_unit(),
+                quantity,
+                get_timestamp(),
+                self
+            ],
+        )?;
+
+        tx.execute("DELETE FROM select_option WHERE schema = ?1", params![self])?;
+        tx.execute(
+            "DELETE FROM reference_target WHERE schema = ?1",
+            params![self],
+        )?;
+        tx.execute(
+            "DELETE FROM reference_edge WHERE schema = ?1",
+            params![self],
+        )?;
+
+        match attr_type {
+            CreateAttributeType::Select { options } => {
+                for option in options {
+                    self.add_option(tx, option)?;
+                }
+            }
+            CreateAttributeType::Reference(reference) => {
+                for target in &reference.targets {
+                    self.add_target(tx, target)?;
+                }
+
+                for edge in &reference.edges {
+                    self.add_edge(tx, edge)?;
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::{params, Error, Transaction};
+    use serde_json::{json, Value};
+
+    use crate::{
+        database::{
+            entity::add_entity,
+            test::test_util::{setup, ASD, ESD},
+            Get,
+        },
+        models::{
+            attribute_schema::{
+                AttributeSchema, AttributeSchemaId, MigrationFailure, MigrationReport, Quantity,
+                UpdateAttributeSchema,
+            },
+            attribute_type::{
+                AttributeType, CreateAttributeType, CreateReferenceAttribute, SelectAttribute,
+                SimpleAttributeType,
+            },
+            entity::EntityId,
+        },
+    };
+
+    fn stored(tx: &Transaction, table: &str, attr: &AttributeSchemaId) -> Vec<String> {
+        let mut statement = tx
+            .prepare(&format!(
+                "SELECT CAST(value AS TEXT) FROM {table} WHERE schema = ?1 ORDER BY value"
+            ))
+            .unwrap();
+        statement
+            .query_map(params![attr], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap()
+    }
+
+    fn retype(attr_type: CreateAttributeType) -> UpdateAttributeSchema {
+        UpdateAttributeSchema {
+            attr_type: Some(attr_type),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn rename() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let es = ESD::create_default(&tx);
+        let attr = ASD::default().name("Title").create(&tx, &es);
+        ASD::default().name("Author").create(&tx, &es);
+
+        let update = |name: &str| UpdateAttributeSchema {
+            name: Some(name.to_string()),
+            ..Default::default()
+        };
+
+        assert!(attr.update(&tx, update("Author"), false).is_err());
+
+        attr.update(&tx, update("Name"), false).unwrap();
+        assert_eq!(AttributeSchema::get(&tx, &attr).unwrap().name, "Name");
+    }
+
+    #[test]
+    fn text_to_integer() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let es = ESD::create_default(&tx);
+        let attr = ASD::default().quantity(Quantity::Optional).create(&tx, &es);
+        add_entity(&tx, &es, json!({ attr.to_string(): "12" })).unwrap();
+        let bad = add_entity(&tx, &es, json!({ attr.to_string(): "twelve" })).unwrap();
+
+        let integer = || retype(CreateAttributeType::Simple(SimpleAttributeType::Integer));
+
+        assert_eq!(
+            attr.update(&tx, integer(), true),
+            Ok(MigrationReport {
+                failures: vec![MigrationFailure {
+                    entity: bad.clone(),
+                    value: json!("twelve"),
+                    reason: "twelve is not an integer".to_string(),
+                }]
+            })
+        );
+        assert_eq!(
+            attr.update(&tx, integer(), false),
+            Err(Error::ModuleError("1 values failed to migrate".to_string()))
+        );
+
+        // Nothing changed after the dry run or the failed update
+        assert_eq!(
+            AttributeSchema::get(&tx, &attr).unwrap().attr_type,
+            AttributeType::Simple(SimpleAttributeType::Text)
+        );
+        assert_eq!(stored(&tx, "text_attribute", &attr), vec!["12", "twelve"]);
+
+        tx.execute(
+            "UPDATE text_attribute SET value = '7' WHERE entity = ?1",
+            params![bad],
+        )
+        .unwrap();
+
+        assert_eq!(
+            attr.update(&tx, integer(), false),
+            Ok(MigrationReport::default())
+        );
+        assert_eq!(stored(&tx, "text_attribute", &attr), Vec::<String>::new());
+        assert_eq!(stored(&tx, "integer_attribute", &attr), vec!["7", "12"]);
+    }
+
+    #[test]
+    fn list_to_optional() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let es = ESD::create_default(&tx);
+        let attr = ASD::default().quantity(Quantity::List).create(&tx, &es);
+        add_entity(&tx, &es, json!({ attr.to_string(): ["A"] })).unwrap();
+        let conflict = add_entity(&tx, &es, json!({ attr.to_string(): ["B", "C"] })).unwrap();
+
+        let optional = UpdateAttributeSchema {
+            quantity: Some(Quantity::Optional),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            attr.update(&tx, optional, true),
+            Ok(MigrationReport {
+                failures: vec![MigrationFailure {
+                    entity: conflict,
+                    value: Value::Null,
+                    reason: "Has 2 values but the field holds one".to_string(),
+                }]
+            })
+        );
+        assert_eq!(
+            AttributeSchema::get(&tx, &attr).unwrap().quantity,
+            Quantity::List
+        );
+
+        // Going back to a list always fits
+        let single = ASD::default()
+            .name("Bar")
+            .quantity(Quantity::Optional)
+            .create(&tx, &es);
+        let list = UpdateAttributeSchema {
+            quantity: Some(Quantity::List),
+            ..Default::default()
+        };
+        assert_eq!(
+            single.update(&tx, list, false),
+            Ok(MigrationReport::default())
+        );
+    }
+
+    #[test]
+    fn text_to_select() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let es = ESD::create_default(&tx);
+        let attr = ASD::default().create(&tx, &es);
+        for colour in ["Red", "Blue", "Red"] {
+            add_entity(&tx, &es, json!({ attr.to_string(): colour })).unwrap();
+        }
+
+        attr.update(
+            &tx,
+            retype(CreateAttributeType::Select {
+                options: Vec::new(),
+            }),
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(
+            AttributeSchema::get(&tx, &attr).unwrap().attr_type,
+            AttributeType::Select(SelectAttribute {
+                options: vec!["Blue".to_string(), "Red".to_string()]
+            })
+        );
+        assert_eq!(
+            stored(&tx, "select_attribute", &attr),
+            vec!["Blue", "Red", "Red"]
+        );
+    }
+
+    #[test]
+    fn text_to_reference() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let person = ESD::default().name("Person").create(&tx);
+        let name = ASD::default().name("Name").create(&tx, &person);
+        let ann = add_entity(&tx, &person, json!({ name.to_string(): "Ann" })).unwrap();
+
+        let book = ESD::default().name("Book").create(&tx);
+        let author = ASD::default().name("Author").create(&tx, &book);
+        add_entity(&tx, &book, json!({ author.to_string(): "Ann" })).unwrap();
+        let unknown = add_entity(&tx, &book, json!({ author.to_string(): "Bob" })).unwrap();
+
+        let reference = |lookup: Option<&AttributeSchemaId>| UpdateAttributeSchema {
+            attr_type: Some(CreateAttributeType::Reference(CreateReferenceAttribute {
+                targets: vec![person.clone()],
+                inverse: None,
+                edges: Vec::new(),
+            })),
+            lookup: lookup.cloned(),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            author.update(&tx, reference(None), true),
+            Err(Error::ModuleError(
+                "Converting text to references needs a lookup attribute".to_string()
+            ))
+        );
+        assert_eq!(
+            author.update(&tx, reference(Some(&name)), true),
+            Ok(MigrationReport {
+                failures: vec![MigrationFailure {
+                    entity: unknown,
+                    value: json!("Bob"),
+                    reason: "No entity is titled Bob".to_string(),
+                }]
+            })
+        );
+
+        let bob: EntityId = add_entity(&tx, &person, json!({ name.to_string(): "Bob" })).unwrap();
+        author.update(&tx, reference(Some(&name)), false).unwrap();
+
+        let mut targets: Vec<EntityId> = tx
+            .prepare("SELECT value FROM reference_attribute WHERE schema = ?1")
+            .unwrap()
+            .query_map(params![author], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        targets.sort_by_key(|target| target.to_string());
+        let mut expected = vec![ann, bob];
+        expected.sort_by_key(|target| target.to_string());
+        assert_eq!(targets, expected);
+    }
+}
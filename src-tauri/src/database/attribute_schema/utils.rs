@@ -1,9 +1,9 @@
 use rusqlite::{
-    types::{FromSql, FromSqlError},
+    types::{FromSql, FromSqlError, ValueRef},
     ToSql,
 };
 
-use crate::models::attribute_schema::Quantity;
+use crate::models::attribute_schema::{Constraints, DefaultValue, Quantity};
 
 impl ToSql for Quantity {
     fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
@@ -26,3 +26,42 @@ impl FromSql for Quantity {
         }
     }
 }
+
+// Stored as JSON, with no rules stored as NULL
+impl ToSql for Constraints {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        if self == &Constraints::default() {
+            return Ok(rusqlite::types::Null.into());
+        }
+
+        match serde_json::to_string(self) {
+            Ok(json) => Ok(json.into()),
+            Err(err) => Err(rusqlite::Error::ToSqlConversionFailure(Box::new(err))),
+        }
+    }
+}
+
+impl FromSql for Constraints {
+    fn column_result(value: ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        match value {
+            ValueRef::Null => Ok(Constraints::default()),
+            value => serde_json::from_str(value.as_str()?)
+                .map_err(|err| FromSqlError::Other(Box::new(err))),
+        }
+    }
+}
+
+impl ToSql for DefaultValue {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        match serde_json::to_string(self) {
+            Ok(json) => Ok(json.into()),
+            Err(err) => Err(rusqlite::Error::ToSqlConversionFailure(Box::new(err))),
+        }
+    }
+}
+
+impl FromSql for DefaultValue {
+    fn column_result(value: ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        serde_json::from_str(value.as_str()?).map_err(|err| FromSqlError::Other(Box::new(err)))
+    }
+}
@@ -1,35 +1,134 @@
+use std::collections::HashMap;
+
 use crate::{
+    database::{
+        attribute_schema::{check_unique_value, RawAttributeSchema, SchemaMap},
+        blob::blob_exists,
+        response_map::EntityData,
+        Get,
+    },
+    formula::{self, Expr, ValueType},
     models::{
-        attribute_schema::AttributeSchemaId,
+        attachment::{AttachmentAttributeId, CreateAttachment},
+        attribute_schema::{AttributeSchemaId, Quantity},
         attribute_type::{
-            AttributeType, CreateAttributeType, CreateReferenceAttribute, ReferenceAttribute,
-            ReferenceAttributeId, SimpleAttributeType, TextAttributeId,
+            Aggregate, AttributeType, BacklinkAttribute, BooleanAttributeId, ComputedAttribute,
+            CreateAttributeType, CreateReferenceAttribute, DateAttributeId, DateTimeAttributeId,
+            EdgeProperty, IntegerAttributeId, MeasureAttribute, NumberAttributeId,
+            ReferenceAttribute, ReferenceAttributeId, ReferenceTarget, RollupAttribute,
+            SelectAttribute, SelectAttributeId, SimpleAttributeType, TextAttributeId,
+            UrlAttributeId,
         },
         entity::EntityId,
         entity_schema::EntitySchemaId,
+        location::{Location, LocationAttributeId},
         longform::{LongformTextId, TextBlockId},
     },
+    units::{check_unit, parse_measure},
     utils::get_timestamp,
 };
+use chrono::{DateTime, FixedOffset, NaiveDate};
 use rusqlite::{
     params,
-    types::{FromSqlError, FromSqlResult},
-    Error, Result, ToSql, Transaction,
+    types::{FromSql, FromSqlError, FromSqlResult, ValueRef},
+    Error, OptionalExtension, Result, ToSql, Transaction,
 };
-use serde_json::Value;
+use serde_json::{Map, Number, Value};
+use url::Url;
 
 impl CreateReferenceAttribute {
     fn get_full(&self, tx: &Transaction) -> Result<ReferenceAttribute> {
-        tx.query_row(
-            "SELECT id, name FROM entity_schema WHERE id=?1",
-            params![self.id],
-            |row| {
-                Ok(ReferenceAttribute {
-                    id: row.get(0)?,
-                    name: row.get(1)?,
-                })
-            },
-        )
+        let mut targets: Vec<ReferenceTarget> = Vec::new();
+
+        for id in &self.targets {
+            if targets.iter().any(|target| &target.id == id) {
+                return Err(Error::ModuleError(
+                    "Reference targets must be distinct".to_string(),
+                ));
+            }
+
+            let name = tx
+                .query_row(
+                    "SELECT name FROM entity_schema WHERE id=?1",
+                    params![id],
+                    |row| row.get(0),
+                )
+                .optional()?;
+
+            let Some(name) = name else {
+                return Err(Error::ModuleError(
+                    "Reference target is not a valid entity schema".to_string(),
+                ));
+            };
+
+            targets.push(ReferenceTarget {
+                id: id.clone(),
+                name,
+            });
+        }
+
+        for (index, edge) in self.edges.iter().enumerate() {
+            edge.check_schema()?;
+
+            if self.edges[..index]
+                .iter()
+                .any(|other| other.name == edge.name)
+            {
+                return Err(Error::ModuleError(
+                    "Edge property names must be distinct".to_string(),
+                ));
+            }
+        }
+
+        Ok(ReferenceAttribute {
+            targets,
+            edges: self.edges.clone(),
+        })
+    }
+}
+
+impl EdgeProperty {
+    // The target key is taken by the entity a link points at
+    fn check_schema(&self) -> Result<()> {
+        if self.name == "target" {
+            return Err(Error::ModuleError(
+                "Edge properties cannot be named target".to_string(),
+            ));
+        }
+
+        match self.attr_type {
+            SimpleAttributeType::Longform
+            | SimpleAttributeType::Attachment
+            | SimpleAttributeType::Location => Err(Error::ModuleError(format!(
+                "Edge properties cannot be of type {:?}",
+                self.attr_type
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_value(&self, value: &Value) -> Result<()> {
+        let valid = match (self.attr_type, value) {
+            (_, Value::Null) => true,
+            (SimpleAttributeType::Integer, Value::Number(number)) => number.is_i64(),
+            (SimpleAttributeType::Number, Value::Number(..)) => true,
+            (SimpleAttributeType::Boolean, Value::Bool(..)) => true,
+            (SimpleAttributeType::Date, Value::String(date)) => date.parse::<NaiveDate>().is_ok(),
+            (SimpleAttributeType::DateTime, Value::String(datetime)) => {
+                DateTime::parse_from_rfc3339(datetime).is_ok()
+            }
+            (SimpleAttributeType::Url, Value::String(url)) => Url::parse(url).is_ok(),
+            (SimpleAttributeType::Text | SimpleAttributeType::RichText, Value::String(..)) => true,
+            _ => false,
+        };
+
+        match valid {
+            true => Ok(()),
+            false => Err(Error::ModuleError(format!(
+                "Edge property {} must be of type {:?}",
+                self.name, self.attr_type
+            ))),
+        }
     }
 }
 
@@ -40,15 +139,502 @@ impl ReferenceAttribute {
         entity: &EntityId,
         schema: &AttributeSchemaId,
         value: &EntityId,
+        properties: &Map<String, Value>,
     ) -> Result<()> {
+        self.check_target(tx, value)?;
+        self.check_properties(properties)?;
+
+        let properties = match properties.is_empty() {
+            true => None,
+            false => Some(Value::Object(properties.clone())),
+        };
+
+        check_unique_value(tx, "reference_attribute", schema, entity, value)?;
+
         let id = ReferenceAttributeId::new();
         let created_at = get_timestamp();
         tx.execute(
-            "INSERT INTO reference_attribute (id, entity, schema, value, created, updated) VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+            "INSERT INTO reference_attribute (id, entity, schema, value, properties, created, updated) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6)",
+            params![id, entity, schema, value, properties, created_at],
+        )?;
+        Ok(())
+    }
+
+    // Links are given as an entity id, or as an object holding the entity id
+    // under target along with the edge properties
+    pub fn insert_link(
+        &self,
+        tx: &Transaction,
+        entity: &EntityId,
+        schema: &AttributeSchemaId,
+        value: &Value,
+    ) -> Result<()> {
+        let (target, properties) = match value {
+            Value::String(target) => (Some(target.clone()), Map::new()),
+            Value::Object(link) => {
+                let mut properties = link.clone();
+                match properties.remove("target") {
+                    Some(Value::String(target)) => (Some(target), properties),
+                    _ => (None, properties),
+                }
+            }
+            _ => return Err(Error::InvalidQuery),
+        };
+
+        let target: EntityId = match target.map(|target| target.as_str().try_into()) {
+            Some(Ok(target)) => target,
+            _ => {
+                return Err(Error::ModuleError(
+                    "Provided value is not a valid entity id".to_string(),
+                ))
+            }
+        };
+
+        self.insert_reference(tx, entity, schema, &target, &properties)
+    }
+
+    fn check_properties(&self, properties: &Map<String, Value>) -> Result<()> {
+        for (name, value) in properties {
+            match self.edges.iter().find(|edge| &edge.name == name) {
+                Some(edge) => edge.check_value(value)?,
+                None => return Err(Error::ModuleError(format!("Unknown edge property {name}"))),
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn insert_reference_vec(
+        &self,
+        tx: &Transaction,
+        entity: &EntityId,
+        schema: &AttributeSchemaId,
+        vals: &Vec<Value>,
+    ) -> Result<()> {
+        for val in vals {
+            self.insert_link(tx, entity, schema, val)?;
+        }
+        Ok(())
+    }
+
+    // Checks the entity is of one of the target schemas, or of a schema
+    // extending one of them
+    fn check_target(&self, tx: &Transaction, value: &EntityId) -> Result<()> {
+        if self.targets.is_empty() {
+            return Ok(());
+        }
+
+        let schema: Option<EntitySchemaId> = tx
+            .query_row(
+                "SELECT schema FROM entity WHERE id = ?1",
+                params![value],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        let Some(schema) = schema else {
+            return Err(Error::ModuleError(
+                "Provided value is not a valid entity id".to_string(),
+            ));
+        };
+
+        let ancestors = schema.ancestors(tx)?;
+        if self
+            .targets
+            .iter()
+            .any(|target| ancestors.contains(&target.id))
+        {
+            return Ok(());
+        }
+
+        let names: Vec<&str> = self
+            .targets
+            .iter()
+            .map(|target| target.name.as_str())
+            .collect();
+        Err(Error::ModuleError(format!(
+            "Referenced entity must be of schema {}",
+            names.join(" | ")
+        )))
+    }
+}
+
+impl SelectAttribute {
+    pub fn insert_option(
+        &self,
+        tx: &Transaction,
+        entity: &EntityId,
+        schema: &AttributeSchemaId,
+        value: &str,
+    ) -> Result<()> {
+        if !self.options.iter().any(|option| option == value) {
+            return Err(Error::ModuleError(
+                "Provided value is not an option of the select field".to_string(),
+            ));
+        }
+
+        check_unique_value(tx, "select_attribute", schema, entity, &value)?;
+
+        let id = SelectAttributeId::new();
+        let created_at = get_timestamp();
+        tx.execute(
+            "INSERT INTO select_attribute (id, entity, schema, value, created, updated) VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
             params![id, entity, schema, value, created_at],
         )?;
         Ok(())
     }
+
+    pub fn insert_option_vec(
+        &self,
+        tx: &Transaction,
+        entity: &EntityId,
+        schema: &AttributeSchemaId,
+        vals: &Vec<Value>,
+    ) -> Result<()> {
+        for val in vals {
+            let val = match val {
+                Value::String(val) => Ok(val),
+                _ => Err(Error::InvalidQuery),
+            }?;
+            self.insert_option(tx, entity, schema, val)?;
+        }
+        Ok(())
+    }
+}
+
+impl MeasureAttribute {
+    pub fn insert_measure(
+        &self,
+        tx: &Transaction,
+        entity: &EntityId,
+        schema: &AttributeSchemaId,
+        value: f64,
+    ) -> Result<()> {
+        check_unique_value(tx, "number_attribute", schema, entity, &value)?;
+
+        let id = NumberAttributeId::new();
+        let created_at = get_timestamp();
+        tx.execute(
+            "INSERT INTO number_attribute (id, entity, schema, value, created, updated) VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+            params![id, entity, schema, value, created_at],
+        )?;
+        Ok(())
+    }
+
+    pub fn insert_string(
+        &self,
+        tx: &Transaction,
+        entity: &EntityId,
+        schema: &AttributeSchemaId,
+        value: &str,
+    ) -> Result<()> {
+        let value = parse_measure(value, &self.unit)?;
+        self.insert_measure(tx, entity, schema, value)
+    }
+
+    pub fn insert_number(
+        &self,
+        tx: &Transaction,
+        entity: &EntityId,
+        schema: &AttributeSchemaId,
+        value: &Number,
+    ) -> Result<()> {
+        let value = match value.as_f64() {
+            Some(value) => Ok(value),
+            None => Err(Error::ModuleError(
+                "Provided value cannot be stored as a number".to_string(),
+            )),
+        }?;
+        self.insert_measure(tx, entity, schema, value)
+    }
+
+    pub fn insert_vec(
+        &self,
+        tx: &Transaction,
+        entity: &EntityId,
+        schema: &AttributeSchemaId,
+        vals: &Vec<Value>,
+    ) -> Result<()> {
+        for val in vals {
+            match val {
+                Value::String(val) => self.insert_string(tx, entity, schema, val),
+                Value::Number(val) => self.insert_number(tx, entity, schema, val),
+                _ => Err(Error::InvalidQuery),
+            }?;
+        }
+        Ok(())
+    }
+}
+
+impl ComputedAttribute {
+    // Checks the expression only reads single valued attributes of the
+    // entity schema, and that it is well typed
+    pub fn check(&self, schema: &SchemaMap) -> Result<ValueType> {
+        let expr = formula::parse(&self.expression)?;
+        expr.check(&|name| value_type(find_by_name(schema, name)?))
+    }
+
+    // Parses the expression and finds the attributes it reads
+    pub fn inputs<'a>(&self, schema: &'a SchemaMap) -> Result<(Expr, Vec<&'a RawAttributeSchema>)> {
+        let expr = formula::parse(&self.expression)?;
+
+        let mut inputs = Vec::new();
+        for name in expr.attributes() {
+            inputs.push(find_by_name(schema, name)?);
+        }
+
+        Ok((expr, inputs))
+    }
+
+    pub fn evaluate(&self, schema: &SchemaMap, data: &EntityData) -> Result<Value> {
+        let (expr, inputs) = self.inputs(schema)?;
+
+        let mut values = HashMap::new();
+        for input in inputs {
+            let value = data.get(&input.id).and_then(|values| values.first());
+            values.insert(input.name.as_str(), value.cloned().unwrap_or(Value::Null));
+        }
+
+        Ok(expr.evaluate(&values))
+    }
+}
+
+fn find_by_name<'a>(schema: &'a SchemaMap, name: &str) -> Result<&'a RawAttributeSchema> {
+    match schema.values().find(|attribute| attribute.name == name) {
+        Some(attribute) => Ok(attribute),
+        None => Err(Error::ModuleError(format!(
+            "Unknown attribute {name} in expression"
+        ))),
+    }
+}
+
+fn value_type(attribute: &RawAttributeSchema) -> Result<ValueType> {
+    let name = &attribute.name;
+
+    if attribute.quantity == Quantity::List {
+        return Err(Error::ModuleError(format!(
+            "Attribute {name} is a list and cannot be used in an expression"
+        )));
+    }
+
+    match &attribute.attr_type {
+        AttributeType::Simple(SimpleAttributeType::Integer | SimpleAttributeType::Number)
+        | AttributeType::Measure(..) => Ok(ValueType::Number),
+        AttributeType::Simple(
+            SimpleAttributeType::Text
+            | SimpleAttributeType::RichText
+            | SimpleAttributeType::Url
+            | SimpleAttributeType::Date
+            | SimpleAttributeType::DateTime,
+        )
+        | AttributeType::Select(..) => Ok(ValueType::Text),
+        _ => Err(Error::ModuleError(format!(
+            "Attribute {name} cannot be used in an expression"
+        ))),
+    }
+}
+
+impl RollupAttribute {
+    // Checks the reference belongs to the entity schema and the aggregated
+    // attribute to one of the schemas it references
+    pub fn check(&self, tx: &Transaction, entity: &EntitySchemaId) -> Result<()> {
+        let reference: Option<EntitySchemaId> = tx
+            .query_row(
+                "SELECT entity FROM attribute_schema WHERE id = ?1 AND type = 'Reference'",
+                params![self.reference],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        match reference {
+            Some(owner) if entity.ancestors(tx)?.contains(&owner) => Ok(()),
+            _ => Err(Error::ModuleError(
+                "Rollup reference must be a reference attribute of the same schema".to_string(),
+            )),
+        }?;
+
+        let aggregate = self.aggregate;
+
+        let Some(attribute) = &self.attribute else {
+            return match aggregate {
+                Aggregate::Count => Ok(()),
+                _ => Err(Error::ModuleError(format!(
+                    "{aggregate:?} needs an attribute to aggregate"
+                ))),
+            };
+        };
+
+        let owner: Option<EntitySchemaId> = tx
+            .query_row(
+                "SELECT entity FROM attribute_schema WHERE id = ?1",
+                params![attribute],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        let targets = reference_targets(tx, &self.reference)?;
+
+        // A reference to any entity can aggregate any attribute
+        let mut allowed = targets.is_empty() && owner.is_some();
+        if let Some(owner) = &owner {
+            for target in targets {
+                allowed |= target.ancestors(tx)?.contains(owner);
+            }
+        }
+
+        if !allowed {
+            return Err(Error::ModuleError(
+                "Rollup attribute must belong to the referenced schema".to_string(),
+            ));
+        }
+
+        let attribute = RawAttributeSchema::get(tx, attribute)?;
+        let table = value_table(&attribute.attr_type);
+
+        let allowed = match aggregate {
+            Aggregate::Count => table.is_some(),
+            Aggregate::Sum | Aggregate::Average => {
+                matches!(table, Some("integer_attribute" | "number_attribute"))
+            }
+            Aggregate::Min | Aggregate::Max => matches!(
+                table,
+                Some("integer_attribute" | "number_attribute" | "date_attribute")
+            ),
+            Aggregate::Distinct => matches!(
+                table,
+                Some(
+                    "text_attribute"
+                        | "integer_attribute"
+                        | "number_attribute"
+                        | "date_attribute"
+                        | "datetime_attribute"
+                        | "url_attribute"
+                        | "select_attribute"
+                )
+            ),
+        };
+
+        if !allowed {
+            return Err(Error::ModuleError(format!(
+                "{aggregate:?} cannot be used on attribute {}",
+                attribute.name
+            )));
+        }
+
+        Ok(())
+    }
+
+    // The table holding the values of the aggregated attribute
+    pub fn value_table(&self, tx: &Transaction) -> Result<Option<&'static str>> {
+        match &self.attribute {
+            Some(attribute) => Ok(value_table(
+                &RawAttributeSchema::get(tx, attribute)?.attr_type,
+            )),
+            None => Ok(None),
+        }
+    }
+}
+
+fn reference_targets(
+    tx: &Transaction,
+    reference: &AttributeSchemaId,
+) -> Result<Vec<EntitySchemaId>> {
+    let mut statement = tx.prepare("SELECT target FROM reference_target WHERE schema = ?1")?;
+    let targets = statement
+        .query_map(params![reference], |row| row.get(0))?
+        .collect::<Result<Vec<EntitySchemaId>>>()?;
+
+    Ok(targets)
+}
+
+impl BacklinkAttribute {
+    // Checks the reference can point at entities of the schema
+    pub fn check(&self, tx: &Transaction, entity: &EntitySchemaId) -> Result<()> {
+        let is_reference: bool = tx.query_row(
+            "SELECT EXISTS (SELECT 1 FROM attribute_schema WHERE id = ?1 AND type = 'Reference')",
+            params![self.reference],
+            |row| row.get(0),
+        )?;
+
+        let targets = reference_targets(tx, &self.reference)?;
+        let ancestors = entity.ancestors(tx)?;
+
+        if !is_reference
+            || !(targets.is_empty() || targets.iter().any(|target| ancestors.contains(target)))
+        {
+            return Err(Error::ModuleError(
+                "Backlink reference must be able to point at this schema".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+pub const ATTRIBUTE_TABLES: [&str; 12] = [
+    "text_attribute",
+    "integer_attribute",
+    "number_attribute",
+    "date_attribute",
+    "datetime_attribute",
+    "boolean_attribute",
+    "select_attribute",
+    "url_attribute",
+    "attachment_attribute",
+    "location_attribute",
+    "reference_attribute",
+    "longform_attribute",
+];
+
+// The table an attribute's values are stored in, if they are stored at all
+pub fn value_table(attr_type: &AttributeType) -> Option<&'static str> {
+    match attr_type {
+        AttributeType::Simple(simple) => match simple {
+            SimpleAttributeType::Text | SimpleAttributeType::RichText => Some("text_attribute"),
+            SimpleAttributeType::Longform => Some("longform_attribute"),
+            SimpleAttributeType::Integer => Some("integer_attribute"),
+            SimpleAttributeType::Number => Some("number_attribute"),
+            SimpleAttributeType::Date => Some("date_attribute"),
+            SimpleAttributeType::DateTime => Some("datetime_attribute"),
+            SimpleAttributeType::Boolean => Some("boolean_attribute"),
+            SimpleAttributeType::Url => Some("url_attribute"),
+            SimpleAttributeType::Attachment => Some("attachment_attribute"),
+            SimpleAttributeType::Location => Some("location_attribute"),
+        },
+        AttributeType::Reference(..) => Some("reference_attribute"),
+        AttributeType::Select(..) => Some("select_attribute"),
+        AttributeType::Measure(..) => Some("number_attribute"),
+        AttributeType::Computed(..) | AttributeType::Rollup(..) | AttributeType::Backlink(..) => {
+            None
+        }
+    }
+}
+
+impl ToSql for Aggregate {
+    fn to_sql(&self) -> Result<rusqlite::types::ToSqlOutput<'_>> {
+        match self {
+            Aggregate::Count => Ok("Count".into()),
+            Aggregate::Sum => Ok("Sum".into()),
+            Aggregate::Min => Ok("Min".into()),
+            Aggregate::Max => Ok("Max".into()),
+            Aggregate::Average => Ok("Average".into()),
+            Aggregate::Distinct => Ok("Distinct".into()),
+        }
+    }
+}
+
+impl FromSql for Aggregate {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value.as_str()? {
+            "Count" => Ok(Aggregate::Count),
+            "Sum" => Ok(Aggregate::Sum),
+            "Min" => Ok(Aggregate::Min),
+            "Max" => Ok(Aggregate::Max),
+            "Average" => Ok(Aggregate::Average),
+            "Distinct" => Ok(Aggregate::Distinct),
+            _ => Err(FromSqlError::InvalidType),
+        }
+    }
 }
 
 impl SimpleAttributeType {
@@ -57,6 +643,14 @@ impl SimpleAttributeType {
             "Text" => Ok(SimpleAttributeType::Text),
             "RichText" => Ok(SimpleAttributeType::RichText),
             "Longform" => Ok(SimpleAttributeType::Longform),
+            "Integer" => Ok(SimpleAttributeType::Integer),
+            "Number" => Ok(SimpleAttributeType::Number),
+            "Date" => Ok(SimpleAttributeType::Date),
+            "DateTime" => Ok(SimpleAttributeType::DateTime),
+            "Boolean" => Ok(SimpleAttributeType::Boolean),
+            "Url" => Ok(SimpleAttributeType::Url),
+            "Attachment" => Ok(SimpleAttributeType::Attachment),
+            "Location" => Ok(SimpleAttributeType::Location),
             _ => Err(FromSqlError::InvalidType),
         }
     }
@@ -85,6 +679,8 @@ impl SimpleAttributeType {
                 Ok(())
             }
             SimpleAttributeType::Text | SimpleAttributeType::RichText => {
+                check_unique_value(tx, "text_attribute", schema, entity, &value)?;
+
                 let id = TextAttributeId::new();
                 tx.execute(
                     "INSERT INTO text_attribute (id, entity, schema, value, created, updated) VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
@@ -92,9 +688,215 @@ impl SimpleAttributeType {
                 )?;
                 Ok(())
             }
+            SimpleAttributeType::Date => {
+                let value = parse_date(value)?.to_string();
+                check_unique_value(tx, "date_attribute", schema, entity, &value)?;
+
+                let id = DateAttributeId::new();
+                tx.execute(
+                    "INSERT INTO date_attribute (id, entity, schema, value, created, updated) VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+                    params![id, entity, schema, value, created_at],
+                )?;
+                Ok(())
+            }
+            SimpleAttributeType::DateTime => {
+                let value = parse_datetime(value)?;
+                check_unique_value(
+                    tx,
+                    "datetime_attribute",
+                    schema,
+                    entity,
+                    &value.to_rfc3339(),
+                )?;
+
+                let id = DateTimeAttributeId::new();
+                tx.execute(
+                    "INSERT INTO datetime_attribute (id, entity, schema, value, timestamp, created, updated) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6)",
+                    params![
+                        id,
+                        entity,
+                        schema,
+                        value.to_rfc3339(),
+                        value.timestamp_millis(),
+                        created_at
+                    ],
+                )?;
+                Ok(())
+            }
+            SimpleAttributeType::Url => {
+                let value = parse_url(value)?;
+                check_unique_value(tx, "url_attribute", schema, entity, &value.as_str())?;
+
+                let id = UrlAttributeId::new();
+                tx.execute(
+                    "INSERT INTO url_attribute (id, entity, schema, value, host, created, updated) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6)",
+                    params![id, entity, schema, value.as_str(), value.host_str(), created_at],
+                )?;
+                Ok(())
+            }
+            SimpleAttributeType::Integer | SimpleAttributeType::Number => Err(Error::ModuleError(
+                "Provided a string to a numeric field".to_string(),
+            )),
+            SimpleAttributeType::Boolean => Err(Error::ModuleError(
+                "Provided a string to a boolean field".to_string(),
+            )),
+            SimpleAttributeType::Attachment => Err(Error::ModuleError(
+                "Provided a string to an attachment field".to_string(),
+            )),
+            SimpleAttributeType::Location => Err(Error::ModuleError(
+                "Provided a string to a location field".to_string(),
+            )),
+        }
+    }
+
+    pub fn insert_object(
+        &self,
+        tx: &Transaction,
+        entity: &EntityId,
+        schema: &AttributeSchemaId,
+        value: &Map<String, Value>,
+    ) -> Result<()> {
+        match self {
+            SimpleAttributeType::Attachment => {
+                let value: CreateAttachment =
+                    match serde_json::from_value(Value::Object(value.clone())) {
+                        Ok(value) => Ok(value),
+                        Err(err) => Err(Error::ModuleError(format!(
+                            "Provided value is not a valid attachment: {err}"
+                        ))),
+                    }?;
+
+                if !blob_exists(tx, &value.hash)? {
+                    return Err(Error::ModuleError(
+                        "Provided attachment is not in the blob store".to_string(),
+                    ));
+                }
+
+                check_unique_value(tx, "attachment_attribute", schema, entity, &value.hash)?;
+
+                let id = AttachmentAttributeId::new();
+                let created_at = get_timestamp();
+                tx.execute(
+                    "INSERT INTO attachment_attribute (id, entity, schema, value, filename, mime, created, updated) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7)",
+                    params![id, entity, schema, value.hash, value.filename, value.mime, created_at],
+                )?;
+                Ok(())
+            }
+            SimpleAttributeType::Location => {
+                let value = parse_location(value)?;
+
+                let id = LocationAttributeId::new();
+                let created_at = get_timestamp();
+                tx.execute(
+                    "INSERT INTO location_attribute (id, entity, schema, value, longitude, label, created, updated) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7)",
+                    params![id, entity, schema, value.latitude, value.longitude, value.label, created_at],
+                )?;
+                Ok(())
+            }
+            _ => Err(Error::ModuleError(
+                "Provided an object to a field that does not take objects".to_string(),
+            )),
+        }
+    }
+
+    pub fn insert_number(
+        &self,
+        tx: &Transaction,
+        entity: &EntityId,
+        schema: &AttributeSchemaId,
+        value: &Number,
+    ) -> Result<()> {
+        let created_at = get_timestamp();
+
+        match self {
+            SimpleAttributeType::Integer => {
+                let value = match value.as_i64() {
+                    Some(value) => Ok(value),
+                    None => Err(Error::ModuleError(
+                        "Provided a non-integer value to an integer field".to_string(),
+                    )),
+                }?;
+                check_unique_value(tx, "integer_attribute", schema, entity, &value)?;
+
+                let id = IntegerAttributeId::new();
+                tx.execute(
+                    "INSERT INTO integer_attribute (id, entity, schema, value, created, updated) VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+                    params![id, entity, schema, value, created_at],
+                )?;
+                Ok(())
+            }
+            SimpleAttributeType::Number => {
+                let value = match value.as_f64() {
+                    Some(value) => Ok(value),
+                    None => Err(Error::ModuleError(
+                        "Provided value cannot be stored as a number".to_string(),
+                    )),
+                }?;
+                check_unique_value(tx, "number_attribute", schema, entity, &value)?;
+
+                let id = NumberAttributeId::new();
+                tx.execute(
+                    "INSERT INTO number_attribute (id, entity, schema, value, created, updated) VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+                    params![id, entity, schema, value, created_at],
+                )?;
+                Ok(())
+            }
+            SimpleAttributeType::Text
+            | SimpleAttributeType::RichText
+            | SimpleAttributeType::Longform
+            | SimpleAttributeType::Date
+            | SimpleAttributeType::DateTime
+            | SimpleAttributeType::Boolean
+            | SimpleAttributeType::Url
+            | SimpleAttributeType::Attachment
+            | SimpleAttributeType::Location => Err(Error::ModuleError(
+                "Provided a number to a non-numeric field".to_string(),
+            )),
         }
     }
 
+    pub fn insert_bool(
+        &self,
+        tx: &Transaction,
+        entity: &EntityId,
+        schema: &AttributeSchemaId,
+        value: bool,
+    ) -> Result<()> {
+        match self {
+            SimpleAttributeType::Boolean => {
+                let id = BooleanAttributeId::new();
+                let created_at = get_timestamp();
+                tx.execute(
+                    "INSERT INTO boolean_attribute (id, entity, schema, value, created, updated) VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+                    params![id, entity, schema, value, created_at],
+                )?;
+                Ok(())
+            }
+            _ => Err(Error::ModuleError(
+                "Provided a boolean to a non-boolean field".to_string(),
+            )),
+        }
+    }
+
+    pub fn insert_vec(
+        &self,
+        tx: &Transaction,
+        entity: &EntityId,
+        schema: &AttributeSchemaId,
+        vals: &Vec<Value>,
+    ) -> Result<()> {
+        for val in vals {
+            match val {
+                Value::String(val) => self.insert_string(tx, entity, schema, val),
+                Value::Number(val) => self.insert_number(tx, entity, schema, val),
+                Value::Bool(val) => self.insert_bool(tx, entity, schema, *val),
+                Value::Object(val) => self.insert_object(tx, entity, schema, val),
+                _ => Err(Error::InvalidQuery),
+            }?;
+        }
+        Ok(())
+    }
+
     pub fn insert_string_vec(
         &self,
         tx: &Transaction,
@@ -113,6 +915,8 @@ impl SimpleAttributeType {
                 Value::String(val) => Ok(val),
                 _ => Err(Error::InvalidQuery),
             }?;
+            check_unique_value(tx, "text_attribute", schema, entity, val)?;
+
             let id = TextAttributeId::new();
             stmt.execute((id, entity, schema, val, created_at))?;
         }
@@ -120,21 +924,118 @@ impl SimpleAttributeType {
     }
 }
 
+pub fn parse_date(value: &str) -> Result<NaiveDate> {
+    match value.parse::<NaiveDate>() {
+        Ok(date) => Ok(date),
+        Err(_) => Err(Error::ModuleError(
+            "Provided value is not a valid ISO 8601 date".to_string(),
+        )),
+    }
+}
+
+pub fn parse_datetime(value: &str) -> Result<DateTime<FixedOffset>> {
+    match DateTime::parse_from_rfc3339(value) {
+        Ok(datetime) => Ok(datetime),
+        Err(_) => Err(Error::ModuleError(
+            "Provided value is not a valid ISO 8601 date and time".to_string(),
+        )),
+    }
+}
+
+pub fn parse_url(value: &str) -> Result<Url> {
+    match Url::parse(value) {
+        Ok(url) => Ok(url),
+        Err(err) => Err(Error::ModuleError(format!(
+            "Provided value is not a valid URL: {err}"
+        ))),
+    }
+}
+
+pub fn parse_location(value: &Map<String, Value>) -> Result<Location> {
+    let location: Location = match serde_json::from_value(Value::Object(value.clone())) {
+        Ok(location) => Ok(location),
+        Err(err) => Err(Error::ModuleError(format!(
+            "Provided value is not a valid location: {err}"
+        ))),
+    }?;
+
+    check_coordinates(location.latitude, location.longitude)?;
+
+    Ok(location)
+}
+
+pub fn check_coordinates(latitude: f64, longitude: f64) -> Result<()> {
+    if !(-90.0..=90.0).contains(&latitude) {
+        return Err(Error::ModuleError(
+            "Latitude must be between -90 and 90".to_string(),
+        ));
+    }
+
+    if !(-180.0..=180.0).contains(&longitude) {
+        return Err(Error::ModuleError(
+            "Longitude must be between -180 and 180".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
 impl ToSql for SimpleAttributeType {
     fn to_sql(&self) -> Result<rusqlite::types::ToSqlOutput<'_>> {
         match self {
             SimpleAttributeType::Text => Ok("Text".into()),
             SimpleAttributeType::RichText => Ok("RichText".into()),
             SimpleAttributeType::Longform => Ok("Longform".into()),
+            SimpleAttributeType::Integer => Ok("Integer".into()),
+            SimpleAttributeType::Number => Ok("Number".into()),
+            SimpleAttributeType::Date => Ok("Date".into()),
+            SimpleAttributeType::DateTime => Ok("DateTime".into()),
+            SimpleAttributeType::Boolean => Ok("Boolean".into()),
+            SimpleAttributeType::Url => Ok("Url".into()),
+            SimpleAttributeType::Attachment => Ok("Attachment".into()),
+            SimpleAttributeType::Location => Ok("Location".into()),
         }
     }
 }
 
 impl CreateAttributeType {
-    pub fn get_ref(&self) -> Option<&EntitySchemaId> {
+    pub fn get_ref(&self) -> Option<&CreateReferenceAttribute> {
         match self {
             CreateAttributeType::Simple(_type) => None,
-            CreateAttributeType::Reference(reference) => Some(&reference.id),
+            CreateAttributeType::Reference(reference) => Some(reference),
+            CreateAttributeType::Select { .. } => None,
+            CreateAttributeType::Measure(..) => None,
+            CreateAttributeType::Computed(..) => None,
+            CreateAttributeType::Rollup(..) => None,
+            CreateAttributeType::Backlink(..) => None,
+        }
+    }
+
+    pub fn get_backlink(&self) -> Option<&BacklinkAttribute> {
+        match self {
+            CreateAttributeType::Backlink(backlink) => Some(backlink),
+            _ => None,
+        }
+    }
+
+    pub fn get_rollup(&self) -> Option<&RollupAttribute> {
+        match self {
+            CreateAttributeType::Rollup(rollup) => Some(rollup),
+            _ => None,
+        }
+    }
+
+    pub fn get_expression(&self) -> Option<&str> {
+        match self {
+            CreateAttributeType::Computed(computed) => Some(&computed.expression),
+            _ => None,
+        }
+    }
+
+    pub fn get_unit(&self) -> Option<&str> {
+        match self {
+            CreateAttributeType::Measure(measure) => Some(&measure.unit),
+            _ => None,
         }
     }
 
@@ -144,6 +1045,20 @@ impl CreateAttributeType {
             CreateAttributeType::Reference(reference) => {
                 Ok(AttributeType::Reference(reference.get_full(tx)?))
             }
+            CreateAttributeType::Select { options } => Ok(AttributeType::Select(SelectAttribute {
+                options: options.clone(),
+            })),
+            CreateAttributeType::Measure(measure) => {
+                check_unit(&measure.dimension, &measure.unit)?;
+                Ok(AttributeType::Measure(measure.clone()))
+            }
+            CreateAttributeType::Computed(computed) => {
+                Ok(AttributeType::Computed(computed.clone()))
+            }
+            CreateAttributeType::Rollup(rollup) => Ok(AttributeType::Rollup(rollup.clone())),
+            CreateAttributeType::Backlink(backlink) => {
+                Ok(AttributeType::Backlink(backlink.clone()))
+            }
         }
     }
 }
@@ -153,6 +1068,11 @@ impl ToSql for CreateAttributeType {
         match self {
             CreateAttributeType::Simple(simple) => simple.to_sql(),
             CreateAttributeType::Reference(_val) => Ok("Reference".into()),
+            CreateAttributeType::Select { .. } => Ok("Select".into()),
+            CreateAttributeType::Measure(..) => Ok("Measure".into()),
+            CreateAttributeType::Computed(..) => Ok("Computed".into()),
+            CreateAttributeType::Rollup(..) => Ok("Rollup".into()),
+            CreateAttributeType::Backlink(..) => Ok("Backlink".into()),
         }
     }
 }
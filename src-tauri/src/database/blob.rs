@@ -0,0 +1,447 @@
+use std::{
+    collections::HashSet,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use rusqlite::{params, Connection, Error, Transaction, TransactionBehavior};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    models::attachment::{Blob, BlobId},
+    utils::get_timestamp,
+};
+
+// Content-addressed storage for attachments. Files are stored once under
+// the hex SHA-256 of their content, and the blob table records which
+// hashes are known to the database
+pub struct BlobStore {
+    root: PathBuf,
+}
+
+// Blobs dropped by garbage collection. The files are only removed once the
+// transaction that deleted the rows has been committed, so a rollback never
+// leaves rows pointing at missing files. Content stored again in between
+// keeps its file
+#[derive(Debug, Default)]
+pub struct Garbage {
+    pub hashes: Vec<String>,
+    pub paths: Vec<PathBuf>,
+}
+
+impl Garbage {
+    // Call after the transaction passed to collect_garbage has committed.
+    // The write lock is held while the files go, so a store cannot record a
+    // blob between the check below and the file being removed
+    pub fn remove(&self, conn: &mut Connection) -> rusqlite::Result<()> {
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+        for path in &self.paths {
+            let name = path.file_name().and_then(|name| name.to_str());
+            if let Some(name) = name {
+                if blob_exists(&tx, name)? {
+                    continue;
+                }
+            }
+
+            remove_file(path)?;
+        }
+
+        tx.commit()
+    }
+}
+
+impl BlobStore {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    // Blobs are kept in a directory next to the SQLite database
+    pub fn for_database(database: &Path) -> Self {
+        let parent = database.parent().unwrap_or(Path::new(""));
+        Self::new(parent.join("blobs"))
+    }
+
+    pub fn path(&self, hash: &str) -> rusqlite::Result<PathBuf> {
+        check_hash(hash)?;
+        Ok(self.root.join(&hash[..2]).join(hash))
+    }
+
+    pub fn store(&self, tx: &Transaction, content: &[u8]) -> rusqlite::Result<Blob> {
+        let hash = format!("{:x}", Sha256::digest(content));
+        let size = content.len() as u64;
+
+        // The row is written before the file so the write lock is held
+        // while the file is created, which keeps Garbage::remove from
+        // removing it in between
+        let id = BlobId::new();
+        let created_at = get_timestamp();
+        tx.execute(
+            "INSERT OR IGNORE INTO blob (id, hash, size, created, updated) VALUES (?1, ?2, ?3, ?4, ?4)",
+            params![id, hash, size, created_at],
+        )?;
+
+        let path = self.path(&hash)?;
+
+        if !path.exists() {
+            let dir = path.parent().unwrap();
+            fs::create_dir_all(dir).map_err(io_error)?;
+
+            let temp = dir.join(format!("{hash}.tmp"));
+            fs::write(&temp, content).map_err(io_error)?;
+            fs::rename(&temp, &path).map_err(io_error)?;
+        }
+
+        Ok(Blob { hash, size })
+    }
+
+    pub fn store_file(&self, tx: &Transaction, file: &Path) -> rusqlite::Result<Blob> {
+        let content = fs::read(file).map_err(io_error)?;
+        self.store(tx, &content)
+    }
+
+    // Removes blobs that are no longer referenced by any attachment, as well
+    // as files left behind by transactions that were rolled back. Only the
+    // rows are deleted here, the files are removed by Garbage::remove
+    pub fn collect_garbage(&self, tx: &Transaction) -> rusqlite::Result<Garbage> {
+        let mut statement = tx.prepare(
+            "DELETE FROM blob WHERE hash NOT IN (SELECT value FROM attachment_attribute) RETURNING hash",
+        )?;
+
+        let rows = statement.query_map((), |row| row.get(0))?;
+
+        let mut garbage = Garbage::default();
+        for hash in rows {
+            let hash: String = hash?;
+            garbage.paths.push(self.path(&hash)?);
+            garbage.hashes.push(hash);
+        }
+
+        let mut statement = tx.prepare("SELECT hash FROM blob")?;
+        let rows = statement.query_map((), |row| row.get(0))?;
+
+        let mut known: HashSet<String> = HashSet::new();
+        for hash in rows {
+            known.insert(hash?);
+        }
+
+        let dirs = match fs::read_dir(&self.root) {
+            Ok(dirs) => dirs,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(garbage),
+            Err(err) => return Err(io_error(err)),
+        };
+
+        for dir in dirs {
+            let dir = dir.map_err(io_error)?.path();
+            if !dir.is_dir() {
+                continue;
+            }
+
+            for file in fs::read_dir(&dir).map_err(io_error)? {
+                let file = file.map_err(io_error)?.path();
+                let name = file.file_name().and_then(|name| name.to_str());
+
+                match name {
+                    Some(name) if known.contains(name) => continue,
+                    _ if garbage.paths.contains(&file) => continue,
+                    _ => garbage.paths.push(file),
+                }
+            }
+        }
+
+        Ok(garbage)
+    }
+}
+
+pub fn blob_exists(tx: &Transaction, hash: &str) -> rusqlite::Result<bool> {
+    let mut stmt = tx.prepare("SELECT 1 FROM blob WHERE hash = ?")?;
+    stmt.exists(params![hash])
+}
+
+fn check_hash(hash: &str) -> rusqlite::Result<()> {
+    let valid = hash.len() == 64
+        && hash
+            .chars()
+            .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c));
+
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::ModuleError(
+            "Provided value is not a valid blob hash".to_string(),
+        ))
+    }
+}
+
+fn remove_file(path: &Path) -> rusqlite::Result<()> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(io_error(err)),
+    }
+}
+
+fn io_error(err: std::io::Error) -> Error {
+    Error::ModuleError(format!("Blob store error: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env::temp_dir, fs, path::PathBuf};
+
+    use rusqlite::{params, Error};
+    use serde_json::Value;
+
+    use crate::{
+        database::{
+            entity::{add_entity, get, EntityField, EntityRequest},
+            test::test_util::{setup, ASD, ESD},
+            Delete,
+        },
+        models::{
+            attribute::GenericAttributeId, attribute_schema::Quantity,
+            attribute_type::SimpleAttributeType, entity::EntityId,
+        },
+    };
+
+    use super::*;
+
+    struct TempStore {
+        root: PathBuf,
+        store: BlobStore,
+    }
+
+    impl TempStore {
+        fn new() -> Self {
+            let root = temp_dir().join(format!("cortex-blobs-{}", uuid::Uuid::new_v4()));
+            let store = BlobStore::new(root.clone());
+            Self { root, store }
+        }
+    }
+
+    impl Drop for TempStore {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.root);
+        }
+    }
+
+    fn count_blobs(tx: &Transaction) -> usize {
+        tx.query_row("SELECT COUNT(*) FROM blob", (), |r| r.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn store_deduplicates() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+        let temp = TempStore::new();
+
+        let first = temp.store.store(&tx, b"Hello world").unwrap();
+        let second = temp.store.store(&tx, b"Hello world").unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(first.size, 11);
+        assert_eq!(count_blobs(&tx), 1);
+
+        let content = fs::read(temp.store.path(&first.hash).unwrap()).unwrap();
+        assert_eq!(content, b"Hello world");
+    }
+
+    #[test]
+    fn attach_and_get() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+        let temp = TempStore::new();
+
+        let schema = ESD::create_default(&tx);
+        let attr = ASD::default()
+            .attr_type(SimpleAttributeType::Attachment)
+            .quantity(Quantity::List)
+            .create(&tx, &schema);
+
+        let blob = temp.store.store(&tx, b"%PDF-1.7").unwrap();
+
+        let data = serde_json::from_str(&format!(
+            r#"
+            {{
+              "{attr}": [{{ "hash": "{}", "filename": "paper.pdf", "mime": "application/pdf" }}]
+            }}
+            "#,
+            blob.hash
+        ))
+        .unwrap();
+
+        let entity = add_entity(&tx, &schema, data).unwrap();
+
+        let request = EntityRequest {
+            0: vec![EntityField::Attribute(attr.clone())],
+        };
+
+        let result = get(&tx, &entity, &request).unwrap();
+        let val = result.get(&attr.to_string()).unwrap();
+
+        let expected: Value = serde_json::from_str(&format!(
+            r#"
+            [{{ "hash": "{}", "filename": "paper.pdf", "mime": "application/pdf", "size": 8 }}]
+            "#,
+            blob.hash
+        ))
+        .unwrap();
+
+        assert_eq!(val, &expected);
+    }
+
+    #[test]
+    fn attach_unknown_blob_error() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let schema = ESD::create_default(&tx);
+        let attr = ASD::default()
+            .attr_type(SimpleAttributeType::Attachment)
+            .create(&tx, &schema);
+
+        let data = serde_json::from_str(&format!(
+            r#"
+            {{
+              "{attr}": {{ "hash": "{}", "filename": "paper.pdf", "mime": "application/pdf" }}
+            }}
+            "#,
+            "0".repeat(64)
+        ))
+        .unwrap();
+
+        let result = add_entity(&tx, &schema, data);
+
+        assert_eq!(
+            result,
+            Err(Error::ModuleError(
+                "Provided attachment is not in the blob store".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn collect_unreferenced() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+        let temp = TempStore::new();
+
+        let schema = ESD::create_default(&tx);
+        let attr = ASD::default()
+            .attr_type(SimpleAttributeType::Attachment)
+            .quantity(Quantity::Optional)
+            .create(&tx, &schema);
+
+        let blob = temp.store.store(&tx, b"Hello world").unwrap();
+        let path = temp.store.path(&blob.hash).unwrap();
+
+        let mut entities: Vec<EntityId> = Vec::new();
+        for _ in 0..2 {
+            let data = serde_json::from_str(&format!(
+                r#"
+                {{
+                  "{attr}": {{ "hash": "{}", "filename": "hello.txt", "mime": "text/plain" }}
+                }}
+                "#,
+                blob.hash
+            ))
+            .unwrap();
+
+            entities.push(add_entity(&tx, &schema, data).unwrap());
+        }
+
+        entities.pop().unwrap().delete(&tx).unwrap();
+
+        let garbage = temp.store.collect_garbage(&tx).unwrap();
+        assert_eq!(garbage.hashes.len(), 0);
+        assert!(path.exists());
+
+        let id: GenericAttributeId = tx
+            .query_row(
+                "SELECT id FROM attachment_attribute WHERE entity = ?",
+                params![entities[0]],
+                |r| r.get(0),
+            )
+            .unwrap();
+
+        id.delete(&tx).unwrap();
+
+        let garbage = temp.store.collect_garbage(&tx).unwrap();
+        assert_eq!(garbage.hashes, vec![blob.hash]);
+        assert_eq!(count_blobs(&tx), 0);
+
+        // Files stay until the deletion is committed
+        assert!(path.exists());
+        tx.commit().unwrap();
+        garbage.remove(&mut conn).unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn collect_rolled_back() {
+        let mut conn = setup();
+        let temp = TempStore::new();
+
+        let tx = conn.transaction().unwrap();
+        let blob = temp.store.store(&tx, b"Hello world").unwrap();
+        tx.commit().unwrap();
+
+        let tx = conn.transaction().unwrap();
+        let garbage = temp.store.collect_garbage(&tx).unwrap();
+        assert_eq!(garbage.hashes, vec![blob.hash.clone()]);
+        tx.rollback().unwrap();
+
+        let tx = conn.transaction().unwrap();
+        assert!(blob_exists(&tx, &blob.hash).unwrap());
+        assert!(temp.store.path(&blob.hash).unwrap().exists());
+    }
+
+    #[test]
+    fn collect_stray_files() {
+        let mut conn = setup();
+        let temp = TempStore::new();
+
+        let tx = conn.transaction().unwrap();
+        let blob = temp.store.store(&tx, b"Hello world").unwrap();
+        tx.rollback().unwrap();
+
+        let path = temp.store.path(&blob.hash).unwrap();
+        assert!(path.exists());
+
+        let tx = conn.transaction().unwrap();
+        let garbage = temp.store.collect_garbage(&tx).unwrap();
+        tx.commit().unwrap();
+        garbage.remove(&mut conn).unwrap();
+
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn collect_then_store_again() {
+        let mut conn = setup();
+        let temp = TempStore::new();
+
+        let tx = conn.transaction().unwrap();
+        let blob = temp.store.store(&tx, b"Hello world").unwrap();
+        tx.commit().unwrap();
+
+        let tx = conn.transaction().unwrap();
+        let garbage = temp.store.collect_garbage(&tx).unwrap();
+        assert_eq!(garbage.hashes, vec![blob.hash.clone()]);
+        tx.commit().unwrap();
+
+        // The file is still there, so storing the same content only adds the row
+        let tx = conn.transaction().unwrap();
+        temp.store.store(&tx, b"Hello world").unwrap();
+        tx.commit().unwrap();
+
+        garbage.remove(&mut conn).unwrap();
+
+        let tx = conn.transaction().unwrap();
+        assert!(blob_exists(&tx, &blob.hash).unwrap());
+        let content = fs::read(temp.store.path(&blob.hash).unwrap()).unwrap();
+        assert_eq!(content, b"Hello world");
+    }
+}
@@ -8,6 +8,7 @@ use crate::{
     },
     models::{
         attribute_schema::{AttributeSchemaId, Quantity},
+        attribute_type::AttributeType,
         entity::EntityId,
         entity_schema::EntitySchemaId,
     },
@@ -19,7 +20,7 @@ pub fn add_entity(
     schema_id: &EntitySchemaId,
     data: Value,
 ) -> rusqlite::Result<EntityId> {
-    let data = match data {
+    let mut data = match data {
         Value::Object(obj) => Ok(obj),
         _ => Err(Error::ModuleError(
             "Provided data is not an object".to_string(),
@@ -31,6 +32,19 @@ pub fn add_entity(
     let schema = RawAttributeSchema::get_map(&tx, schema_id)?;
 
     for (schema_id, value) in &schema {
+        // Computed, rollup and backlink fields are never provided
+        if let AttributeType::Computed(..)
+        | AttributeType::Rollup(..)
+        | AttributeType::Backlink(..) = value.attr_type
+        {
+            continue;
+        }
+
+        let key = schema_id.to_string();
+        if let (None, Some(default)) = (data.get(&key), &value.default) {
+            data.insert(key, default.resolve(&value.attr_type));
+        }
+
         match value.quantity {
             Quantity::Required => {
                 let provided = data.get(&schema_id.to_string());
@@ -41,7 +55,13 @@ pub fn add_entity(
                     _ => Ok(()),
                 }?;
             }
-            _ => continue,
+            // A list that isn't provided is empty
+            Quantity::List => {
+                if !data.contains_key(&schema_id.to_string()) {
+                    value.constraints.check_items(&value.name, 0)?;
+                }
+            }
+            Quantity::Optional => continue,
         }
     }
 
@@ -63,24 +83,7 @@ pub fn add_entity(
             None => Err(Error::ModuleError("Key not found in schema".to_string())),
         }?;
 
-        let quantity = &schema_entry.quantity;
-
-        match quantity {
-            Quantity::Required | Quantity::Optional => match value {
-                Value::Array(..) => Err(Error::ModuleError(
-                    "Provided a list to a non-list field".to_string(),
-                )),
-                Value::Number(..) | Value::String(..) | Value::Object(..) => Ok(()),
-                _ => todo!(),
-            },
-            Quantity::List => Ok(()),
-        }?;
-
-        match value {
-            Value::String(val) => schema_entry.insert(tx, &id, &val),
-            Value::Array(vals) => schema_entry.insert(tx, &id, &vals),
-            _ => todo!(),
-        }?;
+        schema_entry.insert(tx, &id, &value)?;
     }
 
     Ok(id)
@@ -89,8 +92,14 @@ pub fn add_entity(
 #[cfg(test)]
 mod tests {
     use crate::{
-        database::test::test_util::{setup, ASD, ESD},
-        models::attribute_type::SimpleAttributeType,
+        database::{
+            test::test_util::{setup, ASD, ESD},
+            New,
+        },
+        models::{
+            attribute_schema::{AttributeSchema, Constraints, CreateAttributeSchema},
+            attribute_type::{CreateAttributeType, SimpleAttributeType},
+        },
     };
 
     use super::*;
@@ -359,4 +368,301 @@ mod tests {
 
         add_entity(&tx, &schema, data).unwrap();
     }
+
+    // Create an entity with numeric data
+    #[test]
+    fn with_numbers() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let schema = ESD::create_default(&tx);
+        let integer = ASD::default()
+            .attr_type(SimpleAttributeType::Integer)
+            .create(&tx, &schema);
+        let number = ASD::default()
+            .name("Number")
+            .attr_type(SimpleAttributeType::Number)
+            .create(&tx, &schema);
+
+        let data = serde_json::from_str(&format!(
+            r#"
+            {{
+              "{integer}": 320,
+              "{number}": 4.5
+            }}
+            "#
+        ))
+        .unwrap();
+
+        add_entity(&tx, &schema, data).unwrap();
+    }
+
+    // It should throw an error if a fractional value is provided to an integer field
+    #[test]
+    fn fraction_to_integer() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let schema = ESD::create_default(&tx);
+        let attr = ASD::default()
+            .attr_type(SimpleAttributeType::Integer)
+            .create(&tx, &schema);
+
+        let data = serde_json::from_str(&format!(
+            r#"
+            {{
+              "{attr}": 4.5
+            }}
+            "#
+        ))
+        .unwrap();
+
+        let result = add_entity(&tx, &schema, data);
+        assert_eq!(
+            result,
+            Err(Error::ModuleError(
+                "Provided a non-integer value to an integer field".to_string()
+            ))
+        );
+    }
+
+    // It should throw an error if a string is provided to a numeric field
+    #[test]
+    fn string_to_number() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let schema = ESD::create_default(&tx);
+        let attr = ASD::default()
+            .attr_type(SimpleAttributeType::Number)
+            .create(&tx, &schema);
+
+        let data = serde_json::from_str(&format!(
+            r#"
+            {{
+              "{attr}": "4.5"
+            }}
+            "#
+        ))
+        .unwrap();
+
+        let result = add_entity(&tx, &schema, data);
+        assert_eq!(
+            result,
+            Err(Error::ModuleError(
+                "Provided a string to a numeric field".to_string()
+            ))
+        );
+    }
+
+    // It should throw an error if a number is provided to a text field
+    #[test]
+    fn number_to_text() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let schema = ESD::create_default(&tx);
+        let attr = ASD::create_default(&tx, &schema);
+
+        let data = serde_json::from_str(&format!(
+            r#"
+            {{
+              "{attr}": 42
+            }}
+            "#
+        ))
+        .unwrap();
+
+        let result = add_entity(&tx, &schema, data);
+        assert_eq!(
+            result,
+            Err(Error::ModuleError(
+                "Provided a number to a non-numeric field".to_string()
+            ))
+        );
+    }
+
+    // It should throw an error if a date is not in ISO 8601 format
+    #[test]
+    fn invalid_date() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let schema = ESD::create_default(&tx);
+        let attr = ASD::default()
+            .attr_type(SimpleAttributeType::Date)
+            .create(&tx, &schema);
+
+        let data = serde_json::from_str(&format!(
+            r#"
+            {{
+              "{attr}": "29/02/2024"
+            }}
+            "#
+        ))
+        .unwrap();
+
+        let result = add_entity(&tx, &schema, data);
+        assert_eq!(
+            result,
+            Err(Error::ModuleError(
+                "Provided value is not a valid ISO 8601 date".to_string()
+            ))
+        );
+    }
+
+    // It should throw an error if null is provided to a required field
+    #[test]
+    fn null_to_required() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let schema = ESD::create_default(&tx);
+        let attr = ASD::default()
+            .attr_type(SimpleAttributeType::Boolean)
+            .create(&tx, &schema);
+
+        let data = serde_json::from_str(&format!(
+            r#"
+            {{
+              "{attr}": null
+            }}
+            "#
+        ))
+        .unwrap();
+
+        let result = add_entity(&tx, &schema, data);
+        assert_eq!(
+            result,
+            Err(Error::ModuleError(
+                "Provided null to a non-optional field".to_string()
+            ))
+        );
+    }
+
+    // It should throw an error if a boolean is provided to a text field
+    #[test]
+    fn boolean_to_text() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let schema = ESD::create_default(&tx);
+        let attr = ASD::create_default(&tx, &schema);
+
+        let data = serde_json::from_str(&format!(
+            r#"
+            {{
+              "{attr}": true
+            }}
+            "#
+        ))
+        .unwrap();
+
+        let result = add_entity(&tx, &schema, data);
+        assert_eq!(
+            result,
+            Err(Error::ModuleError(
+                "Provided a boolean to a non-boolean field".to_string()
+            ))
+        );
+    }
+
+    // It should throw an error if a value is not one of the select options
+    #[test]
+    fn invalid_select_option() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let schema = ESD::create_default(&tx);
+        let attr = AttributeSchema::new(
+            &tx,
+            CreateAttributeSchema {
+                entity: schema.clone(),
+                name: "Status".to_string(),
+                quantity: Quantity::List,
+                constraints: Constraints::default(),
+                default: None,
+                unique: false,
+                attr_type: CreateAttributeType::Select {
+                    options: vec!["Todo".to_string(), "Done".to_string()],
+                },
+            },
+        )
+        .unwrap()
+        .id;
+
+        let data = serde_json::from_str(&format!(
+            r#"
+            {{
+              "{attr}": ["Todo", "Doing"]
+            }}
+            "#
+        ))
+        .unwrap();
+
+        let result = add_entity(&tx, &schema, data);
+        assert_eq!(
+            result,
+            Err(Error::ModuleError(
+                "Provided value is not an option of the select field".to_string()
+            ))
+        );
+    }
+
+    // It should throw an error if a URL cannot be parsed
+    #[test]
+    fn invalid_url() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let schema = ESD::create_default(&tx);
+        let attr = ASD::default()
+            .attr_type(SimpleAttributeType::Url)
+            .create(&tx, &schema);
+
+        let data = serde_json::from_str(&format!(
+            r#"
+            {{
+              "{attr}": "example.com/books"
+            }}
+            "#
+        ))
+        .unwrap();
+
+        let result = add_entity(&tx, &schema, data);
+        assert_eq!(
+            result,
+            Err(Error::ModuleError(
+                "Provided value is not a valid URL: relative URL without a base".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn invalid_location() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let schema = ESD::create_default(&tx);
+        let attr = ASD::default()
+            .attr_type(SimpleAttributeType::Location)
+            .create(&tx, &schema);
+
+        let data = serde_json::from_str(&format!(
+            r#"
+            {{
+              "{attr}": {{ "latitude": 91.0, "longitude": 0.0 }}
+            }}
+            "#
+        ))
+        .unwrap();
+
+        let result = add_entity(&tx, &schema, data);
+        assert_eq!(
+            result,
+            Err(Error::ModuleError(
+                "Latitude must be between -90 and 90".to_string()
+            ))
+        );
+    }
 }
@@ -94,10 +94,7 @@ mod tests {
         let tx = conn.transaction().unwrap();
 
         let parent_schema = &ESD::create_default(&tx);
-        let child_schema = &ESD {
-            name: "Child".to_string(),
-        }
-        .create(&tx);
+        let child_schema = &ESD::default().name("Child").create(&tx);
 
         let reference_attr = RSD::create_default(&tx, parent_schema, child_schema);
 
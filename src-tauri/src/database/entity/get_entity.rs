@@ -1,20 +1,28 @@
 use std::collections::{HashMap, HashSet};
 
-use rusqlite::{Error, Result, Transaction};
+use rusqlite::{params_from_iter, Error, Result, Transaction};
 use serde_json::Value;
 
-use crate::database::attribute::{get_reference_attrs, get_text_attrs};
+use crate::database::attribute::{
+    get_attachment_attrs, get_backlink_attrs, get_boolean_attrs, get_date_attrs,
+    get_datetime_attrs, get_integer_attrs, get_location_attrs, get_number_attrs,
+    get_reference_attrs, get_rollup_attrs, get_select_attrs, get_text_attrs, get_url_attrs,
+};
 use crate::models::attribute_type::{AttributeType, SimpleAttributeType};
 use crate::models::entity::EntityId;
+use crate::models::entity_schema::EntitySchemaId;
+use crate::units::convert;
+use crate::utils::placeholders;
 use crate::{
     database::{
         attribute_schema::{GetSchemaMap, RawAttributeSchema, SchemaMap},
         response_map::EntitiesData,
+        Get,
     },
     models::attribute_schema::{AttributeSchemaId, Quantity},
 };
 
-use super::{EntityField, EntityRequest, EntityResponse};
+use super::{EntityAttribute, EntityField, EntityRequest, EntityResponse, MeasureField};
 
 fn get_many<'a>(
     tx: &Transaction,
@@ -33,36 +41,66 @@ fn get_many<'a>(
     // Get child attributes
     for attr in request {
         match attr {
-            EntityField::Attribute(..) => {}
+            EntityField::Attribute(..) | EntityField::Measure(..) => {}
             EntityField::Entity(entity_request) => {
                 let attr = &entity_request.attribute;
-                let subrequest = &entity_request.request;
 
                 let schema_info = schema.get(attr).unwrap();
                 let quantity = &schema_info.quantity;
 
-                let mut data = get_reference_attrs(tx, &entity_ids, attr)?;
+                let mut data = match &schema_info.attr_type {
+                    AttributeType::Backlink(backlink) => {
+                        get_backlink_attrs(tx, &entity_ids, &backlink.reference)?
+                    }
+                    _ => get_reference_attrs(tx, &entity_ids, attr)?,
+                };
+
+                // Links of a reference declaring edge properties carry them
+                // under the edge key
+                let edges = match &schema_info.attr_type {
+                    AttributeType::Reference(reference) => reference.edges.clone(),
+                    AttributeType::Backlink(backlink) => {
+                        match RawAttributeSchema::get(tx, &backlink.reference)?.attr_type {
+                            AttributeType::Reference(reference) => reference.edges,
+                            _ => Vec::new(),
+                        }
+                    }
+                    _ => Vec::new(),
+                };
 
                 let mut children = Vec::new();
 
                 for datum in data.values() {
-                    children.extend(datum);
+                    children.extend(datum.iter().map(|edge| &edge.entity));
                 }
 
-                let child_data = get_many(tx, children, subrequest)?;
+                let child_data = get_targets(tx, children, entity_request)?;
+
+                // Entities without any links still get an empty value
+                for entity_id in &entity_ids {
+                    let children = data.remove(*entity_id).unwrap_or_default();
+                    let entity_map = result.entry((*entity_id).clone()).or_default();
+
+                    let mut objects = children.iter().filter_map(|edge| {
+                        let mut child = child_data.get(&edge.entity)?.clone();
+
+                        if !edges.is_empty() {
+                            let properties = edges
+                                .iter()
+                                .map(|property| {
+                                    let value = edge.properties.get(&property.name);
+                                    (property.name.clone(), value.cloned().unwrap_or(Value::Null))
+                                })
+                                .collect();
+                            child.insert("edge".to_string(), Value::Object(properties));
+                        }
 
-                for (entity_id, children) in data.drain().take(1) {
-                    let entity_map = result.entry(entity_id).or_default();
+                        Some(Value::Object(child))
+                    });
 
                     let data = match quantity {
-                        Quantity::Required => {
-                            if children.len() != 1 {
-                                todo!();
-                            }
-
-                            Value::Object(child_data.get(&children[0]).unwrap().clone())
-                        }
-                        _ => todo!(),
+                        Quantity::List => Value::Array(objects.collect()),
+                        _ => objects.next().unwrap_or(Value::Null),
                     };
 
                     entity_map.insert(attr.to_string(), data);
@@ -79,36 +117,84 @@ fn get_many<'a>(
 
     let mut data = plan.execute(tx)?;
 
+    // Rollups are aggregated for all entities at once
+    let mut rollups = HashMap::new();
+    for attr in request {
+        if let EntityField::Attribute(attribute) = attr {
+            if let Some(AttributeType::Rollup(rollup)) =
+                schema.get(attribute).map(|entry| &entry.attr_type)
+            {
+                rollups.insert(attribute, get_rollup_attrs(tx, &entity_ids, rollup)?);
+            }
+        }
+    }
+
     for entity_id in entity_ids {
-        let mut entity_data = data.remove(entity_id).unwrap_or_default();
+        let entity_data = data.remove(entity_id).unwrap_or_default();
 
         let entity_map = result.entry(entity_id.clone()).or_default();
 
         for attr in request {
             match attr {
-                EntityField::Attribute(attribute) => {
-                    let Some(schema) = schema.get(attribute) else {
+                EntityField::Attribute(attribute)
+                | EntityField::Measure(MeasureField { attribute, .. }) => {
+                    let Some(attr_schema) = schema.get(attribute) else {
                         continue;
                     };
 
-                    let attr_data = entity_data.remove(&attribute);
-                    let quantity = &schema.quantity;
+                    if let AttributeType::Computed(computed) = &attr_schema.attr_type {
+                        let data = computed.evaluate(&schema, &entity_data)?;
+                        entity_map.insert(attribute.to_string(), data);
+                        continue;
+                    }
+
+                    if let Some(rollup) = rollups.get_mut(attribute) {
+                        let data = rollup.remove(entity_id).unwrap_or(Value::Null);
+                        entity_map.insert(attribute.to_string(), data);
+                        continue;
+                    }
+
+                    // Attributes can also be read by computed fields, so
+                    // they are left in place
+                    let attr_data = entity_data.get(attribute).cloned();
+                    let quantity = &attr_schema.quantity;
 
                     let data = match quantity {
                         Quantity::Required => match attr_data {
                             Some(mut data) => {
                                 if data.len() > 1 {
-                                    todo!()
+                                    Err(Error::ModuleError(
+                                        "Multiple values for single field".to_string(),
+                                    ))
                                 } else {
                                     Ok(data.remove(0))
                                 }
                             }
                             None => Err(Error::QueryReturnedNoRows),
                         },
-                        Quantity::Optional => todo!(),
+                        // Unset optional fields are returned as null
+                        Quantity::Optional => match attr_data {
+                            Some(mut data) => {
+                                if data.len() > 1 {
+                                    Err(Error::ModuleError(
+                                        "Multiple values for single field".to_string(),
+                                    ))
+                                } else {
+                                    Ok(data.remove(0))
+                                }
+                            }
+                            None => Ok(Value::Null),
+                        },
                         Quantity::List => Ok(Value::Array(attr_data.unwrap_or_default())),
                     }?;
 
+                    let data = match attr {
+                        EntityField::Measure(field) => {
+                            convert_measure(data, &attr_schema.attr_type, &field.unit)?
+                        }
+                        _ => data,
+                    };
+
                     entity_map.insert(attribute.to_string(), data);
                 }
                 EntityField::Entity(..) => (),
@@ -119,6 +205,47 @@ fn get_many<'a>(
     Ok(result)
 }
 
+// Gets referenced entities grouped by schema, as each schema can have its own
+// fields requested through fragments
+fn get_targets(
+    tx: &Transaction,
+    entity_ids: Vec<&EntityId>,
+    request: &EntityAttribute,
+) -> Result<HashMap<EntityId, EntityResponse>> {
+    let mut statement = tx.prepare(&format!(
+        "SELECT id, schema FROM entity WHERE id IN ({})",
+        placeholders(entity_ids.len())
+    ))?;
+    let mut rows = statement.query(params_from_iter(&entity_ids))?;
+
+    let mut schemas: HashMap<EntityId, EntitySchemaId> = HashMap::new();
+    while let Some(row) = rows.next()? {
+        schemas.insert(row.get(0)?, row.get(1)?);
+    }
+
+    let mut groups: HashMap<EntitySchemaId, Vec<&EntityId>> = HashMap::new();
+    for entity_id in entity_ids {
+        let schema = schemas.get(entity_id).ok_or(Error::QueryReturnedNoRows)?;
+        groups.entry(schema.clone()).or_default().push(entity_id);
+    }
+
+    let mut result = HashMap::new();
+    for (schema, entity_ids) in groups {
+        let ancestors = schema.ancestors(tx)?;
+
+        let mut fields = request.request.0.clone();
+        for fragment in &request.fragments {
+            if ancestors.contains(&fragment.schema) {
+                fields.extend(fragment.request.0.iter().cloned());
+            }
+        }
+
+        result.extend(get_many(tx, entity_ids, &EntityRequest(fields))?);
+    }
+
+    Ok(result)
+}
+
 pub fn get(
     tx: &Transaction,
     entity_id: &EntityId,
@@ -134,9 +261,41 @@ pub fn get(
     }
 }
 
+fn convert_measure(value: Value, attr_type: &AttributeType, unit: &str) -> Result<Value> {
+    let AttributeType::Measure(measure) = attr_type else {
+        return Err(Error::ModuleError(
+            "Attribute is not a measure field".to_string(),
+        ));
+    };
+
+    match value {
+        Value::Number(number) => {
+            let number = number.as_f64().unwrap_or_default();
+            Ok(Value::from(convert(number, &measure.unit, unit)?))
+        }
+        Value::Array(values) => {
+            let mut converted = Vec::new();
+            for value in values {
+                converted.push(convert_measure(value, attr_type, unit)?);
+            }
+            Ok(Value::Array(converted))
+        }
+        value => Ok(value),
+    }
+}
+
 struct RequestPlan<'a> {
     entities: &'a Vec<&'a EntityId>,
     text: HashSet<&'a AttributeSchemaId>,
+    integer: HashSet<&'a AttributeSchemaId>,
+    number: HashSet<&'a AttributeSchemaId>,
+    date: HashSet<&'a AttributeSchemaId>,
+    datetime: HashSet<&'a AttributeSchemaId>,
+    boolean: HashSet<&'a AttributeSchemaId>,
+    select: HashSet<&'a AttributeSchemaId>,
+    url: HashSet<&'a AttributeSchemaId>,
+    attachment: HashSet<&'a AttributeSchemaId>,
+    location: HashSet<&'a AttributeSchemaId>,
 }
 
 impl<'a> RequestPlan<'a> {
@@ -144,6 +303,15 @@ impl<'a> RequestPlan<'a> {
         Self {
             entities,
             text: HashSet::new(),
+            integer: HashSet::new(),
+            number: HashSet::new(),
+            date: HashSet::new(),
+            datetime: HashSet::new(),
+            boolean: HashSet::new(),
+            select: HashSet::new(),
+            url: HashSet::new(),
+            attachment: HashSet::new(),
+            location: HashSet::new(),
         }
     }
 
@@ -153,36 +321,113 @@ impl<'a> RequestPlan<'a> {
         let text_attrs: Vec<&AttributeSchemaId> = self.text.into_iter().collect();
         response_map = get_text_attrs(tx, response_map, self.entities, &text_attrs)?;
 
+        let integer_attrs: Vec<&AttributeSchemaId> = self.integer.into_iter().collect();
+        response_map = get_integer_attrs(tx, response_map, self.entities, &integer_attrs)?;
+
+        let number_attrs: Vec<&AttributeSchemaId> = self.number.into_iter().collect();
+        response_map = get_number_attrs(tx, response_map, self.entities, &number_attrs)?;
+
+        let date_attrs: Vec<&AttributeSchemaId> = self.date.into_iter().collect();
+        response_map = get_date_attrs(tx, response_map, self.entities, &date_attrs)?;
+
+        let datetime_attrs: Vec<&AttributeSchemaId> = self.datetime.into_iter().collect();
+        response_map = get_datetime_attrs(tx, response_map, self.entities, &datetime_attrs)?;
+
+        let boolean_attrs: Vec<&AttributeSchemaId> = self.boolean.into_iter().collect();
+        response_map = get_boolean_attrs(tx, response_map, self.entities, &boolean_attrs)?;
+
+        let select_attrs: Vec<&AttributeSchemaId> = self.select.into_iter().collect();
+        response_map = get_select_attrs(tx, response_map, self.entities, &select_attrs)?;
+
+        let url_attrs: Vec<&AttributeSchemaId> = self.url.into_iter().collect();
+        response_map = get_url_attrs(tx, response_map, self.entities, &url_attrs)?;
+
+        let attachment_attrs: Vec<&AttributeSchemaId> = self.attachment.into_iter().collect();
+        response_map = get_attachment_attrs(tx, response_map, self.entities, &attachment_attrs)?;
+
+        let location_attrs: Vec<&AttributeSchemaId> = self.location.into_iter().collect();
+        response_map = get_location_attrs(tx, response_map, self.entities, &location_attrs)?;
+
         match response_map {
             Some(b) => Ok(b.finalize()),
             None => Ok(HashMap::new()),
         }
     }
 
-    pub fn add_attr(&mut self, schema: &SchemaMap, attribute: &'a EntityField) -> Result<()> {
+    pub fn add_attr(&mut self, schema: &'a SchemaMap, attribute: &EntityField) -> Result<()> {
         match attribute {
-            EntityField::Entity(..) => {}
-            EntityField::Attribute(attribute) => {
+            EntityField::Entity(..) => Ok(()),
+            EntityField::Attribute(attribute)
+            | EntityField::Measure(MeasureField { attribute, .. }) => {
                 let schema_entry = schema.get(&attribute);
                 let schema_entry = match schema_entry {
                     Some(entry) => Ok(entry),
                     None => Err(Error::ModuleError("Schema entry not found".to_string())),
                 }?;
 
-                match schema_entry.attr_type {
-                    AttributeType::Reference(..) => Err(Error::InvalidQuery),
-                    AttributeType::Simple(attr_type) => {
-                        match attr_type {
-                            SimpleAttributeType::Longform => todo!(),
-                            SimpleAttributeType::Text | SimpleAttributeType::RichText => {
-                                self.text.insert(attribute);
-                            }
-                        }
-                        Ok(())
+                self.add_schema(schema, schema_entry)
+            }
+        }
+    }
+
+    fn add_schema(
+        &mut self,
+        schema: &'a SchemaMap,
+        schema_entry: &'a RawAttributeSchema,
+    ) -> Result<()> {
+        let attribute = &schema_entry.id;
+
+        match &schema_entry.attr_type {
+            AttributeType::Reference(..) | AttributeType::Backlink(..) => Err(Error::InvalidQuery),
+            AttributeType::Select(..) => {
+                self.select.insert(attribute);
+                Ok(())
+            }
+            AttributeType::Measure(..) => {
+                self.number.insert(attribute);
+                Ok(())
+            }
+            AttributeType::Rollup(..) => Ok(()),
+            AttributeType::Computed(computed) => {
+                let (_, inputs) = computed.inputs(schema)?;
+                for input in inputs {
+                    self.add_schema(schema, input)?;
+                }
+                Ok(())
+            }
+            AttributeType::Simple(attr_type) => {
+                match attr_type {
+                    SimpleAttributeType::Longform => todo!(),
+                    SimpleAttributeType::Text | SimpleAttributeType::RichText => {
+                        self.text.insert(attribute);
                     }
-                }?;
+                    SimpleAttributeType::Integer => {
+                        self.integer.insert(attribute);
+                    }
+                    SimpleAttributeType::Number => {
+                        self.number.insert(attribute);
+                    }
+                    SimpleAttributeType::Date => {
+                        self.date.insert(attribute);
+                    }
+                    SimpleAttributeType::DateTime => {
+                        self.datetime.insert(attribute);
+                    }
+                    SimpleAttributeType::Boolean => {
+                        self.boolean.insert(attribute);
+                    }
+                    SimpleAttributeType::Url => {
+                        self.url.insert(attribute);
+                    }
+                    SimpleAttributeType::Attachment => {
+                        self.attachment.insert(attribute);
+                    }
+                    SimpleAttributeType::Location => {
+                        self.location.insert(attribute);
+                    }
+                }
+                Ok(())
             }
         }
-        Ok(())
     }
 }
@@ -1,27 +1,83 @@
 pub mod add_entity;
 mod delete_entity;
 pub mod get_entity;
+mod named;
+pub mod query_entity;
 pub use add_entity::add_entity;
 pub use get_entity::get;
+pub use named::{add_named_entity, get_named};
+pub use query_entity::{
+    get_in_bounding_box, get_in_date_range, get_linking_to_host, get_within_radius,
+};
 
 use serde::Deserialize;
 use serde_json::{Map, Value};
 
-use crate::models::attribute_schema::AttributeSchemaId;
+use crate::models::{attribute_schema::AttributeSchemaId, entity_schema::EntitySchemaId};
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Clone)]
 pub enum EntityField {
     Entity(EntityAttribute),
     Attribute(AttributeSchemaId),
+    Measure(MeasureField),
 }
 
-#[derive(Deserialize)]
+// A measure attribute converted to the given unit
+#[derive(Deserialize, Clone)]
+pub struct MeasureField {
+    pub attribute: AttributeSchemaId,
+    pub unit: String,
+}
+
+#[derive(Deserialize, Clone)]
 pub struct EntityAttribute {
     pub attribute: AttributeSchemaId,
     pub request: EntityRequest,
+    #[serde(default)]
+    pub fragments: Vec<EntityFragment>,
+}
+
+// Fields requested only of referenced entities of the schema, or of a schema
+// extending it, like a GraphQL inline fragment
+#[derive(Deserialize, Clone)]
+pub struct EntityFragment {
+    pub schema: EntitySchemaId,
+    pub request: EntityRequest,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Clone)]
 pub struct EntityRequest(pub Vec<EntityField>);
 
 pub type EntityResponse = Map<String, Value>;
+
+// The same request with attributes and schemas given by name. Names are
+// resolved against the schema being read, and the response is keyed by name
+#[derive(Deserialize, Clone)]
+pub enum NamedField {
+    Entity(NamedEntityAttribute),
+    Attribute(String),
+    Measure(NamedMeasureField),
+}
+
+#[derive(Deserialize, Clone)]
+pub struct NamedMeasureField {
+    pub attribute: String,
+    pub unit: String,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct NamedEntityAttribute {
+    pub attribute: String,
+    pub request: NamedRequest,
+    #[serde(default)]
+    pub fragments: Vec<NamedFragment>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct NamedFragment {
+    pub schema: String,
+    pub request: NamedRequest,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct NamedRequest(pub Vec<NamedField>);
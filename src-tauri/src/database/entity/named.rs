@@ -0,0 +1,445 @@
+use std::collections::HashMap;
+
+use rusqlite::{params, Error, OptionalExtension, Result, Transaction};
+use serde_json::{Map, Value};
+
+use crate::{
+    database::attribute_schema::{GetSchemaMap, RawAttributeSchema, SchemaMap},
+    models::{
+        attribute_schema::AttributeSchemaId, attribute_type::AttributeType, entity::EntityId,
+        entity_schema::EntitySchemaId,
+    },
+};
+
+use super::{
+    add_entity, get, EntityAttribute, EntityField, EntityFragment, EntityRequest, EntityResponse,
+    MeasureField, NamedField, NamedRequest,
+};
+
+// Names of the attributes a request resolved, to key the response by
+type Names = HashMap<AttributeSchemaId, String>;
+
+fn find<'a>(schema: &'a SchemaMap, name: &str) -> Result<&'a RawAttributeSchema> {
+    schema
+        .values()
+        .find(|attribute| attribute.name == name)
+        .ok_or_else(|| Error::ModuleError(format!("Unknown attribute {name}")))
+}
+
+// The schemas a reference or backlink field leads to
+fn target_schemas(tx: &Transaction, attribute: &RawAttributeSchema) -> Result<Vec<EntitySchemaId>> {
+    match &attribute.attr_type {
+        AttributeType::Reference(reference) => Ok(reference
+            .targets
+            .iter()
+            .map(|target| target.id.clone())
+            .collect()),
+        AttributeType::Backlink(backlink) => Ok(vec![tx.query_row(
+            "SELECT entity FROM attribute_schema WHERE id = ?1",
+            params![backlink.reference],
+            |row| row.get(0),
+        )?]),
+        _ => Err(Error::ModuleError(format!(
+            "Attribute {} is not a reference field",
+            attribute.name
+        ))),
+    }
+}
+
+// Nested fields are resolved separately against every schema the reference
+// can point at, and requested through a fragment for each
+fn resolve(
+    tx: &Transaction,
+    schema: &EntitySchemaId,
+    request: &NamedRequest,
+    names: &mut Names,
+) -> Result<EntityRequest> {
+    let map = RawAttributeSchema::get_map(tx, schema)?;
+
+    let mut fields = Vec::new();
+    for field in &request.0 {
+        let field = match field {
+            NamedField::Attribute(name) => {
+                let attribute = find(&map, name)?;
+                names.insert(attribute.id.clone(), attribute.name.clone());
+                EntityField::Attribute(attribute.id.clone())
+            }
+            NamedField::Measure(measure) => {
+                let attribute = find(&map, &measure.attribute)?;
+                names.insert(attribute.id.clone(), attribute.name.clone());
+                EntityField::Measure(MeasureField {
+                    attribute: attribute.id.clone(),
+                    unit: measure.unit.clone(),
+                })
+            }
+            NamedField::Entity(entity) => {
+                let attribute = find(&map, &entity.attribute)?;
+                names.insert(attribute.id.clone(), attribute.name.clone());
+
+                let targets = target_schemas(tx, attribute)?;
+                if targets.is_empty() && !entity.request.0.is_empty() {
+                    return Err(Error::ModuleError(format!(
+                        "Attribute {} can point at any schema, so its fields must be requested through fragments",
+                        attribute.name
+                    )));
+                }
+
+                let mut fragments = Vec::new();
+                for target in targets {
+                    let request = resolve(tx, &target, &entity.request, names)?;
+                    fragments.push(EntityFragment {
+                        schema: target,
+                        request,
+                    });
+                }
+
+                for fragment in &entity.fragments {
+                    let target: EntitySchemaId = tx
+                        .query_row(
+                            "SELECT id FROM entity_schema WHERE name = ?1",
+                            params![fragment.schema],
+                            |row| row.get(0),
+                        )
+                        .optional()?
+                        .ok_or_else(|| {
+                            Error::ModuleError(format!("Unknown schema {}", fragment.schema))
+                        })?;
+
+                    let request = resolve(tx, &target, &fragment.request, names)?;
+                    fragments.push(EntityFragment {
+                        schema: target,
+                        request,
+                    });
+                }
+
+                EntityField::Entity(EntityAttribute {
+                    attribute: attribute.id.clone(),
+                    request: EntityRequest(Vec::new()),
+                    fragments,
+                })
+            }
+        };
+
+        fields.push(field);
+    }
+
+    Ok(EntityRequest(fields))
+}
+
+// Swaps attribute ids for names in a response, following the request so
+// only requested fields are renamed. Keys inside values such as edges,
+// attachments and locations are left alone
+fn rename(mut response: EntityResponse, request: &EntityRequest, names: &Names) -> EntityResponse {
+    for field in &request.0 {
+        let id = match field {
+            EntityField::Attribute(id) => id,
+            EntityField::Measure(measure) => &measure.attribute,
+            EntityField::Entity(entity) => &entity.attribute,
+        };
+
+        let Some(mut value) = response.remove(&id.to_string()) else {
+            continue;
+        };
+
+        if let EntityField::Entity(entity) = field {
+            value = rename_entities(value, entity, names);
+        }
+
+        let name = names.get(id).cloned().unwrap_or_else(|| id.to_string());
+        response.insert(name, value);
+    }
+
+    response
+}
+
+fn rename_entities(value: Value, entity: &EntityAttribute, names: &Names) -> Value {
+    match value {
+        Value::Object(object) => {
+            let object = rename(object, &entity.request, names);
+            Value::Object(entity.fragments.iter().fold(object, |object, fragment| {
+                rename(object, &fragment.request, names)
+            }))
+        }
+        Value::Array(values) => Value::Array(
+            values
+                .into_iter()
+                .map(|value| rename_entities(value, entity, names))
+                .collect(),
+        ),
+        value => value,
+    }
+}
+
+// Creates an entity from an object keyed by attribute name
+pub fn add_named_entity(
+    tx: &Transaction,
+    schema_id: &EntitySchemaId,
+    data: Value,
+) -> Result<EntityId> {
+    let Value::Object(data) = data else {
+        return Err(Error::ModuleError(
+            "Provided data is not an object".to_string(),
+        ));
+    };
+
+    let map = RawAttributeSchema::get_map(tx, schema_id)?;
+
+    let mut keyed = Map::new();
+    for (name, value) in data {
+        keyed.insert(find(&map, &name)?.id.to_string(), value);
+    }
+
+    add_entity(tx, schema_id, Value::Object(keyed))
+}
+
+pub fn get_named(
+    tx: &Transaction,
+    entity_id: &EntityId,
+    request: &NamedRequest,
+) -> Result<EntityResponse> {
+    let schema: EntitySchemaId = tx.query_row(
+        "SELECT schema FROM entity WHERE id = ?1",
+        params![entity_id],
+        |row| row.get(0),
+    )?;
+
+    let mut names = HashMap::new();
+    let request = resolve(tx, &schema, request, &mut names)?;
+
+    Ok(rename(get(tx, entity_id, &request)?, &request, &names))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{
+        database::{
+            entity::{NamedEntityAttribute, NamedFragment},
+            test::test_util::{setup, ASD, ESD, RSD},
+            New,
+        },
+        models::{
+            attachment::BlobId,
+            attribute_schema::{AttributeSchema, Constraints, CreateAttributeSchema, Quantity},
+            attribute_type::{
+                CreateAttributeType, CreateReferenceAttribute, EdgeProperty, SimpleAttributeType,
+            },
+        },
+        utils::get_timestamp,
+    };
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let book = ESD::default().name("Book").create(&tx);
+        let author = ESD::default().name("Author").create(&tx);
+        ASD::default().name("title").create(&tx, &book);
+        ASD::default().name("name").create(&tx, &author);
+        RSD::default().name("author").create(&tx, &book, &author);
+
+        let melville =
+            add_named_entity(&tx, &author, json!({ "name": "Herman Melville" })).unwrap();
+        let moby_dick = add_named_entity(
+            &tx,
+            &book,
+            json!({ "title": "Moby Dick", "author": melville.to_string() }),
+        )
+        .unwrap();
+
+        let request = NamedRequest(vec![
+            NamedField::Attribute("title".to_string()),
+            NamedField::Entity(NamedEntityAttribute {
+                attribute: "author".to_string(),
+                request: NamedRequest(vec![NamedField::Attribute("name".to_string())]),
+                fragments: Vec::new(),
+            }),
+        ]);
+
+        let response = get_named(&tx, &moby_dick, &request).unwrap();
+        assert_eq!(response.get("title"), Some(&json!("Moby Dick")));
+        assert_eq!(
+            response.get("author").and_then(|author| author.get("name")),
+            Some(&json!("Herman Melville"))
+        );
+    }
+
+    // Keys inside edges, attachments and locations are not attribute names
+    #[test]
+    fn structured_values() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let person = ESD::default().name("Person").create(&tx);
+        let project = ESD::default().name("Project").create(&tx);
+        ASD::default().name("name").create(&tx, &project);
+        ASD::default()
+            .name("photo")
+            .attr_type(SimpleAttributeType::Attachment)
+            .create(&tx, &person);
+        ASD::default()
+            .name("home")
+            .attr_type(SimpleAttributeType::Location)
+            .create(&tx, &person);
+        AttributeSchema::new(
+            &tx,
+            CreateAttributeSchema {
+                entity: person.clone(),
+                name: "projects".to_string(),
+                quantity: Quantity::List,
+                constraints: Constraints::default(),
+                default: None,
+                unique: false,
+                attr_type: CreateAttributeType::Reference(CreateReferenceAttribute {
+                    targets: vec![project.clone()],
+                    inverse: None,
+                    edges: vec![EdgeProperty {
+                        name: "role".to_string(),
+                        attr_type: SimpleAttributeType::Text,
+                    }],
+                }),
+            },
+        )
+        .unwrap();
+
+        let hash = "a".repeat(64);
+        tx.execute(
+            "INSERT INTO blob (id, hash, size, created, updated) VALUES (?1, ?2, 4, ?3, ?3)",
+            params![BlobId::new(), hash, get_timestamp()],
+        )
+        .unwrap();
+
+        let cortex = add_named_entity(&tx, &project, json!({ "name": "Cortex" })).unwrap();
+        let photo = json!({ "hash": hash, "filename": "me.png", "mime": "image/png" });
+        let home = json!({ "latitude": 51.5, "longitude": -0.1, "label": "London" });
+        let alice = add_named_entity(
+            &tx,
+            &person,
+            json!({
+                "photo": photo,
+                "home": home,
+                "projects": [{ "target": cortex.to_string(), "role": "lead" }],
+            }),
+        )
+        .unwrap();
+
+        let request = NamedRequest(vec![
+            NamedField::Attribute("photo".to_string()),
+            NamedField::Attribute("home".to_string()),
+            NamedField::Entity(NamedEntityAttribute {
+                attribute: "projects".to_string(),
+                request: NamedRequest(vec![NamedField::Attribute("name".to_string())]),
+                fragments: Vec::new(),
+            }),
+        ]);
+
+        let response = get_named(&tx, &alice, &request).unwrap();
+        assert_eq!(
+            response.get("photo"),
+            Some(&json!({ "hash": hash, "filename": "me.png", "mime": "image/png", "size": 4 }))
+        );
+        assert_eq!(response.get("home"), Some(&home));
+
+        let projects = response.get("projects").and_then(Value::as_array).unwrap();
+        assert_eq!(projects.len(), 1);
+        assert_eq!(projects[0].get("name"), Some(&json!("Cortex")));
+        assert_eq!(projects[0].get("edge"), Some(&json!({ "role": "lead" })));
+    }
+
+    #[test]
+    fn unknown_names() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let book = ESD::default().name("Book").create(&tx);
+        ASD::default()
+            .name("title")
+            .quantity(Quantity::Optional)
+            .create(&tx, &book);
+
+        assert_eq!(
+            add_named_entity(&tx, &book, json!({ "subtitle": "The Whale" })),
+            Err(Error::ModuleError("Unknown attribute subtitle".to_string()))
+        );
+
+        let entity = add_named_entity(&tx, &book, json!({})).unwrap();
+        let request = NamedRequest(vec![NamedField::Attribute("pages".to_string())]);
+        assert_eq!(
+            get_named(&tx, &entity, &request).map(|_| ()),
+            Err(Error::ModuleError("Unknown attribute pages".to_string()))
+        );
+
+        let request = NamedRequest(vec![NamedField::Entity(NamedEntityAttribute {
+            attribute: "title".to_string(),
+            request: NamedRequest(Vec::new()),
+            fragments: Vec::new(),
+        })]);
+        assert_eq!(
+            get_named(&tx, &entity, &request).map(|_| ()),
+            Err(Error::ModuleError(
+                "Attribute title is not a reference field".to_string()
+            ))
+        );
+    }
+
+    // References without targets take their fields from named fragments
+    #[test]
+    fn untargeted_fragments() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let note = ESD::default().name("Note").create(&tx);
+        let person = ESD::default().name("Person").create(&tx);
+        ASD::default().name("name").create(&tx, &person);
+        AttributeSchema::new(
+            &tx,
+            CreateAttributeSchema {
+                entity: note.clone(),
+                name: "subject".to_string(),
+                quantity: Quantity::Required,
+                constraints: Constraints::default(),
+                default: None,
+                unique: false,
+                attr_type: CreateAttributeType::Reference(CreateReferenceAttribute {
+                    targets: Vec::new(),
+                    inverse: None,
+                    edges: Vec::new(),
+                }),
+            },
+        )
+        .unwrap();
+
+        let alice = add_named_entity(&tx, &person, json!({ "name": "Alice" })).unwrap();
+        let entity = add_named_entity(&tx, &note, json!({ "subject": alice.to_string() })).unwrap();
+
+        let field = |request: Vec<NamedField>, fragments: Vec<NamedFragment>| {
+            NamedRequest(vec![NamedField::Entity(NamedEntityAttribute {
+                attribute: "subject".to_string(),
+                request: NamedRequest(request),
+                fragments,
+            })])
+        };
+        let name = || vec![NamedField::Attribute("name".to_string())];
+
+        assert!(get_named(&tx, &entity, &field(name(), Vec::new())).is_err());
+
+        let request = field(
+            Vec::new(),
+            vec![NamedFragment {
+                schema: "Person".to_string(),
+                request: NamedRequest(name()),
+            }],
+        );
+        let response = get_named(&tx, &entity, &request).unwrap();
+        assert_eq!(
+            response
+                .get("subject")
+                .and_then(|subject| subject.get("name")),
+            Some(&json!("Alice"))
+        );
+    }
+}
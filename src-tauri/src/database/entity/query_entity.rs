@@ -0,0 +1,475 @@
+use rusqlite::{params, types::Value, Error, Transaction};
+use url::Host;
+
+use crate::{
+    database::{
+        attribute_schema::RawAttributeSchema,
+        attribute_type::{check_coordinates, parse_date, parse_datetime},
+        entity_schema::descendants,
+        Get,
+    },
+    models::{
+        attribute_schema::AttributeSchemaId,
+        attribute_type::{AttributeType, SimpleAttributeType},
+        entity::EntityId,
+        entity_schema::EntitySchemaId,
+        location::BoundingBox,
+    },
+};
+
+// Mean radius of the earth
+const EARTH_RADIUS_KM: f64 = 6371.0088;
+
+// Gets the entities of a schema with a value of the given Date or DateTime
+// attribute in the range [from, to), ordered by their earliest matching value
+pub fn get_in_date_range(
+    tx: &Transaction,
+    schema: &EntitySchemaId,
+    attribute: &AttributeSchemaId,
+    from: &str,
+    to: &str,
+) -> rusqlite::Result<Vec<EntityId>> {
+    let attr_schema = RawAttributeSchema::get(tx, attribute)?;
+
+    let (table, column, from, to) = match attr_schema.attr_type {
+        AttributeType::Simple(SimpleAttributeType::Date) => Ok((
+            "date_attribute",
+            "value",
+            Value::Text(parse_date(from)?.to_string()),
+            Value::Text(parse_date(to)?.to_string()),
+        )),
+        AttributeType::Simple(SimpleAttributeType::DateTime) => Ok((
+            "datetime_attribute",
+            "timestamp",
+            Value::Integer(parse_datetime(from)?.timestamp_millis()),
+            Value::Integer(parse_datetime(to)?.timestamp_millis()),
+        )),
+        _ => Err(Error::ModuleError(
+            "Attribute is not a date field".to_string(),
+        )),
+    }?;
+
+    // Entities of a schema include those of the schemas extending it
+    let subtypes = descendants("SELECT ?1");
+    let mut statement = tx.prepare(&format!(
+        "WITH RECURSIVE {subtypes} SELECT a.entity FROM {table} a INNER JOIN entity e ON a.entity = e.id WHERE e.schema IN (SELECT id FROM descendants) AND a.schema = ?2 AND a.{column} >= ?3 AND a.{column} < ?4 GROUP BY a.entity ORDER BY MIN(a.{column})"
+    ))?;
+
+    let rows = statement.query_map(params![schema, attribute, from, to], |row| row.get(0))?;
+
+    let mut entities = Vec::new();
+    for entity in rows {
+        entities.push(entity?);
+    }
+
+    Ok(entities)
+}
+
+// Gets the entities with a URL attribute pointing at the given host
+pub fn get_linking_to_host(tx: &Transaction, host: &str) -> rusqlite::Result<Vec<EntityId>> {
+    let host = match Host::parse(host) {
+        Ok(host) => Ok(host.to_string()),
+        Err(err) => Err(Error::ModuleError(format!(
+            "Provided value is not a valid host: {err}"
+        ))),
+    }?;
+
+    let mut statement =
+        tx.prepare("SELECT DISTINCT entity FROM url_attribute WHERE host = ?1 ORDER BY entity")?;
+
+    let rows = statement.query_map(params![host], |row| row.get(0))?;
+
+    let mut entities = Vec::new();
+    for entity in rows {
+        entities.push(entity?);
+    }
+
+    Ok(entities)
+}
+
+// Gets the entities of a schema with a value of the given Location attribute
+// inside the bounding box
+pub fn get_in_bounding_box(
+    tx: &Transaction,
+    schema: &EntitySchemaId,
+    attribute: &AttributeSchemaId,
+    bounds: &BoundingBox,
+) -> rusqlite::Result<Vec<EntityId>> {
+    check_coordinates(bounds.south, bounds.west)?;
+    check_coordinates(bounds.north, bounds.east)?;
+
+    let mut entities: Vec<EntityId> = Vec::new();
+    for (entity, ..) in get_location_candidates(tx, schema, attribute, bounds)? {
+        if entities.last() != Some(&entity) {
+            entities.push(entity);
+        }
+    }
+
+    Ok(entities)
+}
+
+// Gets the entities of a schema with a value of the given Location attribute
+// within radius_km of the point, ordered by the distance of their nearest value
+pub fn get_within_radius(
+    tx: &Transaction,
+    schema: &EntitySchemaId,
+    attribute: &AttributeSchemaId,
+    latitude: f64,
+    longitude: f64,
+    radius_km: f64,
+) -> rusqlite::Result<Vec<EntityId>> {
+    check_coordinates(latitude, longitude)?;
+
+    // Narrow down the candidates with the index using a box around the
+    // circle, then check the actual distance of each candidate
+    let delta_latitude = (radius_km / EARTH_RADIUS_KM).to_degrees();
+    let south = latitude - delta_latitude;
+    let north = latitude + delta_latitude;
+
+    let delta_longitude = ((radius_km / EARTH_RADIUS_KM).sin() / latitude.to_radians().cos())
+        .asin()
+        .to_degrees();
+
+    let bounds = if south <= -90.0 || north >= 90.0 || delta_longitude.is_nan() {
+        BoundingBox {
+            south: south.max(-90.0),
+            west: -180.0,
+            north: north.min(90.0),
+            east: 180.0,
+        }
+    } else {
+        BoundingBox {
+            south,
+            west: wrap_longitude(longitude - delta_longitude),
+            north,
+            east: wrap_longitude(longitude + delta_longitude),
+        }
+    };
+
+    let mut entities: Vec<(EntityId, f64)> = Vec::new();
+    for (entity, lat, lon) in get_location_candidates(tx, schema, attribute, &bounds)? {
+        let distance = distance_km(latitude, longitude, lat, lon);
+        if distance > radius_km {
+            continue;
+        }
+
+        match entities.last_mut() {
+            Some((last, nearest)) if last == &entity => *nearest = nearest.min(distance),
+            _ => entities.push((entity, distance)),
+        }
+    }
+
+    entities.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+    Ok(entities.into_iter().map(|(entity, _)| entity).collect())
+}
+
+// Gets every location value inside the bounds, ordered by entity
+fn get_location_candidates(
+    tx: &Transaction,
+    schema: &EntitySchemaId,
+    attribute: &AttributeSchemaId,
+    bounds: &BoundingBox,
+) -> rusqlite::Result<Vec<(EntityId, f64, f64)>> {
+    let attr_schema = RawAttributeSchema::get(tx, attribute)?;
+
+    if attr_schema.attr_type != AttributeType::Simple(SimpleAttributeType::Location) {
+        return Err(Error::ModuleError(
+            "Attribute is not a location field".to_string(),
+        ));
+    }
+
+    let longitude = if bounds.west <= bounds.east {
+        "a.longitude BETWEEN ?5 AND ?6"
+    } else {
+        "(a.longitude >= ?5 OR a.longitude <= ?6)"
+    };
+
+    let subtypes = descendants("SELECT ?1");
+    let mut statement = tx.prepare(&format!(
+        "WITH RECURSIVE {subtypes} SELECT a.entity, a.value, a.longitude FROM location_attribute a INNER JOIN entity e ON a.entity = e.id WHERE e.schema IN (SELECT id FROM descendants) AND a.schema = ?2 AND a.value BETWEEN ?3 AND ?4 AND {longitude} ORDER BY a.entity"
+    ))?;
+
+    let rows = statement.query_map(
+        params![
+            schema,
+            attribute,
+            bounds.south,
+            bounds.north,
+            bounds.west,
+            bounds.east
+        ],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )?;
+
+    let mut candidates = Vec::new();
+    for candidate in rows {
+        candidates.push(candidate?);
+    }
+
+    Ok(candidates)
+}
+
+fn wrap_longitude(longitude: f64) -> f64 {
+    if longitude < -180.0 {
+        longitude + 360.0
+    } else if longitude > 180.0 {
+        longitude - 360.0
+    } else {
+        longitude
+    }
+}
+
+// Great circle distance using the haversine formula
+fn distance_km(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let d_lat = (lat2 - lat1).to_radians();
+    let d_lon = (lon2 - lon1).to_radians();
+
+    let a = (d_lat / 2.0).sin().powi(2)
+        + lat1.to_radians().cos() * lat2.to_radians().cos() * (d_lon / 2.0).sin().powi(2);
+
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::{
+        database::{
+            entity::add_entity,
+            test::test_util::{setup, ASD, ESD},
+        },
+        models::attribute_schema::Quantity,
+    };
+
+    use super::*;
+
+    fn add_location(
+        tx: &Transaction,
+        schema: &EntitySchemaId,
+        attr: &AttributeSchemaId,
+        latitude: f64,
+        longitude: f64,
+    ) -> EntityId {
+        let data = serde_json::from_str(&format!(
+            r#"
+            {{
+              "{attr}": {{ "latitude": {latitude}, "longitude": {longitude} }}
+            }}
+            "#
+        ))
+        .unwrap();
+
+        add_entity(tx, schema, data).unwrap()
+    }
+
+    #[test]
+    fn date_range() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let schema = ESD::create_default(&tx);
+        let attr = ASD::default()
+            .attr_type(SimpleAttributeType::Date)
+            .create(&tx, &schema);
+
+        let mut ids = Vec::new();
+        for date in ["2024-01-31", "2024-02-01", "2024-02-29", "2024-03-01"] {
+            let data = serde_json::from_str(&format!(
+                r#"
+                {{
+                  "{attr}": "{date}"
+                }}
+                "#
+            ))
+            .unwrap();
+
+            ids.push(add_entity(&tx, &schema, data).unwrap());
+        }
+
+        let result = get_in_date_range(&tx, &schema, &attr, "2024-02-01", "2024-03-01").unwrap();
+
+        assert_eq!(result, vec![ids[1].clone(), ids[2].clone()]);
+    }
+
+    #[test]
+    fn datetime_range() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let schema = ESD::create_default(&tx);
+        let attr = ASD::default()
+            .attr_type(SimpleAttributeType::DateTime)
+            .quantity(Quantity::List)
+            .create(&tx, &schema);
+
+        let data = serde_json::from_str(&format!(
+            r#"
+            {{
+              "{attr}": ["2024-01-01T23:30:00-02:00", "2024-01-05T12:00:00Z"]
+            }}
+            "#
+        ))
+        .unwrap();
+
+        let late = add_entity(&tx, &schema, data).unwrap();
+
+        let data = serde_json::from_str(&format!(
+            r#"
+            {{
+              "{attr}": ["2024-01-01T23:30:00+09:00"]
+            }}
+            "#
+        ))
+        .unwrap();
+
+        let early = add_entity(&tx, &schema, data).unwrap();
+
+        // The first value of `late` is 2024-01-02T01:30:00Z, so it is inside the
+        // range even though its local date is January 1st
+        let result = get_in_date_range(
+            &tx,
+            &schema,
+            &attr,
+            "2024-01-01T00:00:00Z",
+            "2024-01-03T00:00:00Z",
+        )
+        .unwrap();
+
+        assert_eq!(result, vec![early, late]);
+    }
+
+    #[test]
+    fn not_a_date() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let schema = ESD::create_default(&tx);
+        let attr = ASD::create_default(&tx, &schema);
+
+        let result = get_in_date_range(&tx, &schema, &attr, "2024-01-01", "2024-02-01");
+
+        assert_eq!(
+            result,
+            Err(Error::ModuleError(
+                "Attribute is not a date field".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn linking_to_host() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let schema = ESD::create_default(&tx);
+        let attr = ASD::default()
+            .attr_type(SimpleAttributeType::Url)
+            .quantity(Quantity::List)
+            .create(&tx, &schema);
+
+        let data = serde_json::from_str(&format!(
+            r#"
+            {{
+              "{attr}": ["https://example.com/a", "http://EXAMPLE.com/b"]
+            }}
+            "#
+        ))
+        .unwrap();
+
+        let linking = add_entity(&tx, &schema, data).unwrap();
+
+        let data = serde_json::from_str(&format!(
+            r#"
+            {{
+              "{attr}": ["https://www.example.com/a"]
+            }}
+            "#
+        ))
+        .unwrap();
+
+        add_entity(&tx, &schema, data).unwrap();
+
+        let result = get_linking_to_host(&tx, "Example.com").unwrap();
+
+        assert_eq!(result, vec![linking]);
+    }
+
+    #[test]
+    fn bounding_box() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let schema = ESD::create_default(&tx);
+        let attr = ASD::default()
+            .attr_type(SimpleAttributeType::Location)
+            .create(&tx, &schema);
+
+        let paris = add_location(&tx, &schema, &attr, 48.8566, 2.3522);
+        let lyon = add_location(&tx, &schema, &attr, 45.764, 4.8357);
+        add_location(&tx, &schema, &attr, 51.5072, -0.1276);
+        let fiji = add_location(&tx, &schema, &attr, -17.7134, 178.065);
+        let samoa = add_location(&tx, &schema, &attr, -13.759, -172.1046);
+
+        let bounds = BoundingBox {
+            south: 42.0,
+            west: -2.0,
+            north: 50.0,
+            east: 8.0,
+        };
+        let result = get_in_bounding_box(&tx, &schema, &attr, &bounds).unwrap();
+        assert_eq!(HashSet::from_iter(result), HashSet::from([paris, lyon]));
+
+        // Crosses the antimeridian
+        let bounds = BoundingBox {
+            south: -20.0,
+            west: 170.0,
+            north: -10.0,
+            east: -170.0,
+        };
+        let result = get_in_bounding_box(&tx, &schema, &attr, &bounds).unwrap();
+        assert_eq!(HashSet::from_iter(result), HashSet::from([fiji, samoa]));
+    }
+
+    #[test]
+    fn within_radius() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let schema = ESD::create_default(&tx);
+        let attr = ASD::default()
+            .attr_type(SimpleAttributeType::Location)
+            .quantity(Quantity::List)
+            .create(&tx, &schema);
+
+        let paris = add_location(&tx, &schema, &attr, 48.8566, 2.3522);
+        let versailles = add_location(&tx, &schema, &attr, 48.8049, 2.1204);
+        add_location(&tx, &schema, &attr, 45.764, 4.8357);
+
+        // Around 17km from Paris, around 2km from Versailles
+        let result = get_within_radius(&tx, &schema, &attr, 48.7904, 2.1375, 20.0).unwrap();
+        assert_eq!(result, vec![versailles.clone(), paris.clone()]);
+
+        let result = get_within_radius(&tx, &schema, &attr, 48.7904, 2.1375, 5.0).unwrap();
+        assert_eq!(result, vec![versailles]);
+    }
+
+    #[test]
+    fn not_a_location() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let schema = ESD::create_default(&tx);
+        let attr = ASD::create_default(&tx, &schema);
+
+        let result = get_within_radius(&tx, &schema, &attr, 0.0, 0.0, 10.0);
+
+        assert_eq!(
+            result,
+            Err(Error::ModuleError(
+                "Attribute is not a location field".to_string()
+            ))
+        );
+    }
+}
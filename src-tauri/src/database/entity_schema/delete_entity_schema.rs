@@ -0,0 +1,378 @@
+use rusqlite::{params, Error, Result, Transaction};
+
+use crate::{
+    database::{attribute_type::ATTRIBUTE_TABLES, Delete},
+    models::entity_schema::{DeleteMode, DeletePreview, EntitySchemaId},
+};
+
+use super::descendants;
+
+// Schemas extending the deleted one go with it
+fn deleted(query: &str) -> String {
+    format!(
+        "WITH RECURSIVE {},
+          deleted_entities(id) AS (SELECT id FROM entity WHERE schema IN (SELECT id FROM descendants)),
+          deleted_attributes(id) AS (SELECT id FROM attribute_schema WHERE entity IN (SELECT id FROM descendants))
+          {query}",
+        descendants("SELECT ?1")
+    )
+}
+
+// Links held by surviving entities to entities being deleted
+const INCOMING: &str = "FROM reference_attribute
+  WHERE value IN (SELECT id FROM deleted_entities) AND entity NOT IN (SELECT id FROM deleted_entities)";
+
+// References elsewhere that can only point at the deleted schemas
+const ORPHANED: &str = "SELECT a.id FROM attribute_schema a
+  WHERE a.id NOT IN (SELECT id FROM deleted_attributes)
+  AND EXISTS (SELECT 1 FROM reference_target t WHERE t.schema = a.id AND t.target IN (SELECT id FROM descendants))
+  AND NOT EXISTS (SELECT 1 FROM reference_target t WHERE t.schema = a.id AND t.target NOT IN (SELECT id FROM descendants))";
+
+// Surviving entities whose links to deleted entities are required, or whose
+// lists would drop below their minimum. Links in references that are removed
+// with the schema are not counted
+const BLOCKING: &str = "SELECT COUNT(DISTINCT r.entity) FROM reference_attribute r
+  INNER JOIN attribute_schema a ON a.id = r.schema
+  WHERE r.value IN (SELECT id FROM deleted_entities) AND r.entity NOT IN (SELECT id FROM deleted_entities)
+  AND a.id NOT IN (SELECT id FROM deleted_attributes)
+  AND a.id NOT IN (ORPHANED)
+  AND (a.quantity = 'Required' OR (
+    SELECT COUNT(*) FROM reference_attribute k
+      WHERE k.entity = r.entity AND k.schema = r.schema AND k.value NOT IN (SELECT id FROM deleted_entities)
+  ) < json_extract(a.constraints, '$.min_items'))";
+
+impl EntitySchemaId {
+    pub fn delete_preview(&self, tx: &Transaction) -> Result<DeletePreview> {
+        let mut statement = tx.prepare(&deleted(
+            "SELECT name FROM entity_schema WHERE id IN (SELECT id FROM descendants) AND id != ?1 ORDER BY name",
+        ))?;
+        let schemas = statement
+            .query_map(params![self], |row| row.get(0))?
+            .collect::<Result<_>>()?;
+
+        let entities = tx.query_row(
+            &deleted("SELECT COUNT(*) FROM deleted_entities"),
+            params![self],
+            |row| row.get(0),
+        )?;
+
+        let mut values = 0;
+        for table in ATTRIBUTE_TABLES {
+            values += tx.query_row(
+                &deleted(&format!(
+                    "SELECT COUNT(*) FROM {table} WHERE entity IN (SELECT id FROM deleted_entities)"
+                )),
+                params![self],
+                |row| row.get::<_, usize>(0),
+            )?;
+        }
+
+        let references = tx.query_row(
+            &deleted(&format!("SELECT COUNT(*) {INCOMING}")),
+            params![self],
+            |row| row.get(0),
+        )?;
+
+        let blocking = tx.query_row(
+            &deleted(&BLOCKING.replace("ORPHANED", ORPHANED)),
+            params![self],
+            |row| row.get(0),
+        )?;
+
+        // Rollups and backlinks built on a removed attribute go with it
+        let mut statement = tx.prepare(&deleted(&format!(
+            "SELECT name FROM attribute_schema
+              WHERE id IN ({ORPHANED})
+              OR (id NOT IN (SELECT id FROM deleted_attributes) AND (
+                rollup_reference IN (SELECT id FROM deleted_attributes UNION {ORPHANED})
+                OR rollup_attribute IN (SELECT id FROM deleted_attributes)
+                OR backlink_reference IN (SELECT id FROM deleted_attributes UNION {ORPHANED})
+              ))
+              ORDER BY name"
+        )))?;
+        let dependents = statement
+            .query_map(params![self], |row| row.get(0))?
+            .collect::<Result<_>>()?;
+
+        Ok(DeletePreview {
+            schemas,
+            entities,
+            values,
+            references,
+            blocking,
+            dependents,
+        })
+    }
+
+    // Deletes the schema along with the schemas extending it. Reference
+    // attributes elsewhere lose the deleted schemas as targets, and are
+    // removed when no other target is left
+    pub fn delete_with(self, tx: &Transaction, mode: DeleteMode) -> Result<()> {
+        let preview = self.delete_preview(tx)?;
+
+        if mode == DeleteMode::Restrict && preview.entities > 0 {
+            return Err(Error::ModuleError(format!(
+                "Schema has {} entities",
+                preview.entities
+            )));
+        }
+
+        if preview.blocking > 0 {
+            return Err(Error::ModuleError(format!(
+                "{} entities need their links to the deleted entities",
+                preview.blocking
+            )));
+        }
+
+        // Links in references removed with the schema may be required, so the
+        // required and list bound triggers are suspended while they go
+        tx.execute(
+            &deleted(&format!(
+                "INSERT INTO attribute_migration (schema) {ORPHANED}"
+            )),
+            params![self],
+        )?;
+        tx.execute(&deleted(&format!("DELETE {INCOMING}")), params![self])?;
+        tx.execute(
+            &deleted(&format!(
+                "DELETE FROM attribute_migration WHERE schema IN ({ORPHANED})"
+            )),
+            params![self],
+        )?;
+
+        // Entities and the values they hold go in one statement, so links
+        // between deleted entities are gone before the foreign keys are checked
+        tx.execute(
+            &deleted("DELETE FROM entity WHERE id IN (SELECT id FROM deleted_entities)"),
+            params![self],
+        )?;
+
+        tx.execute(
+            &deleted(&format!(
+                "DELETE FROM attribute_schema WHERE id IN ({ORPHANED})"
+            )),
+            params![self],
+        )?;
+        tx.execute(
+            &deleted("DELETE FROM reference_target WHERE target IN (SELECT id FROM descendants)"),
+            params![self],
+        )?;
+
+        let result = tx.execute(
+            &deleted("DELETE FROM entity_schema WHERE id IN (SELECT id FROM descendants)"),
+            params![self],
+        )?;
+
+        if result == 0 {
+            Err(Error::QueryReturnedNoRows)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Delete for EntitySchemaId {
+    fn delete(self, tx: &Transaction) -> Result<()> {
+        self.delete_with(tx, DeleteMode::Restrict)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::{params, Error};
+    use serde_json::json;
+
+    use crate::{
+        database::{
+            entity::add_entity,
+            test::test_util::{setup, ASD, ESD, RSD},
+            Delete, New,
+        },
+        models::{
+            attribute_schema::{
+                AttributeSchema, AttributeSchemaId, Constraints, CreateAttributeSchema, Quantity,
+            },
+            attribute_type::{CreateAttributeType, CreateReferenceAttribute},
+            entity_schema::{DeleteMode, DeletePreview, EntitySchemaId},
+        },
+    };
+
+    #[test]
+    fn delete_modes() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let person = ESD::default().name("Person").create(&tx);
+        let author = ESD::default().name("Author").parent(&person).create(&tx);
+        let name = ASD::default().name("Name").create(&tx, &person);
+
+        let book = ESD::default().name("Book").create(&tx);
+        let writer = RSD::default()
+            .name("Writer")
+            .quantity(Quantity::Optional)
+            .create(&tx, &book, &person);
+
+        add_entity(&tx, &person, json!({ name.to_string(): "Ann" })).unwrap();
+        let bob = add_entity(&tx, &author, json!({ name.to_string(): "Bob" })).unwrap();
+        let novel = add_entity(&tx, &book, json!({ writer.to_string(): bob.to_string() })).unwrap();
+
+        assert_eq!(
+            person.delete_preview(&tx),
+            Ok(DeletePreview {
+                schemas: vec!["Author".to_string()],
+                entities: 2,
+                values: 2,
+                references: 1,
+                blocking: 0,
+                dependents: vec!["Writer".to_string()],
+            })
+        );
+
+        assert_eq!(
+            person.clone().delete(&tx),
+            Err(Error::ModuleError("Schema has 2 entities".to_string()))
+        );
+
+        person
+            .clone()
+            .delete_with(&tx, DeleteMode::Cascade)
+            .unwrap();
+
+        let count = |sql: &str| -> usize { tx.query_row(sql, [], |row| row.get(0)).unwrap() };
+        assert_eq!(count("SELECT COUNT(*) FROM entity_schema"), 1);
+        assert_eq!(count("SELECT COUNT(*) FROM attribute_schema"), 0);
+        assert_eq!(count("SELECT COUNT(*) FROM entity"), 1);
+        assert_eq!(count("SELECT COUNT(*) FROM text_attribute"), 0);
+        assert!(tx
+            .prepare("SELECT 1 FROM entity WHERE id = ?1")
+            .unwrap()
+            .exists(params![novel])
+            .unwrap());
+    }
+
+    fn create_reference(
+        tx: &rusqlite::Transaction,
+        owner: &EntitySchemaId,
+        targets: Vec<EntitySchemaId>,
+        quantity: Quantity,
+        min_items: Option<usize>,
+    ) -> AttributeSchemaId {
+        AttributeSchema::new(
+            tx,
+            CreateAttributeSchema {
+                entity: owner.clone(),
+                name: format!("Link {}", targets.len()),
+                quantity,
+                constraints: Constraints {
+                    min_items,
+                    ..Default::default()
+                },
+                default: None,
+                unique: false,
+                attr_type: CreateAttributeType::Reference(CreateReferenceAttribute {
+                    targets,
+                    inverse: None,
+                    edges: Vec::new(),
+                }),
+            },
+        )
+        .unwrap()
+        .id
+    }
+
+    // Surviving entities cannot lose a required link, but references that
+    // only point at the deleted schema go with it whatever their quantity
+    #[test]
+    fn required_references() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let person = ESD::default().name("Person").create(&tx);
+        let place = ESD::default().name("Place").create(&tx);
+        let note = ESD::default().name("Note").create(&tx);
+        let book = ESD::default().name("Book").create(&tx);
+
+        let subject = create_reference(
+            &tx,
+            &note,
+            vec![person.clone(), place.clone()],
+            Quantity::Required,
+            None,
+        );
+        let writer = create_reference(&tx, &book, vec![person.clone()], Quantity::Required, None);
+
+        let ann = add_entity(&tx, &person, json!({})).unwrap();
+        add_entity(&tx, &note, json!({ subject.to_string(): ann.to_string() })).unwrap();
+        add_entity(&tx, &book, json!({ writer.to_string(): ann.to_string() })).unwrap();
+
+        assert_eq!(person.delete_preview(&tx).unwrap().blocking, 1);
+        assert_eq!(
+            person.clone().delete_with(&tx, DeleteMode::Cascade),
+            Err(Error::ModuleError(
+                "1 entities need their links to the deleted entities".to_string()
+            ))
+        );
+
+        // Once the note links elsewhere the writer reference goes with the schema
+        let paris = add_entity(&tx, &place, json!({})).unwrap();
+        tx.execute(
+            "UPDATE reference_attribute SET value = ?1 WHERE schema = ?2",
+            params![paris, subject],
+        )
+        .unwrap();
+
+        assert_eq!(person.delete_preview(&tx).unwrap().blocking, 0);
+        person.delete_with(&tx, DeleteMode::Cascade).unwrap();
+
+        let count = |sql: &str| -> usize { tx.query_row(sql, [], |row| row.get(0)).unwrap() };
+        assert_eq!(count("SELECT COUNT(*) FROM entity"), 3);
+        assert_eq!(count("SELECT COUNT(*) FROM reference_attribute"), 1);
+    }
+
+    #[test]
+    fn min_items_references() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let person = ESD::default().name("Person").create(&tx);
+        let place = ESD::default().name("Place").create(&tx);
+        let note = ESD::default().name("Note").create(&tx);
+        let subjects = create_reference(
+            &tx,
+            &note,
+            vec![person.clone(), place.clone()],
+            Quantity::List,
+            Some(1),
+        );
+
+        let ann = add_entity(&tx, &person, json!({})).unwrap();
+        let paris = add_entity(&tx, &place, json!({})).unwrap();
+        let links = |targets: Vec<String>| json!({ subjects.to_string(): targets });
+        add_entity(&tx, &note, links(vec![ann.to_string(), paris.to_string()])).unwrap();
+        let lonely = add_entity(&tx, &note, links(vec![ann.to_string()])).unwrap();
+
+        assert_eq!(person.delete_preview(&tx).unwrap().blocking, 1);
+        assert!(person
+            .clone()
+            .delete_with(&tx, DeleteMode::Cascade)
+            .is_err());
+
+        lonely.delete(&tx).unwrap();
+        person.delete_with(&tx, DeleteMode::Cascade).unwrap();
+
+        let count = |sql: &str| -> usize { tx.query_row(sql, [], |row| row.get(0)).unwrap() };
+        assert_eq!(count("SELECT COUNT(*) FROM reference_attribute"), 1);
+    }
+
+    #[test]
+    fn delete_empty_schema() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let es = ESD::create_default(&tx);
+        ASD::default().create(&tx, &es);
+
+        es.clone().delete(&tx).unwrap();
+        assert_eq!(es.delete(&tx), Err(Error::QueryReturnedNoRows));
+    }
+}
@@ -0,0 +1,403 @@
+use std::collections::HashMap;
+
+use rusqlite::{params, params_from_iter, types::ValueRef, Error, Result, ToSql, Transaction};
+
+use crate::{
+    database::{
+        attribute_schema::{GetSchemaMap, RawAttributeSchema},
+        attribute_type::value_table,
+    },
+    models::{
+        attribute_schema::AttributeSchemaId,
+        attribute_type::AttributeType,
+        entity::EntityId,
+        entity_schema::{EntityDisplay, EntitySchemaId},
+    },
+    utils::{get_timestamp, placeholders},
+};
+
+// Templates are stored with attribute ids in the braces so renaming an
+// attribute keeps the display working
+enum Segment {
+    Text(String),
+    Field(String),
+}
+
+fn parse(template: &str) -> Result<Vec<Segment>> {
+    let mut segments = Vec::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}') else {
+            return Err(Error::ModuleError(
+                "Unclosed placeholder in display template".to_string(),
+            ));
+        };
+
+        if start > 0 {
+            segments.push(Segment::Text(rest[..start].to_string()));
+        }
+        segments.push(Segment::Field(rest[start + 1..start + end].to_string()));
+        rest = &rest[start + end + 1..];
+    }
+
+    if !rest.is_empty() {
+        segments.push(Segment::Text(rest.to_string()));
+    }
+
+    Ok(segments)
+}
+
+fn join(segments: &[Segment], field: impl Fn(&str) -> String) -> String {
+    segments
+        .iter()
+        .map(|segment| match segment {
+            Segment::Text(text) => text.clone(),
+            Segment::Field(name) => format!("{{{}}}", field(name)),
+        })
+        .collect()
+}
+
+// Only values with a plain text form can be shown in a label
+fn displayable(attr_type: &AttributeType) -> bool {
+    matches!(
+        value_table(attr_type),
+        Some(
+            "text_attribute"
+                | "integer_attribute"
+                | "number_attribute"
+                | "date_attribute"
+                | "datetime_attribute"
+                | "url_attribute"
+                | "select_attribute"
+        )
+    )
+}
+
+fn stored(tx: &Transaction, schema: &EntitySchemaId) -> Result<Option<String>> {
+    tx.query_row(
+        "SELECT display FROM entity_schema WHERE id = ?1",
+        params![schema],
+        |row| row.get(0),
+    )
+}
+
+impl EntitySchemaId {
+    pub fn set_display(&self, tx: &Transaction, display: Option<EntityDisplay>) -> Result<()> {
+        let template = match display {
+            Some(EntityDisplay::Attribute(name)) => Some(format!("{{{name}}}")),
+            Some(EntityDisplay::Template(template)) => Some(template),
+            None => None,
+        };
+
+        let template = match template {
+            Some(template) => {
+                let map = RawAttributeSchema::get_map(tx, self)?;
+                let segments = parse(&template)?;
+
+                let mut ids = HashMap::new();
+                for segment in &segments {
+                    let Segment::Field(name) = segment else {
+                        continue;
+                    };
+
+                    let attribute = map
+                        .values()
+                        .find(|attribute| &attribute.name == name)
+                        .ok_or_else(|| Error::ModuleError(format!("Unknown attribute {name}")))?;
+
+                    if !displayable(&attribute.attr_type) {
+                        return Err(Error::ModuleError(format!(
+                            "Attribute {name} cannot be displayed"
+                        )));
+                    }
+
+                    ids.insert(name.clone(), attribute.id.to_string());
+                }
+
+                if ids.is_empty() {
+                    return Err(Error::ModuleError(
+                        "Display template must name an attribute".to_string(),
+                    ));
+                }
+
+                Some(join(&segments, |name| ids[name].clone()))
+            }
+            None => None,
+        };
+
+        tx.execute(
+            "UPDATE entity_schema SET display = ?1, updated = ?2 WHERE id = ?3",
+            params![template, get_timestamp(), self],
+        )?;
+
+        Ok(())
+    }
+
+    // The display set on the schema itself, with attributes named
+    pub fn display(&self, tx: &Transaction) -> Result<Option<EntityDisplay>> {
+        let Some(template) = stored(tx, self)? else {
+            return Ok(None);
+        };
+
+        let map = RawAttributeSchema::get_map(tx, self)?;
+        let segments = parse(&template)?;
+        let named = join(&segments, |id| {
+            AttributeSchemaId::try_from(id)
+                .ok()
+                .and_then(|id| map.get(&id))
+                .map_or(id.to_string(), |attribute| attribute.name.clone())
+        });
+
+        Ok(match segments.as_slice() {
+            [Segment::Field(..)] => Some(EntityDisplay::Attribute(
+                named[1..named.len() - 1].to_string(),
+            )),
+            _ => Some(EntityDisplay::Template(named)),
+        })
+    }
+
+    // The stored template of the nearest schema in the hierarchy that has one
+    fn inherited_display(&self, tx: &Transaction) -> Result<Option<Vec<Segment>>> {
+        for schema in self.ancestors(tx)? {
+            if let Some(template) = stored(tx, &schema)? {
+                return parse(&template).map(Some);
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+// Reads the text of an attribute for each of the entities, joining list items
+fn read_text(
+    tx: &Transaction,
+    attribute: &RawAttributeSchema,
+    entities: &[&EntityId],
+) -> Result<HashMap<EntityId, String>> {
+    let Some(table) =
+        value_table(&attribute.attr_type).filter(|_| displayable(&attribute.attr_type))
+    else {
+        return Ok(HashMap::new());
+    };
+
+    let mut statement = tx.prepare(&format!(
+        "SELECT entity, value FROM {table} WHERE schema = ? AND entity IN ({})
+          ORDER BY entity, position",
+        placeholders(entities.len())
+    ))?;
+
+    let mut params: Vec<&dyn ToSql> = vec![&attribute.id];
+    params.extend(entities.iter().map(|entity| *entity as &dyn ToSql));
+    let mut rows = statement.query(params_from_iter(params))?;
+
+    let mut values: HashMap<EntityId, Vec<String>> = HashMap::new();
+    while let Some(row) = rows.next()? {
+        let text = match row.get_ref(1)? {
+            ValueRef::Integer(value) => value.to_string(),
+            ValueRef::Real(value) => value.to_string(),
+            ValueRef::Text(..) => row.get(1)?,
+            _ => continue,
+        };
+        let text = match &attribute.attr_type {
+            AttributeType::Measure(measure) => format!("{text} {}", measure.unit),
+            _ => text,
+        };
+
+        values.entry(row.get(0)?).or_default().push(text);
+    }
+
+    Ok(values
+        .into_iter()
+        .map(|(entity, values)| (entity, values.join(", ")))
+        .collect())
+}
+
+// Labels for entities of any schemas, read with one query per schema and
+// attribute rather than per entity. Unknown entities, those whose schemas have
+// no display and those whose displayed fields are all empty are left out
+pub fn display_names(tx: &Transaction, entities: &[EntityId]) -> Result<HashMap<EntityId, String>> {
+    let mut statement = tx.prepare(&format!(
+        "SELECT id, schema FROM entity WHERE id IN ({})",
+        placeholders(entities.len())
+    ))?;
+    let mut rows = statement.query(params_from_iter(entities))?;
+
+    let mut by_schema: HashMap<EntitySchemaId, Vec<EntityId>> = HashMap::new();
+    while let Some(row) = rows.next()? {
+        by_schema.entry(row.get(1)?).or_default().push(row.get(0)?);
+    }
+
+    let mut names = HashMap::new();
+    for (schema, entities) in by_schema {
+        let Some(segments) = schema.inherited_display(tx)? else {
+            continue;
+        };
+
+        let map = RawAttributeSchema::get_map(tx, &schema)?;
+        let entities: Vec<&EntityId> = entities.iter().collect();
+
+        let mut fields = HashMap::new();
+        for segment in &segments {
+            let Segment::Field(id) = segment else {
+                continue;
+            };
+            let Some(attribute) = AttributeSchemaId::try_from(id.as_str())
+                .ok()
+                .and_then(|id| map.get(&id))
+            else {
+                continue;
+            };
+
+            fields.insert(id.clone(), read_text(tx, attribute, &entities)?);
+        }
+
+        for entity in entities {
+            let mut filled = false;
+            let name: String = segments
+                .iter()
+                .map(|segment| match segment {
+                    Segment::Text(text) => text.as_str(),
+                    Segment::Field(id) => {
+                        match fields.get(id).and_then(|values| values.get(entity)) {
+                            Some(value) => {
+                                filled = true;
+                                value.as_str()
+                            }
+                            None => "",
+                        }
+                    }
+                })
+                .collect();
+
+            if filled {
+                names.insert(entity.clone(), name);
+            }
+        }
+    }
+
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{
+        database::{
+            entity::add_entity,
+            test::test_util::{setup, ASD, ESD, RSD},
+            Delete, Get,
+        },
+        models::{attribute_schema::Quantity, entity_schema::EntitySchema},
+    };
+
+    use super::*;
+
+    #[test]
+    fn display_names() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let person = ESD::default().name("Person").create(&tx);
+        let author = ESD::default().name("Author").parent(&person).create(&tx);
+        let book = ESD::default().name("Book").create(&tx);
+        let first = ASD::default()
+            .name("first")
+            .quantity(Quantity::Optional)
+            .create(&tx, &person);
+        let last = ASD::default()
+            .name("last")
+            .quantity(Quantity::Optional)
+            .create(&tx, &person);
+        let title = ASD::default().name("title").create(&tx, &book);
+
+        person
+            .set_display(
+                &tx,
+                Some(EntityDisplay::Template("{last}, {first}".to_string())),
+            )
+            .unwrap();
+        book.set_display(&tx, Some(EntityDisplay::Attribute("title".to_string())))
+            .unwrap();
+
+        assert_eq!(
+            EntitySchema::get(&tx, &book).unwrap().display,
+            Some(EntityDisplay::Attribute("title".to_string()))
+        );
+        assert_eq!(author.display(&tx).unwrap(), None);
+
+        let alice = add_entity(
+            &tx,
+            &person,
+            json!({ first.to_string(): "Alice", last.to_string(): "Liddell" }),
+        )
+        .unwrap();
+        let melville = add_entity(
+            &tx,
+            &author,
+            json!({ first.to_string(): "Herman", last.to_string(): "Melville" }),
+        )
+        .unwrap();
+        let nameless = add_entity(&tx, &person, json!({})).unwrap();
+        let moby_dick = add_entity(&tx, &book, json!({ title.to_string(): "Moby Dick" })).unwrap();
+
+        let names = super::display_names(
+            &tx,
+            &[
+                alice.clone(),
+                melville.clone(),
+                nameless,
+                moby_dick.clone(),
+                EntityId::new(),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(
+            names,
+            HashMap::from([
+                (alice, "Liddell, Alice".to_string()),
+                (melville, "Melville, Herman".to_string()),
+                (moby_dick, "Moby Dick".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn invalid_displays() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let book = ESD::default().name("Book").create(&tx);
+        let subtitle = ASD::default()
+            .name("subtitle")
+            .quantity(Quantity::Optional)
+            .create(&tx, &book);
+        RSD::default().name("sequel").create(&tx, &book, &book);
+
+        let set = |template: &str| {
+            book.set_display(&tx, Some(EntityDisplay::Template(template.to_string())))
+        };
+        let error = |message: &str| Err(Error::ModuleError(message.to_string()));
+
+        assert_eq!(set("{title}"), error("Unknown attribute title"));
+        assert_eq!(
+            set("{sequel}"),
+            error("Attribute sequel cannot be displayed")
+        );
+        assert_eq!(
+            set("Untitled"),
+            error("Display template must name an attribute")
+        );
+        assert_eq!(
+            set("{subtitle"),
+            error("Unclosed placeholder in display template")
+        );
+
+        // Deleting a displayed attribute removes the display
+        set("Book: {subtitle}").unwrap();
+        subtitle.delete(&tx).unwrap();
+        assert_eq!(book.display(&tx).unwrap(), None);
+    }
+}
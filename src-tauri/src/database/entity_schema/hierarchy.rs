@@ -0,0 +1,696 @@
+use std::collections::HashSet;
+
+use rusqlite::{params, Error, OptionalExtension, Result, Transaction};
+
+use crate::{
+    database::{
+        attribute_schema::{check_computed, GetSchemaMap, RawAttributeSchema, SchemaMap},
+        attribute_type::value_table,
+        Get,
+    },
+    models::{
+        attribute_schema::AttributeSchemaId,
+        attribute_type::AttributeType,
+        entity::EntityId,
+        entity_schema::{DeleteMode, EntitySchemaId, ParentPreview},
+    },
+    utils::get_timestamp,
+};
+
+// Recursive CTEs for use after WITH RECURSIVE, listing the starting schema
+// with the schemas it extends, or with the schemas extending it. UNION rather
+// than UNION ALL stops at any cycle
+pub fn ancestors(start: &str) -> String {
+    format!(
+        "ancestors(id) AS (
+          {start}
+          UNION SELECT s.parent FROM entity_schema s INNER JOIN ancestors a ON s.id = a.id WHERE s.parent IS NOT NULL
+        )"
+    )
+}
+
+pub fn descendants(start: &str) -> String {
+    format!(
+        "descendants(id) AS (
+          {start}
+          UNION SELECT s.id FROM entity_schema s INNER JOIN descendants d ON s.parent = d.id
+        )"
+    )
+}
+
+fn name_clash(owner: Option<(String, String)>) -> Result<()> {
+    match owner {
+        Some((name, owner)) => Err(Error::ModuleError(format!(
+            "Attribute {name} is already defined by {owner} in the same hierarchy"
+        ))),
+        None => Ok(()),
+    }
+}
+
+fn dependent_error(name: &str, err: Error) -> Error {
+    match err {
+        Error::ModuleError(message) => Error::ModuleError(format!(
+            "{name} relies on an attribute no longer inherited: {message}"
+        )),
+        err => err,
+    }
+}
+
+impl EntitySchemaId {
+    // The schema itself followed by the schemas it inherits from
+    pub fn ancestors(&self, tx: &Transaction) -> Result<Vec<EntitySchemaId>> {
+        let mut statement = tx.prepare(&format!(
+            "WITH RECURSIVE {} SELECT id FROM ancestors",
+            ancestors("SELECT ?1")
+        ))?;
+        let rows = statement.query_map(params![self], |row| row.get(0))?;
+        rows.collect()
+    }
+
+    // The schema itself followed by every schema extending it
+    pub fn descendants(&self, tx: &Transaction) -> Result<Vec<EntitySchemaId>> {
+        let mut statement = tx.prepare(&format!(
+            "WITH RECURSIVE {} SELECT id FROM descendants",
+            descendants("SELECT ?1")
+        ))?;
+        let rows = statement.query_map(params![self], |row| row.get(0))?;
+        rows.collect()
+    }
+
+    // Entities of the schema and of every schema extending it
+    pub fn entities(&self, tx: &Transaction) -> Result<Vec<EntityId>> {
+        let mut statement = tx.prepare(&format!(
+            "WITH RECURSIVE {} SELECT id FROM entity WHERE schema IN (SELECT id FROM descendants)",
+            descendants("SELECT ?1")
+        ))?;
+        let rows = statement.query_map(params![self], |row| row.get(0))?;
+        rows.collect()
+    }
+
+    // Checks no schema above or below this one has an attribute with the
+    // name, which the new attribute would override or be overridden by
+    pub fn check_attribute_name(&self, tx: &Transaction, name: &str) -> Result<()> {
+        let owner = tx
+            .query_row(
+                &format!(
+                    "WITH RECURSIVE {}, {}
+                      SELECT a.name, s.name FROM attribute_schema a INNER JOIN entity_schema s ON a.entity = s.id
+                      WHERE a.name = ?2 AND a.entity != ?1
+                      AND (a.entity IN (SELECT id FROM ancestors) OR a.entity IN (SELECT id FROM descendants))",
+                    ancestors("SELECT ?1"),
+                    descendants("SELECT ?1"),
+                ),
+                params![self, name],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+
+        name_clash(owner)
+    }
+
+    // Attributes inherited now that would not be under the new parent
+    fn lost_attributes(
+        &self,
+        tx: &Transaction,
+        parent: Option<&EntitySchemaId>,
+    ) -> Result<Vec<RawAttributeSchema>> {
+        let current: Option<EntitySchemaId> = tx.query_row(
+            "SELECT parent FROM entity_schema WHERE id = ?1",
+            params![self],
+            |row| row.get(0),
+        )?;
+
+        let inherited = |parent: Option<&EntitySchemaId>| match parent {
+            Some(parent) => RawAttributeSchema::get_map(tx, parent),
+            None => Ok(Default::default()),
+        };
+        let kept = inherited(parent)?;
+
+        let mut lost: Vec<_> = inherited(current.as_ref())?
+            .into_values()
+            .filter(|attribute| !kept.contains_key(&attribute.id))
+            .collect();
+        lost.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(lost)
+    }
+
+    pub fn parent_preview(
+        &self,
+        tx: &Transaction,
+        parent: Option<&EntitySchemaId>,
+    ) -> Result<ParentPreview> {
+        let lost = self.lost_attributes(tx, parent)?;
+
+        let mut holders = HashSet::new();
+        let mut values = 0;
+        for attribute in &lost {
+            let Some(table) = value_table(&attribute.attr_type) else {
+                continue;
+            };
+
+            let mut statement = tx.prepare(&format!(
+                "WITH RECURSIVE {} SELECT entity FROM {table}
+                  WHERE schema = ?2 AND entity IN (SELECT id FROM entity WHERE schema IN (SELECT id FROM descendants))",
+                descendants("SELECT ?1")
+            ))?;
+            let rows = statement
+                .query_map(params![self, attribute.id], |row| row.get::<_, EntityId>(0))?;
+
+            for entity in rows {
+                holders.insert(entity?);
+                values += 1;
+            }
+        }
+
+        Ok(ParentPreview {
+            attributes: lost.into_iter().map(|attribute| attribute.name).collect(),
+            entities: holders.len(),
+            values,
+        })
+    }
+
+    // Moves the schema under a new parent, or to the top level with None.
+    // Entities of the schema are backfilled with the defaults of any required
+    // attributes, or lists with a minimum, they newly inherit. Values of the
+    // attributes no longer inherited stop the move under Restrict, and are
+    // deleted under Cascade. Fields and displays still relying on them always
+    // stop it
+    pub fn set_parent(
+        &self,
+        tx: &Transaction,
+        parent: Option<&EntitySchemaId>,
+        mode: DeleteMode,
+    ) -> Result<()> {
+        let inherited = RawAttributeSchema::get_map(tx, self)?;
+
+        if let Some(parent) = parent {
+            if parent.ancestors(tx)?.contains(self) {
+                return Err(Error::ModuleError(
+                    "Schema cannot extend itself or one of its descendants".to_string(),
+                ));
+            }
+
+            let owner = tx
+                .query_row(
+                    &format!(
+                        "WITH RECURSIVE {}, {}
+                          SELECT a.name, s.name FROM attribute_schema a INNER JOIN entity_schema s ON a.entity = s.id
+                          WHERE a.entity IN (SELECT id FROM ancestors)
+                          AND a.name IN (SELECT b.name FROM attribute_schema b WHERE b.entity IN (SELECT id FROM descendants))",
+                        ancestors("SELECT ?1"),
+                        descendants("SELECT ?2"),
+                    ),
+                    params![parent, self],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .optional()?;
+
+            name_clash(owner)?;
+        }
+
+        let preview = self.parent_preview(tx, parent)?;
+        if mode == DeleteMode::Restrict && preview.values > 0 {
+            return Err(Error::ModuleError(format!(
+                "{} entities hold values of attributes no longer inherited",
+                preview.entities
+            )));
+        }
+
+        // Dependents are checked against the new hierarchy, so a failure
+        // takes the schema back to its old parent
+        tx.execute_batch("SAVEPOINT parent_change")?;
+
+        let result = self.reparent(tx, parent, inherited);
+
+        if result.is_err() {
+            tx.execute_batch("ROLLBACK TO parent_change")?;
+        }
+        tx.execute_batch("RELEASE parent_change")?;
+        result
+    }
+
+    fn reparent(
+        &self,
+        tx: &Transaction,
+        parent: Option<&EntitySchemaId>,
+        inherited: SchemaMap,
+    ) -> Result<()> {
+        let lost = self.lost_attributes(tx, parent)?;
+
+        tx.execute(
+            "UPDATE entity_schema SET parent = ?1, updated = ?2 WHERE id = ?3",
+            params![parent, get_timestamp(), self],
+        )?;
+
+        self.check_dependents(tx, &lost)?;
+
+        // Required values and list minimums no longer apply once the attribute
+        // is not inherited, so their triggers are suspended while they go
+        for attribute in lost {
+            let Some(table) = value_table(&attribute.attr_type) else {
+                continue;
+            };
+
+            tx.execute(
+                "INSERT INTO attribute_migration (schema) VALUES (?1)",
+                params![attribute.id],
+            )?;
+            tx.execute(
+                &format!(
+                    "WITH RECURSIVE {} DELETE FROM {table}
+                      WHERE schema = ?2 AND entity IN (SELECT id FROM entity WHERE schema IN (SELECT id FROM descendants))",
+                    descendants("SELECT ?1")
+                ),
+                params![self, attribute.id],
+            )?;
+            tx.execute(
+                "DELETE FROM attribute_migration WHERE schema = ?1",
+                params![attribute.id],
+            )?;
+        }
+
+        let previous: HashSet<_> = inherited.into_keys().collect();
+        let entities = self.entities(tx)?;
+
+        for (id, attribute) in RawAttributeSchema::get_map(tx, self)? {
+            if attribute.needs_value() && !previous.contains(&id) {
+                attribute.backfill(tx, &entities)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Computed fields, rollups, backlinks and displays of the moved schemas,
+    // and rollups elsewhere aggregating a lost attribute, must still hold
+    fn check_dependents(&self, tx: &Transaction, lost: &[RawAttributeSchema]) -> Result<()> {
+        check_computed(tx, self)?;
+
+        for schema in self.descendants(tx)? {
+            for attribute in RawAttributeSchema::get_map(tx, &schema)?.values() {
+                match &attribute.attr_type {
+                    AttributeType::Rollup(rollup) => rollup.check(tx, &schema),
+                    AttributeType::Backlink(backlink) => backlink.check(tx, &schema),
+                    _ => Ok(()),
+                }
+                .map_err(|err| dependent_error(&attribute.name, err))?;
+            }
+        }
+
+        for attribute in lost {
+            let mut statement =
+                tx.prepare("SELECT id, entity FROM attribute_schema WHERE rollup_attribute = ?1")?;
+            let rollups = statement
+                .query_map(params![attribute.id], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect::<Result<Vec<(AttributeSchemaId, EntitySchemaId)>>>()?;
+
+            for (id, owner) in rollups {
+                let rollup = RawAttributeSchema::get(tx, &id)?;
+                if let AttributeType::Rollup(inner) = &rollup.attr_type {
+                    inner
+                        .check(tx, &owner)
+                        .map_err(|err| dependent_error(&rollup.name, err))?;
+                }
+            }
+
+            let display: Option<String> = tx
+                .query_row(
+                    &format!(
+                        "WITH RECURSIVE {} SELECT name FROM entity_schema
+                          WHERE id IN (SELECT id FROM descendants) AND instr(display, ?2) > 0",
+                        descendants("SELECT ?1")
+                    ),
+                    params![self, attribute.id.to_string()],
+                    |row| row.get(0),
+                )
+                .optional()?;
+
+            if let Some(schema) = display {
+                return Err(Error::ModuleError(format!(
+                    "Display of {schema} uses {}, which is no longer inherited",
+                    attribute.name
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::{Error, Transaction};
+    use serde_json::json;
+
+    use crate::{
+        database::{
+            attribute_schema::{GetSchemaMap, RawAttributeSchema},
+            entity::{add_entity, get_in_date_range},
+            test::test_util::{setup, ASD, ESD, RSD},
+            Get, New,
+        },
+        models::{
+            attribute_schema::{
+                AttributeSchema, Constraints, CreateAttributeSchema, DefaultValue, Quantity,
+            },
+            attribute_type::{
+                Aggregate, ComputedAttribute, CreateAttributeType, RollupAttribute,
+                SimpleAttributeType,
+            },
+            entity_schema::{
+                DeleteMode, EntityDisplay, EntitySchema, EntitySchemaId, ParentPreview,
+            },
+        },
+    };
+
+    fn create_derived(
+        tx: &Transaction,
+        schema: &EntitySchemaId,
+        name: &str,
+        attr_type: CreateAttributeType,
+    ) {
+        AttributeSchema::new(
+            tx,
+            CreateAttributeSchema {
+                entity: schema.clone(),
+                name: name.to_string(),
+                quantity: Quantity::Optional,
+                constraints: Constraints::default(),
+                default: None,
+                unique: false,
+                attr_type,
+            },
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn inherited_attributes() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let media = ESD::default().name("Media").create(&tx);
+        let book = ESD::default().name("Book").parent(&media).create(&tx);
+        let title = ASD::default().name("Title").create(&tx, &media);
+        let isbn = ASD::default().name("Isbn").create(&tx, &book);
+
+        let stored = EntitySchema::get(&tx, &book).unwrap();
+        assert_eq!(stored.parent, Some(media.clone()));
+        let names: Vec<_> = stored.attributes.iter().map(|attr| &attr.name).collect();
+        assert_eq!(names.len(), 2);
+        assert!(names.contains(&&"Title".to_string()));
+
+        let stored = EntitySchema::get(&tx, &media).unwrap();
+        assert_eq!(stored.attributes.len(), 1);
+
+        let map = RawAttributeSchema::get_map(&tx, &book).unwrap();
+        assert!(map.contains_key(&title) && map.contains_key(&isbn));
+
+        // Inherited attributes are required of the subtype's entities too
+        let result = add_entity(&tx, &book, json!({ isbn.to_string(): "978-0" }));
+        assert_eq!(
+            result,
+            Err(Error::ModuleError(
+                "Did not provide field required by schema".to_string()
+            ))
+        );
+
+        let entity = add_entity(
+            &tx,
+            &book,
+            json!({ isbn.to_string(): "978-0", title.to_string(): "Dune" }),
+        )
+        .unwrap();
+        assert_eq!(RawAttributeSchema::get_map(&tx, &entity).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn subtype_entities() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let media = ESD::default().name("Media").create(&tx);
+        let book = ESD::default().name("Book").parent(&media).create(&tx);
+        let released = ASD::default()
+            .name("Released")
+            .attr_type(SimpleAttributeType::Date)
+            .create(&tx, &media);
+
+        let film = add_entity(&tx, &media, json!({ released.to_string(): "2021-10-22" })).unwrap();
+        let novel = add_entity(&tx, &book, json!({ released.to_string(): "1965-08-01" })).unwrap();
+
+        let entities = media.entities(&tx).unwrap();
+        assert!(entities.contains(&film) && entities.contains(&novel));
+        assert_eq!(book.entities(&tx).unwrap(), vec![novel.clone()]);
+
+        let found = get_in_date_range(&tx, &media, &released, "1900-01-01", "2100-01-01").unwrap();
+        assert_eq!(found, vec![novel.clone(), film]);
+
+        let found = get_in_date_range(&tx, &book, &released, "1900-01-01", "2100-01-01").unwrap();
+        assert_eq!(found, vec![novel]);
+    }
+
+    #[test]
+    fn cycle_error() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let media = ESD::default().name("Media").create(&tx);
+        let book = ESD::default().name("Book").parent(&media).create(&tx);
+        let novel = ESD::default().name("Novel").parent(&book).create(&tx);
+
+        let error = Err(Error::ModuleError(
+            "Schema cannot extend itself or one of its descendants".to_string(),
+        ));
+        assert_eq!(
+            media.set_parent(&tx, Some(&novel), DeleteMode::Restrict),
+            error
+        );
+        assert_eq!(
+            media.set_parent(&tx, Some(&media), DeleteMode::Restrict),
+            error
+        );
+
+        novel
+            .set_parent(&tx, Some(&media), DeleteMode::Restrict)
+            .unwrap();
+        assert_eq!(novel.ancestors(&tx).unwrap(), vec![novel.clone(), media]);
+    }
+
+    #[test]
+    fn name_override_error() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let media = ESD::default().name("Media").create(&tx);
+        let book = ESD::default().name("Book").parent(&media).create(&tx);
+        let other = ESD::default().name("Other").create(&tx);
+        ASD::default().name("Title").create(&tx, &media);
+        ASD::default().name("Isbn").create(&tx, &book);
+        ASD::default().name("Title").create(&tx, &other);
+
+        assert_eq!(
+            book.check_attribute_name(&tx, "Title"),
+            Err(Error::ModuleError(
+                "Attribute Title is already defined by Media in the same hierarchy".to_string()
+            ))
+        );
+        assert_eq!(
+            media.check_attribute_name(&tx, "Isbn"),
+            Err(Error::ModuleError(
+                "Attribute Isbn is already defined by Book in the same hierarchy".to_string()
+            ))
+        );
+        assert_eq!(
+            other.set_parent(&tx, Some(&book), DeleteMode::Restrict),
+            Err(Error::ModuleError(
+                "Attribute Title is already defined by Media in the same hierarchy".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn set_parent_backfill() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let media = ESD::default().name("Media").create(&tx);
+        let book = ESD::default().name("Book").create(&tx);
+        let entity = add_entity(&tx, &book, json!({})).unwrap();
+
+        ASD::default().name("Title").create(&tx, &media);
+        assert_eq!(
+            book.set_parent(&tx, Some(&media), DeleteMode::Restrict),
+            Err(Error::ModuleError(
+                "Required field added to a schema with entities must have a default".to_string()
+            ))
+        );
+
+        let other = ESD::default().name("Other").create(&tx);
+        let format = ASD::default()
+            .name("Format")
+            .quantity(Quantity::Required)
+            .default_value(DefaultValue::Value(json!("Paperback")))
+            .create(&tx, &other);
+        book.set_parent(&tx, Some(&other), DeleteMode::Restrict)
+            .unwrap();
+
+        let value: String = tx
+            .query_row(
+                "SELECT value FROM text_attribute WHERE entity = ?1 AND schema = ?2",
+                rusqlite::params![entity, format],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(value, "Paperback");
+    }
+
+    #[test]
+    fn set_parent_lost_values() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let media = ESD::default().name("Media").create(&tx);
+        let book = ESD::default().name("Book").parent(&media).create(&tx);
+        let novel = ESD::default().name("Novel").parent(&book).create(&tx);
+        let title = ASD::default().name("Title").create(&tx, &media);
+        let tags = ASD::default()
+            .name("Tags")
+            .quantity(Quantity::List)
+            .create(&tx, &media);
+        let isbn = ASD::default()
+            .name("Isbn")
+            .quantity(Quantity::Optional)
+            .create(&tx, &book);
+
+        add_entity(
+            &tx,
+            &book,
+            json!({ title.to_string(): "Dune", tags.to_string(): ["sf", "classic"] }),
+        )
+        .unwrap();
+        let entity = add_entity(
+            &tx,
+            &novel,
+            json!({ title.to_string(): "Emma", isbn.to_string(): "978-0" }),
+        )
+        .unwrap();
+        add_entity(&tx, &media, json!({ title.to_string(): "Heat" })).unwrap();
+
+        assert_eq!(
+            book.parent_preview(&tx, None).unwrap(),
+            ParentPreview {
+                attributes: vec!["Tags".to_string(), "Title".to_string()],
+                entities: 2,
+                values: 4,
+            }
+        );
+        assert_eq!(
+            novel.parent_preview(&tx, Some(&media)).unwrap(),
+            ParentPreview {
+                attributes: vec!["Isbn".to_string()],
+                entities: 1,
+                values: 1,
+            }
+        );
+
+        assert_eq!(
+            book.set_parent(&tx, None, DeleteMode::Restrict),
+            Err(Error::ModuleError(
+                "2 entities hold values of attributes no longer inherited".to_string()
+            ))
+        );
+
+        book.set_parent(&tx, None, DeleteMode::Cascade).unwrap();
+        assert_eq!(RawAttributeSchema::get_map(&tx, &entity).unwrap().len(), 1);
+
+        let count = |table: &str| -> usize {
+            tx.query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| {
+                row.get(0)
+            })
+            .unwrap()
+        };
+        // Only the media entity keeps its title, and the novel its isbn
+        assert_eq!(count("text_attribute"), 2);
+    }
+
+    #[test]
+    fn set_parent_dependents() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let media = ESD::default().name("Media").create(&tx);
+        let book = ESD::default().name("Book").parent(&media).create(&tx);
+        let shelf = ESD::default().name("Shelf").create(&tx);
+        let pages = ASD::default()
+            .name("Pages")
+            .attr_type(SimpleAttributeType::Integer)
+            .quantity(Quantity::Optional)
+            .create(&tx, &media);
+        ASD::default()
+            .name("Title")
+            .quantity(Quantity::Optional)
+            .create(&tx, &media);
+
+        let moved = |tx: &Transaction| {
+            let result = book.set_parent(tx, None, DeleteMode::Cascade);
+            // A refused move leaves the schema where it was
+            if result.is_err() {
+                assert_eq!(
+                    EntitySchema::get(tx, &book).unwrap().parent,
+                    Some(media.clone())
+                );
+            }
+            result
+        };
+
+        // Computed fields reading a lost attribute
+        let spread = CreateAttributeType::Computed(ComputedAttribute {
+            expression: "Pages * 2".to_string(),
+        });
+        create_derived(&tx, &book, "Spread", spread);
+        assert_eq!(
+            moved(&tx),
+            Err(Error::ModuleError(
+                "Unknown attribute Pages in expression".to_string()
+            ))
+        );
+        tx.execute("DELETE FROM attribute_schema WHERE name = 'Spread'", [])
+            .unwrap();
+
+        // Rollups elsewhere aggregating a lost attribute
+        let books = RSD::default()
+            .name("Books")
+            .quantity(Quantity::List)
+            .create(&tx, &shelf, &book);
+        let total = CreateAttributeType::Rollup(RollupAttribute {
+            reference: books,
+            attribute: Some(pages),
+            aggregate: Aggregate::Sum,
+        });
+        create_derived(&tx, &shelf, "Total", total);
+        assert_eq!(
+            moved(&tx),
+            Err(Error::ModuleError(
+                "Total relies on an attribute no longer inherited: Rollup attribute must belong to the referenced schema".to_string()
+            ))
+        );
+        tx.execute("DELETE FROM attribute_schema WHERE name = 'Total'", [])
+            .unwrap();
+
+        // Displays naming a lost attribute
+        book.set_display(&tx, Some(EntityDisplay::Attribute("Title".to_string())))
+            .unwrap();
+        assert_eq!(
+            moved(&tx),
+            Err(Error::ModuleError(
+                "Display of Book uses Title, which is no longer inherited".to_string()
+            ))
+        );
+        book.set_display(&tx, None).unwrap();
+
+        moved(&tx).unwrap();
+        assert_eq!(EntitySchema::get(&tx, &book).unwrap().parent, None);
+    }
+}
@@ -0,0 +1,354 @@
+use rusqlite::{params, Result, Transaction};
+use serde_json::{json, Map, Value};
+
+use crate::{
+    database::attribute_schema::{GetSchemaMap, RawAttributeSchema},
+    models::{
+        attribute_schema::{Constraints, Quantity},
+        attribute_type::{AttributeType, EdgeProperty, SimpleAttributeType},
+        entity_schema::EntitySchemaId,
+    },
+};
+
+const DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+fn uuid() -> Value {
+    json!({ "type": "string", "format": "uuid" })
+}
+
+fn simple_schema(simple: &SimpleAttributeType) -> Value {
+    match simple {
+        SimpleAttributeType::Text
+        | SimpleAttributeType::RichText
+        | SimpleAttributeType::Longform => json!({ "type": "string" }),
+        SimpleAttributeType::Integer => json!({ "type": "integer" }),
+        SimpleAttributeType::Number => json!({ "type": "number" }),
+        SimpleAttributeType::Date => json!({ "type": "string", "format": "date" }),
+        SimpleAttributeType::DateTime => json!({ "type": "string", "format": "date-time" }),
+        SimpleAttributeType::Boolean => json!({ "type": "boolean" }),
+        SimpleAttributeType::Url => json!({ "type": "string", "format": "uri" }),
+        SimpleAttributeType::Attachment => json!({
+            "type": "object",
+            "properties": {
+                "hash": { "type": "string" },
+                "filename": { "type": "string" },
+                "mime": { "type": "string" },
+            },
+            "required": ["hash", "filename", "mime"],
+        }),
+        SimpleAttributeType::Location => json!({
+            "type": "object",
+            "properties": {
+                "latitude": { "type": "number", "minimum": -90, "maximum": 90 },
+                "longitude": { "type": "number", "minimum": -180, "maximum": 180 },
+                "label": { "type": "string" },
+            },
+            "required": ["latitude", "longitude"],
+        }),
+    }
+}
+
+// Adds the constraints that have a JSON Schema keyword to a string or number
+// schema
+fn constrain(schema: Value, constraints: &Constraints) -> Value {
+    let Value::Object(mut schema) = schema else {
+        return schema;
+    };
+
+    let keywords = match schema.get("type").and_then(Value::as_str) {
+        Some("string") => vec![
+            ("minLength", constraints.min_length.map(Value::from)),
+            ("maxLength", constraints.max_length.map(Value::from)),
+            ("pattern", constraints.pattern.clone().map(Value::from)),
+        ],
+        Some("integer" | "number") => vec![
+            ("minimum", constraints.min.map(Value::from)),
+            ("maximum", constraints.max.map(Value::from)),
+        ],
+        _ => Vec::new(),
+    };
+
+    for (keyword, value) in keywords {
+        if let Some(value) = value {
+            schema.insert(keyword.to_string(), value);
+        }
+    }
+
+    Value::Object(schema)
+}
+
+// A link is the target's id, or an object carrying the edge properties,
+// each of which can be left null
+fn reference_schema(edges: &[EdgeProperty]) -> Value {
+    if edges.is_empty() {
+        return uuid();
+    }
+
+    let mut properties = Map::new();
+    properties.insert("target".to_string(), uuid());
+    for edge in edges {
+        properties.insert(
+            edge.name.clone(),
+            json!({ "anyOf": [simple_schema(&edge.attr_type), { "type": "null" }] }),
+        );
+    }
+
+    json!({
+        "oneOf": [
+            uuid(),
+            {
+                "type": "object",
+                "properties": properties,
+                "required": ["target"],
+                "additionalProperties": false,
+            },
+        ]
+    })
+}
+
+// The shape of a single value, or None for fields that are never provided
+fn item_schema(attribute: &RawAttributeSchema) -> Option<Value> {
+    let constraints = &attribute.constraints;
+
+    let schema = match &attribute.attr_type {
+        AttributeType::Simple(simple) => constrain(simple_schema(simple), constraints),
+        AttributeType::Reference(reference) => reference_schema(&reference.edges),
+        AttributeType::Select(select) => json!({ "type": "string", "enum": select.options }),
+        // Measures take a number in the field's unit or text naming a unit
+        AttributeType::Measure(measure) => json!({
+            "oneOf": [
+                constrain(json!({ "type": "number" }), constraints),
+                { "type": "string" },
+            ],
+            "description": format!("Number in {}, or a quantity with its unit", measure.unit),
+        }),
+        AttributeType::Computed(..) | AttributeType::Rollup(..) | AttributeType::Backlink(..) => {
+            return None;
+        }
+    };
+
+    Some(schema)
+}
+
+fn field_schema(attribute: &RawAttributeSchema) -> Option<Value> {
+    let item = item_schema(attribute)?;
+
+    let mut schema = match attribute.quantity {
+        Quantity::Required => item,
+        Quantity::Optional => json!({ "anyOf": [item, { "type": "null" }] }),
+        Quantity::List => {
+            let mut list = json!({ "type": "array", "items": item });
+            if let Some(min) = attribute.constraints.min_items {
+                list["minItems"] = Value::from(min);
+            }
+            if let Some(max) = attribute.constraints.max_items {
+                list["maxItems"] = Value::from(max);
+            }
+            list
+        }
+    };
+
+    schema["title"] = Value::from(attribute.name.clone());
+
+    Some(schema)
+}
+
+impl EntitySchemaId {
+    // Describes the object add_entity takes for the schema, keyed by
+    // attribute id. Fields with a default can be left out even when a value
+    // is needed
+    pub fn json_schema(&self, tx: &Transaction) -> Result<Value> {
+        let name: String = tx.query_row(
+            "SELECT name FROM entity_schema WHERE id = ?1",
+            params![self],
+            |row| row.get(0),
+        )?;
+
+        let mut attributes: Vec<RawAttributeSchema> = RawAttributeSchema::get_map(tx, self)?
+            .into_values()
+            .collect();
+        attributes.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut properties = Map::new();
+        let mut required = Vec::new();
+        for attribute in &attributes {
+            let Some(schema) = field_schema(attribute) else {
+                continue;
+            };
+
+            if attribute.needs_value() && attribute.default.is_none() {
+                required.push(Value::from(attribute.id.to_string()));
+            }
+
+            properties.insert(attribute.id.to_string(), schema);
+        }
+
+        Ok(json!({
+            "$schema": DIALECT,
+            "title": name,
+            "type": "object",
+            "properties": properties,
+            "required": required,
+            "additionalProperties": false,
+        }))
+    }
+}
+
+// A JSON Schema for every entity schema, keyed by schema name
+pub fn json_schemas(tx: &Transaction) -> Result<Map<String, Value>> {
+    let mut statement = tx.prepare("SELECT id, name FROM entity_schema ORDER BY name")?;
+    let schemas = statement
+        .query_map([], |row| {
+            Ok((row.get::<_, EntitySchemaId>(0)?, row.get(1)?))
+        })?
+        .collect::<Result<Vec<(EntitySchemaId, String)>>>()?;
+
+    schemas
+        .into_iter()
+        .map(|(id, name)| Ok((name, id.json_schema(tx)?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{
+        database::{
+            entity::add_entity,
+            test::test_util::{setup, ASD, ESD, RSD},
+            New,
+        },
+        models::{
+            attribute_schema::{
+                AttributeSchema, Constraints, CreateAttributeSchema, DefaultValue, Quantity,
+            },
+            attribute_type::{
+                CreateAttributeType, CreateReferenceAttribute, EdgeProperty, SimpleAttributeType,
+            },
+        },
+    };
+
+    #[test]
+    fn json_schema() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let es = ESD::default().name("Book").create(&tx);
+        let title = ASD::default()
+            .name("Title")
+            .constraints(Constraints {
+                max_length: Some(80),
+                ..Default::default()
+            })
+            .create(&tx, &es);
+        let pages = ASD::default()
+            .name("Pages")
+            .attr_type(SimpleAttributeType::Integer)
+            .quantity(Quantity::Optional)
+            .create(&tx, &es);
+        let read = ASD::default()
+            .name("Read")
+            .attr_type(SimpleAttributeType::Boolean)
+            .default_value(DefaultValue::Value(json!(false)))
+            .create(&tx, &es);
+        let sequels = RSD::default()
+            .name("Sequels")
+            .quantity(Quantity::List)
+            .create(&tx, &es, &es);
+        let genres = AttributeSchema::new(
+            &tx,
+            CreateAttributeSchema {
+                entity: es.clone(),
+                name: "Genres".to_string(),
+                attr_type: CreateAttributeType::Select {
+                    options: vec!["Fiction".to_string(), "History".to_string()],
+                },
+                quantity: Quantity::List,
+                constraints: Constraints {
+                    min_items: Some(1),
+                    ..Default::default()
+                },
+                default: None,
+                unique: false,
+            },
+        )
+        .unwrap()
+        .id;
+
+        assert_eq!(
+            es.json_schema(&tx).unwrap(),
+            json!({
+                "$schema": "https://json-schema.org/draft/2020-12/schema",
+                "title": "Book",
+                "type": "object",
+                "properties": {
+                    title.to_string(): { "title": "Title", "type": "string", "maxLength": 80 },
+                    pages.to_string(): {
+                        "title": "Pages",
+                        "anyOf": [{ "type": "integer" }, { "type": "null" }],
+                    },
+                    read.to_string(): { "title": "Read", "type": "boolean" },
+                    sequels.to_string(): {
+                        "title": "Sequels",
+                        "type": "array",
+                        "items": { "type": "string", "format": "uuid" },
+                    },
+                    genres.to_string(): {
+                        "title": "Genres",
+                        "type": "array",
+                        "items": { "type": "string", "enum": ["Fiction", "History"] },
+                        "minItems": 1,
+                    },
+                },
+                "required": [genres.to_string(), title.to_string()],
+                "additionalProperties": false,
+            })
+        );
+    }
+
+    #[test]
+    fn null_edge_properties() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let person = ESD::default().name("Person").create(&tx);
+        let project = ESD::default().name("Project").create(&tx);
+        let members = AttributeSchema::new(
+            &tx,
+            CreateAttributeSchema {
+                entity: project.clone(),
+                name: "Members".to_string(),
+                attr_type: CreateAttributeType::Reference(CreateReferenceAttribute {
+                    targets: vec![person.clone()],
+                    inverse: None,
+                    edges: vec![EdgeProperty {
+                        name: "role".to_string(),
+                        attr_type: SimpleAttributeType::Text,
+                    }],
+                }),
+                quantity: Quantity::List,
+                constraints: Constraints::default(),
+                default: None,
+                unique: false,
+            },
+        )
+        .unwrap()
+        .id;
+
+        let schema = project.json_schema(&tx).unwrap();
+        assert_eq!(
+            schema["properties"][members.to_string()]["items"]["oneOf"][1]["properties"]["role"],
+            json!({ "anyOf": [{ "type": "string" }, { "type": "null" }] })
+        );
+
+        // The payload the schema allows is taken by add_entity
+        let member = add_entity(&tx, &person, json!({})).unwrap();
+        add_entity(
+            &tx,
+            &project,
+            json!({ members.to_string(): [{ "target": member.to_string(), "role": null }] }),
+        )
+        .unwrap();
+    }
+}
@@ -9,21 +9,43 @@ use rusqlite::{params, Result, Transaction};
 
 use super::{Get, GetMany, New};
 
+mod delete_entity_schema;
+mod display;
+mod hierarchy;
+mod json_schema;
+
+pub use display::display_names;
+pub use hierarchy::{ancestors, descendants};
+pub use json_schema::json_schemas;
+
 impl New<CreateEntitySchema> for EntitySchema {
     fn new(conn: &Transaction, data: CreateEntitySchema) -> Result<Self> {
-        let new_entity_schema = Self {
+        let mut new_entity_schema = Self {
             id: EntitySchemaId::new(),
             name: data.name,
+            parent: data.parent,
+            display: None,
             attributes: Vec::new(),
         };
 
         let created_at = get_timestamp();
 
         conn.execute(
-            "INSERT INTO entity_schema (id, name, created, updated) VALUES (?1, ?2, ?3, ?3)",
-            (&new_entity_schema.id, &new_entity_schema.name, created_at),
+            "INSERT INTO entity_schema (id, name, parent, created, updated) VALUES (?1, ?2, ?3, ?4, ?4)",
+            (
+                &new_entity_schema.id,
+                &new_entity_schema.name,
+                &new_entity_schema.parent,
+                created_at,
+            ),
         )?;
 
+        // Attributes are inherited, so a new schema with a parent starts out
+        // with the parent's attributes
+        if new_entity_schema.parent.is_some() {
+            new_entity_schema.attributes = AttributeSchema::get_many(conn, &new_entity_schema.id)?;
+        }
+
         Ok(new_entity_schema)
     }
 }
@@ -31,14 +53,17 @@ impl New<CreateEntitySchema> for EntitySchema {
 impl Get<EntitySchemaId> for EntitySchema {
     fn get(tx: &Transaction, id: &EntitySchemaId) -> Result<Self> {
         let attributes = AttributeSchema::get_many(tx, id)?;
+        let display = id.display(tx)?;
 
         tx.query_row(
-            "SELECT id, name FROM entity_schema WHERE id=?1",
+            "SELECT id, name, parent FROM entity_schema WHERE id=?1",
             params![id],
             |row| {
                 Ok(Self {
                     id: row.get(0)?,
                     name: row.get(1)?,
+                    parent: row.get(2)?,
+                    display,
                     attributes,
                 })
             },
@@ -51,7 +76,7 @@ mod tests {
     use crate::{
         database::{test::test_util::setup, New},
         models::{
-            attribute_schema::{CreateAttributeSchema, Quantity},
+            attribute_schema::{Constraints, CreateAttributeSchema, Quantity},
             attribute_type::{CreateAttributeType, SimpleAttributeType},
         },
     };
@@ -65,6 +90,7 @@ mod tests {
             &tx,
             CreateEntitySchema {
                 name: NAME.to_string(),
+                parent: None,
             },
         )
         .expect("Unable to create entity");
@@ -101,6 +127,9 @@ mod tests {
             CreateAttributeSchema {
                 name: name1.to_string(),
                 quantity: Quantity::Required,
+                constraints: Constraints::default(),
+                default: None,
+                unique: false,
                 entity: entity_id.clone(),
                 attr_type: attr_type_1,
             },
@@ -112,6 +141,9 @@ mod tests {
             CreateAttributeSchema {
                 name: name2.to_string(),
                 quantity: Quantity::Required,
+                constraints: Constraints::default(),
+                default: None,
+                unique: false,
                 entity: entity_id.clone(),
                 attr_type: attr_type_2,
             },
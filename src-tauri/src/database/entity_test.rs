@@ -1,14 +1,25 @@
+use rusqlite::{Error, Transaction};
 use serde_json::Value;
 
 use crate::{
     database::{
-        entity::{get, EntityField, EntityRequest},
+        entity::{get, EntityField, EntityRequest, MeasureField},
         test::test_util::{assert_string_key, setup, ASD, ESD, RSD},
+        New,
+    },
+    models::{
+        attribute_schema::{
+            AttributeSchema, AttributeSchemaId, Constraints, CreateAttributeSchema, Quantity,
+        },
+        attribute_type::{
+            Aggregate, ComputedAttribute, CreateAttributeType, CreateReferenceAttribute, Dimension,
+            EdgeProperty, MeasureAttribute, RollupAttribute, SimpleAttributeType,
+        },
+        entity_schema::EntitySchemaId,
     },
-    models::attribute_schema::Quantity,
 };
 
-use super::entity::{add_entity, EntityAttribute};
+use super::entity::{add_entity, EntityAttribute, EntityFragment};
 
 #[test]
 fn text() {
@@ -65,8 +76,9 @@ fn list() {
     let result = get(&tx, &entity_id, &request).unwrap();
     let val = result.get(&attribute_id.to_string()).unwrap();
 
-    let val_1 = Value::String("Hello moon".to_string());
-    let val_2 = Value::String("Hello world".to_string());
+    // Lists keep the order they were provided in
+    let val_1 = Value::String("Hello world".to_string());
+    let val_2 = Value::String("Hello moon".to_string());
 
     let vec = vec![val_1, val_2];
     let expected = Value::Array(vec);
@@ -74,6 +86,217 @@ fn list() {
     assert_eq!(val, &expected);
 }
 
+#[test]
+fn integer() {
+    let mut conn = setup();
+    let tx = conn.transaction().unwrap();
+    let schema_id = &ESD::default().create(&tx);
+    let attribute_id = ASD::default()
+        .attr_type(SimpleAttributeType::Integer)
+        .create(&tx, schema_id);
+
+    let data = serde_json::from_str(&format!(
+        r#"
+            {{
+              "{attribute_id}": 320
+            }}
+            "#
+    ))
+    .unwrap();
+
+    let entity_id = add_entity(&tx, &schema_id, data).unwrap();
+
+    let request = EntityRequest {
+        0: vec![EntityField::Attribute(attribute_id.clone())],
+    };
+
+    let result = get(&tx, &entity_id, &request).unwrap();
+    let val = result.get(&attribute_id.to_string()).unwrap();
+
+    assert_eq!(val, &Value::from(320));
+}
+
+#[test]
+fn number_list() {
+    let mut conn = setup();
+    let tx = conn.transaction().unwrap();
+    let schema_id = &ESD::default().create(&tx);
+    let attribute_id = ASD::default()
+        .attr_type(SimpleAttributeType::Number)
+        .quantity(Quantity::List)
+        .create(&tx, schema_id);
+
+    let data = serde_json::from_str(&format!(
+        r#"
+            {{
+              "{attribute_id}": [4.5, 2]
+            }}
+            "#
+    ))
+    .unwrap();
+
+    let entity_id = add_entity(&tx, &schema_id, data).unwrap();
+
+    let request = EntityRequest {
+        0: vec![EntityField::Attribute(attribute_id.clone())],
+    };
+
+    let result = get(&tx, &entity_id, &request).unwrap();
+    let val = result.get(&attribute_id.to_string()).unwrap();
+
+    let expected = Value::Array(vec![Value::from(4.5), Value::from(2.0)]);
+
+    assert_eq!(val, &expected);
+}
+
+#[test]
+fn datetime() {
+    let mut conn = setup();
+    let tx = conn.transaction().unwrap();
+    let schema_id = &ESD::default().create(&tx);
+    let date_id = ASD::default()
+        .attr_type(SimpleAttributeType::Date)
+        .create(&tx, schema_id);
+    let datetime_id = ASD::default()
+        .name("2")
+        .attr_type(SimpleAttributeType::DateTime)
+        .create(&tx, schema_id);
+
+    let data = serde_json::from_str(&format!(
+        r#"
+            {{
+              "{date_id}": "2024-02-29",
+              "{datetime_id}": "2024-02-29T18:30:00+09:00"
+            }}
+            "#
+    ))
+    .unwrap();
+
+    let entity_id = add_entity(&tx, &schema_id, data).unwrap();
+
+    let request = EntityRequest {
+        0: vec![
+            EntityField::Attribute(date_id.clone()),
+            EntityField::Attribute(datetime_id.clone()),
+        ],
+    };
+
+    let result = get(&tx, &entity_id, &request).unwrap();
+
+    assert_string_key(&result, date_id, "2024-02-29");
+    assert_string_key(&result, datetime_id, "2024-02-29T18:30:00+09:00");
+}
+
+#[test]
+fn optional_boolean() {
+    let mut conn = setup();
+    let tx = conn.transaction().unwrap();
+    let schema_id = &ESD::default().create(&tx);
+    let attribute_id = ASD::default()
+        .attr_type(SimpleAttributeType::Boolean)
+        .quantity(Quantity::Optional)
+        .create(&tx, schema_id);
+
+    let request = EntityRequest {
+        0: vec![EntityField::Attribute(attribute_id.clone())],
+    };
+
+    for (provided, expected) in [
+        ("true", Value::Bool(true)),
+        ("false", Value::Bool(false)),
+        ("null", Value::Null),
+    ] {
+        let data = serde_json::from_str(&format!(
+            r#"
+            {{
+              "{attribute_id}": {provided}
+            }}
+            "#
+        ))
+        .unwrap();
+
+        let entity_id = add_entity(&tx, &schema_id, data).unwrap();
+
+        let result = get(&tx, &entity_id, &request).unwrap();
+        let val = result.get(&attribute_id.to_string()).unwrap();
+
+        assert_eq!(val, &expected);
+    }
+
+    let data = serde_json::from_str("{}").unwrap();
+    let entity_id = add_entity(&tx, &schema_id, data).unwrap();
+
+    let result = get(&tx, &entity_id, &request).unwrap();
+    let val = result.get(&attribute_id.to_string()).unwrap();
+
+    assert_eq!(val, &Value::Null);
+}
+
+#[test]
+fn multiple_values_error() {
+    let mut conn = setup();
+    let tx = conn.transaction().unwrap();
+    let schema_id = &ESD::default().create(&tx);
+    let attribute_id = ASD::default()
+        .quantity(Quantity::List)
+        .create(&tx, schema_id);
+
+    let entity_id = add_entity(
+        &tx,
+        schema_id,
+        serde_json::json!({ attribute_id.to_string(): ["a", "b"] }),
+    )
+    .unwrap();
+
+    // Values written while the field was a list are left behind by changing
+    // the quantity directly
+    tx.execute(
+        "UPDATE attribute_schema SET quantity = 'Optional' WHERE id = ?1",
+        rusqlite::params![attribute_id],
+    )
+    .unwrap();
+
+    let request = EntityRequest {
+        0: vec![EntityField::Attribute(attribute_id)],
+    };
+
+    assert_eq!(
+        get(&tx, &entity_id, &request),
+        Err(Error::ModuleError(
+            "Multiple values for single field".to_string()
+        ))
+    );
+}
+
+#[test]
+fn url() {
+    let mut conn = setup();
+    let tx = conn.transaction().unwrap();
+    let schema_id = &ESD::default().create(&tx);
+    let attribute_id = ASD::default()
+        .attr_type(SimpleAttributeType::Url)
+        .create(&tx, schema_id);
+
+    let data = serde_json::from_str(&format!(
+        r#"
+            {{
+              "{attribute_id}": "HTTPS://Example.COM:443/a/../books?id=1"
+            }}
+            "#
+    ))
+    .unwrap();
+
+    let entity_id = add_entity(&tx, &schema_id, data).unwrap();
+
+    let request = EntityRequest {
+        0: vec![EntityField::Attribute(attribute_id.clone())],
+    };
+
+    let result = get(&tx, &entity_id, &request).unwrap();
+
+    assert_string_key(&result, attribute_id, "https://example.com/books?id=1");
+}
+
 #[test]
 fn multifield() {
     let mut conn = setup();
@@ -115,10 +338,7 @@ fn reference() {
     let tx = conn.transaction().unwrap();
 
     let parent_schema = &ESD::create_default(&tx);
-    let child_schema = &ESD {
-        name: "Child".to_string(),
-    }
-    .create(&tx);
+    let child_schema = &ESD::default().name("Child").create(&tx);
 
     let reference_attr = RSD::create_default(&tx, parent_schema, child_schema);
 
@@ -154,6 +374,7 @@ fn reference() {
         0: vec![EntityField::Entity(EntityAttribute {
             attribute: reference_attr.clone(),
             request: child_request,
+            fragments: Vec::new(),
         })],
     };
 
@@ -162,3 +383,764 @@ fn reference() {
     let expected_child = result.get(&reference_attr.to_string()).unwrap();
     assert!(matches!(expected_child, Value::Object(..)));
 }
+
+fn create_reference(
+    tx: &Transaction,
+    schema_id: &EntitySchemaId,
+    targets: Vec<EntitySchemaId>,
+) -> rusqlite::Result<AttributeSchemaId> {
+    let attribute = AttributeSchema::new(
+        tx,
+        CreateAttributeSchema {
+            entity: schema_id.clone(),
+            name: "Subject".to_string(),
+            quantity: Quantity::List,
+            constraints: Constraints::default(),
+            default: None,
+            unique: false,
+            attr_type: CreateAttributeType::Reference(CreateReferenceAttribute {
+                targets,
+                inverse: None,
+                edges: Vec::new(),
+            }),
+        },
+    )?;
+
+    Ok(attribute.id)
+}
+
+#[test]
+fn polymorphic_reference() {
+    let mut conn = setup();
+    let tx = conn.transaction().unwrap();
+
+    let note = &ESD::default().name("Note").create(&tx);
+    let person = &ESD::default().name("Person").create(&tx);
+    let place = &ESD::default().name("Place").create(&tx);
+    let city = &ESD::default().name("City").parent(place).create(&tx);
+    let other = &ESD::default().name("Other").create(&tx);
+
+    let subject = create_reference(&tx, note, vec![person.clone(), place.clone()]).unwrap();
+    let person_name = ASD::default().name("Name").create(&tx, person);
+    let place_label = ASD::default().name("Label").create(&tx, place);
+
+    let alice = add_entity(
+        &tx,
+        person,
+        serde_json::json!({ person_name.to_string(): "Alice" }),
+    )
+    .unwrap();
+    // Subtypes of a target are accepted
+    let paris = add_entity(
+        &tx,
+        city,
+        serde_json::json!({ place_label.to_string(): "Paris" }),
+    )
+    .unwrap();
+    let stray = add_entity(&tx, other, serde_json::json!({})).unwrap();
+
+    let result = add_entity(
+        &tx,
+        note,
+        serde_json::json!({ subject.to_string(): [stray.to_string()] }),
+    );
+    assert_eq!(
+        result.map(|_| ()),
+        Err(Error::ModuleError(
+            "Referenced entity must be of schema Person | Place".to_string()
+        ))
+    );
+
+    let note_id = add_entity(
+        &tx,
+        note,
+        serde_json::json!({ subject.to_string(): [alice.to_string(), paris.to_string()] }),
+    )
+    .unwrap();
+
+    let fragment = |schema: &EntitySchemaId, attr: &AttributeSchemaId| EntityFragment {
+        schema: schema.clone(),
+        request: EntityRequest(vec![EntityField::Attribute(attr.clone())]),
+    };
+    let request = EntityRequest(vec![EntityField::Entity(EntityAttribute {
+        attribute: subject.clone(),
+        request: EntityRequest(Vec::new()),
+        fragments: vec![
+            fragment(person, &person_name),
+            fragment(place, &place_label),
+        ],
+    })]);
+
+    let result = get(&tx, &note_id, &request).unwrap();
+    let Some(Value::Array(subjects)) = result.get(&subject.to_string()) else {
+        panic!("Expected a list of subjects");
+    };
+
+    // Each target only has the fields requested for its own schema
+    let mut subjects = subjects
+        .iter()
+        .map(|s| {
+            let s = s.as_object().unwrap();
+            (
+                s.get(&person_name.to_string()).cloned(),
+                s.get(&place_label.to_string()).cloned(),
+            )
+        })
+        .collect::<Vec<_>>();
+    subjects.sort_by_key(|s| s.0.is_none());
+    assert_eq!(
+        subjects,
+        vec![
+            (Some(Value::from("Alice")), None),
+            (None, Some(Value::from("Paris")))
+        ]
+    );
+}
+
+#[test]
+fn backlink() {
+    let mut conn = setup();
+    let tx = conn.transaction().unwrap();
+
+    let book = &ESD::default().name("Book").create(&tx);
+    let author = &ESD::default().name("Author").create(&tx);
+    let title = ASD::default().name("Title").create(&tx, book);
+
+    let written_by = AttributeSchema::new(
+        &tx,
+        CreateAttributeSchema {
+            entity: book.clone(),
+            name: "Author".to_string(),
+            quantity: Quantity::Required,
+            constraints: Constraints::default(),
+            default: None,
+            unique: false,
+            attr_type: CreateAttributeType::Reference(CreateReferenceAttribute {
+                targets: vec![author.clone()],
+                inverse: Some("Books".to_string()),
+                edges: Vec::new(),
+            }),
+        },
+    )
+    .unwrap()
+    .id;
+
+    let books: AttributeSchemaId = tx
+        .query_row(
+            "SELECT id FROM attribute_schema WHERE entity = ?1 AND name = 'Books'",
+            rusqlite::params![author],
+            |row| row.get(0),
+        )
+        .unwrap();
+
+    let tolkien = add_entity(&tx, author, serde_json::json!({})).unwrap();
+    let unpublished = add_entity(&tx, author, serde_json::json!({})).unwrap();
+    for name in ["The Hobbit", "The Silmarillion"] {
+        let data = serde_json::json!({
+            title.to_string(): name,
+            written_by.to_string(): tolkien.to_string(),
+        });
+        add_entity(&tx, book, data).unwrap();
+    }
+
+    let request = EntityRequest(vec![EntityField::Entity(EntityAttribute {
+        attribute: books.clone(),
+        request: EntityRequest(vec![EntityField::Attribute(title.clone())]),
+        fragments: Vec::new(),
+    })]);
+
+    let result = get(&tx, &tolkien, &request).unwrap();
+    let Some(Value::Array(linked)) = result.get(&books.to_string()) else {
+        panic!("Expected a list of books");
+    };
+    let mut titles = linked
+        .iter()
+        .map(|book| book.get(title.to_string()).unwrap().as_str().unwrap())
+        .collect::<Vec<_>>();
+    titles.sort();
+    assert_eq!(titles, vec!["The Hobbit", "The Silmarillion"]);
+
+    let result = get(&tx, &unpublished, &request).unwrap();
+    assert_eq!(
+        result.get(&books.to_string()),
+        Some(&Value::Array(Vec::new()))
+    );
+}
+
+#[test]
+fn edge_properties() {
+    let mut conn = setup();
+    let tx = conn.transaction().unwrap();
+
+    let person = &ESD::default().name("Person").create(&tx);
+    let project = &ESD::default().name("Project").create(&tx);
+
+    let edge = |name: &str, attr_type| EdgeProperty {
+        name: name.to_string(),
+        attr_type,
+    };
+    let projects = AttributeSchema::new(
+        &tx,
+        CreateAttributeSchema {
+            entity: person.clone(),
+            name: "Projects".to_string(),
+            quantity: Quantity::List,
+            constraints: Constraints::default(),
+            default: None,
+            unique: false,
+            attr_type: CreateAttributeType::Reference(CreateReferenceAttribute {
+                targets: vec![project.clone()],
+                inverse: None,
+                edges: vec![
+                    edge("role", SimpleAttributeType::Text),
+                    edge("since", SimpleAttributeType::Date),
+                ],
+            }),
+        },
+    )
+    .unwrap()
+    .id;
+
+    let cortex = add_entity(&tx, project, serde_json::json!({})).unwrap();
+
+    let result = add_entity(
+        &tx,
+        person,
+        serde_json::json!({ projects.to_string(): [{ "target": cortex.to_string(), "rank": 1 }] }),
+    );
+    assert_eq!(
+        result.map(|_| ()),
+        Err(Error::ModuleError("Unknown edge property rank".to_string()))
+    );
+
+    let result = add_entity(
+        &tx,
+        person,
+        serde_json::json!({ projects.to_string(): [{ "target": cortex.to_string(), "since": 2024 }] }),
+    );
+    assert_eq!(
+        result.map(|_| ()),
+        Err(Error::ModuleError(
+            "Edge property since must be of type Date".to_string()
+        ))
+    );
+
+    let data = serde_json::json!({
+        projects.to_string(): [{ "target": cortex.to_string(), "role": "lead", "since": "2024-03-01" }],
+    });
+    let alice = add_entity(&tx, person, data).unwrap();
+
+    let request = EntityRequest(vec![EntityField::Entity(EntityAttribute {
+        attribute: projects.clone(),
+        request: EntityRequest(Vec::new()),
+        fragments: Vec::new(),
+    })]);
+
+    let result = get(&tx, &alice, &request).unwrap();
+    assert_eq!(
+        result.get(&projects.to_string()),
+        Some(&serde_json::json!([{ "edge": { "role": "lead", "since": "2024-03-01" } }]))
+    );
+}
+
+#[test]
+fn invalid_reference_id() {
+    let mut conn = setup();
+    let tx = conn.transaction().unwrap();
+
+    let note = &ESD::default().name("Note").create(&tx);
+    let subject = create_reference(&tx, note, Vec::new()).unwrap();
+    let error = Err(Error::ModuleError(
+        "Provided value is not a valid entity id".to_string(),
+    ));
+
+    let result = add_entity(
+        &tx,
+        note,
+        serde_json::json!({ subject.to_string(): ["garbage"] }),
+    );
+    assert_eq!(result.map(|_| ()), error);
+
+    let result = add_entity(
+        &tx,
+        note,
+        serde_json::json!({ subject.to_string(): [{ "target": "bad" }] }),
+    );
+    assert_eq!(result.map(|_| ()), error);
+}
+
+#[test]
+fn untargeted_reference() {
+    let mut conn = setup();
+    let tx = conn.transaction().unwrap();
+
+    let note = &ESD::default().name("Note").create(&tx);
+    let other = &ESD::default().name("Other").create(&tx);
+
+    // No targets accept an entity of any schema
+    let subject = create_reference(&tx, note, Vec::new()).unwrap();
+    let stray = add_entity(&tx, other, serde_json::json!({})).unwrap();
+
+    add_entity(
+        &tx,
+        note,
+        serde_json::json!({ subject.to_string(): [stray.to_string()] }),
+    )
+    .unwrap();
+}
+
+fn create_measure(
+    tx: &Transaction,
+    schema_id: &EntitySchemaId,
+    dimension: Dimension,
+    unit: &str,
+) -> rusqlite::Result<AttributeSchemaId> {
+    let attribute = AttributeSchema::new(
+        tx,
+        CreateAttributeSchema {
+            entity: schema_id.clone(),
+            name: "Distance".to_string(),
+            quantity: Quantity::List,
+            constraints: Constraints::default(),
+            default: None,
+            unique: false,
+            attr_type: CreateAttributeType::Measure(MeasureAttribute {
+                dimension,
+                unit: unit.to_string(),
+            }),
+        },
+    )?;
+
+    Ok(attribute.id)
+}
+
+#[test]
+fn measure() {
+    let mut conn = setup();
+    let tx = conn.transaction().unwrap();
+    let schema_id = &ESD::default().create(&tx);
+    let attribute_id = create_measure(&tx, schema_id, Dimension::Length, "m").unwrap();
+
+    let data = serde_json::from_str(&format!(
+        r#"
+            {{
+              "{attribute_id}": ["3.5 km", "20cm", 12]
+            }}
+            "#
+    ))
+    .unwrap();
+
+    let entity_id = add_entity(&tx, &schema_id, data).unwrap();
+
+    let request = EntityRequest {
+        0: vec![EntityField::Attribute(attribute_id.clone())],
+    };
+
+    let result = get(&tx, &entity_id, &request).unwrap();
+    let val = result.get(&attribute_id.to_string()).unwrap();
+
+    let expected: Value = serde_json::from_str("[3500.0, 0.2, 12.0]").unwrap();
+    assert_eq!(val, &expected);
+
+    let request = EntityRequest {
+        0: vec![EntityField::Measure(MeasureField {
+            attribute: attribute_id.clone(),
+            unit: "km".to_string(),
+        })],
+    };
+
+    let result = get(&tx, &entity_id, &request).unwrap();
+    let val = result.get(&attribute_id.to_string()).unwrap();
+
+    let expected: Value = serde_json::from_str("[3.5, 0.0002, 0.012]").unwrap();
+    assert_eq!(val, &expected);
+
+    let request = EntityRequest {
+        0: vec![EntityField::Measure(MeasureField {
+            attribute: attribute_id.clone(),
+            unit: "kg".to_string(),
+        })],
+    };
+
+    assert_eq!(
+        get(&tx, &entity_id, &request),
+        Err(Error::ModuleError(
+            "Cannot convert from m to kg".to_string()
+        ))
+    );
+}
+
+#[test]
+fn measure_wrong_unit_error() {
+    let mut conn = setup();
+    let tx = conn.transaction().unwrap();
+    let schema_id = &ESD::default().create(&tx);
+
+    assert_eq!(
+        create_measure(&tx, schema_id, Dimension::Length, "kg"),
+        Err(Error::ModuleError(
+            "Unit kg is not a unit of Length".to_string()
+        ))
+    );
+    assert_eq!(
+        create_measure(&tx, schema_id, Dimension::Mass, "stone"),
+        Err(Error::ModuleError("Unknown unit stone".to_string()))
+    );
+}
+
+#[test]
+fn location() {
+    let mut conn = setup();
+    let tx = conn.transaction().unwrap();
+    let schema_id = &ESD::default().create(&tx);
+    let attribute_id = ASD::default()
+        .attr_type(SimpleAttributeType::Location)
+        .quantity(Quantity::List)
+        .create(&tx, schema_id);
+
+    let data = serde_json::from_str(&format!(
+        r#"
+            {{
+              "{attribute_id}": [
+                {{ "latitude": 35.6764, "longitude": 139.65, "label": "Tokyo" }},
+                {{ "latitude": -33.8688, "longitude": 151.2093 }}
+              ]
+            }}
+            "#
+    ))
+    .unwrap();
+
+    let entity_id = add_entity(&tx, &schema_id, data).unwrap();
+
+    let request = EntityRequest {
+        0: vec![EntityField::Attribute(attribute_id.clone())],
+    };
+
+    let result = get(&tx, &entity_id, &request).unwrap();
+    let val = result.get(&attribute_id.to_string()).unwrap();
+
+    let expected: Value = serde_json::from_str(
+        r#"
+        [
+          { "latitude": 35.6764, "longitude": 139.65, "label": "Tokyo" },
+          { "latitude": -33.8688, "longitude": 151.2093, "label": null }
+        ]
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(val, &expected);
+}
+
+fn create_computed(
+    tx: &Transaction,
+    schema_id: &EntitySchemaId,
+    name: &str,
+    expression: &str,
+) -> rusqlite::Result<AttributeSchemaId> {
+    let attribute = AttributeSchema::new(
+        tx,
+        CreateAttributeSchema {
+            entity: schema_id.clone(),
+            name: name.to_string(),
+            quantity: Quantity::Optional,
+            constraints: Constraints::default(),
+            default: None,
+            unique: false,
+            attr_type: CreateAttributeType::Computed(ComputedAttribute {
+                expression: expression.to_string(),
+            }),
+        },
+    )?;
+
+    Ok(attribute.id)
+}
+
+#[test]
+fn computed() {
+    let mut conn = setup();
+    let tx = conn.transaction().unwrap();
+    let schema_id = &ESD::default().create(&tx);
+    let first = ASD::default().name("first").create(&tx, schema_id);
+    let last = ASD::default().name("last").create(&tx, schema_id);
+    let pages = ASD::default()
+        .name("pages")
+        .attr_type(SimpleAttributeType::Integer)
+        .create(&tx, schema_id);
+    let pages_read = ASD::default()
+        .name("Pages read")
+        .attr_type(SimpleAttributeType::Integer)
+        .quantity(Quantity::Optional)
+        .create(&tx, schema_id);
+
+    let full_name =
+        create_computed(&tx, schema_id, "Full name", "concat(first, \" \", last)").unwrap();
+    let remaining = create_computed(&tx, schema_id, "Remaining", "pages - `Pages read`").unwrap();
+
+    let data = serde_json::from_str(&format!(
+        r#"
+            {{
+              "{first}": "Ada",
+              "{last}": "Lovelace",
+              "{pages}": 300,
+              "{pages_read}": 120
+            }}
+            "#
+    ))
+    .unwrap();
+
+    let entity_id = add_entity(&tx, &schema_id, data).unwrap();
+
+    let request = EntityRequest {
+        0: vec![
+            EntityField::Attribute(first.clone()),
+            EntityField::Attribute(full_name.clone()),
+            EntityField::Attribute(remaining.clone()),
+        ],
+    };
+
+    let result = get(&tx, &entity_id, &request).unwrap();
+
+    assert_string_key(&result, first.clone(), "Ada");
+    assert_string_key(&result, full_name, "Ada Lovelace");
+    assert_eq!(
+        result.get(&remaining.to_string()).unwrap(),
+        &Value::from(180.0)
+    );
+    assert_eq!(result.get(&pages.to_string()), None);
+
+    let data = serde_json::from_str(&format!(
+        r#"
+            {{
+              "{first}": "Ada",
+              "{last}": "Lovelace",
+              "{pages}": 300,
+              "{remaining}": 10
+            }}
+            "#
+    ))
+    .unwrap();
+
+    assert_eq!(
+        add_entity(&tx, &schema_id, data),
+        Err(Error::ModuleError(
+            "Cannot set a computed field".to_string()
+        ))
+    );
+}
+
+#[test]
+fn computed_invalid_expression_error() {
+    let mut conn = setup();
+    let tx = conn.transaction().unwrap();
+    let schema_id = &ESD::default().create(&tx);
+    ASD::default().name("title").create(&tx, schema_id);
+    ASD::default()
+        .name("tags")
+        .quantity(Quantity::List)
+        .create(&tx, schema_id);
+
+    assert_eq!(
+        create_computed(&tx, schema_id, "Bar", "upper(subtitle)"),
+        Err(Error::ModuleError(
+            "Unknown attribute subtitle in expression".to_string()
+        ))
+    );
+    assert_eq!(
+        create_computed(&tx, schema_id, "Bar", "title * 2"),
+        Err(Error::ModuleError(
+            "Invalid expression: Multiply expects numbers, use concat to join text".to_string()
+        ))
+    );
+    assert_eq!(
+        create_computed(&tx, schema_id, "Bar", "concat(tags)"),
+        Err(Error::ModuleError(
+            "Attribute tags is a list and cannot be used in an expression".to_string()
+        ))
+    );
+}
+
+fn create_rollup(
+    tx: &Transaction,
+    schema_id: &EntitySchemaId,
+    name: &str,
+    rollup: RollupAttribute,
+) -> rusqlite::Result<AttributeSchemaId> {
+    let attribute = AttributeSchema::new(
+        tx,
+        CreateAttributeSchema {
+            entity: schema_id.clone(),
+            name: name.to_string(),
+            quantity: Quantity::Optional,
+            constraints: Constraints::default(),
+            default: None,
+            unique: false,
+            attr_type: CreateAttributeType::Rollup(rollup),
+        },
+    )?;
+
+    Ok(attribute.id)
+}
+
+#[test]
+fn rollup() {
+    let mut conn = setup();
+    let tx = conn.transaction().unwrap();
+    let book_schema = &ESD::default().name("Book").create(&tx);
+    let pages = ASD::default()
+        .name("Pages")
+        .attr_type(SimpleAttributeType::Integer)
+        .create(&tx, book_schema);
+    let genre = ASD::default().name("Genre").create(&tx, book_schema);
+
+    let author_schema = &ESD::default().name("Author").create(&tx);
+    let books = RSD::default()
+        .name("Books")
+        .quantity(Quantity::List)
+        .create(&tx, author_schema, book_schema);
+
+    let rollup = |name: &str, attribute: Option<&AttributeSchemaId>, aggregate| {
+        create_rollup(
+            &tx,
+            author_schema,
+            name,
+            RollupAttribute {
+                reference: books.clone(),
+                attribute: attribute.cloned(),
+                aggregate,
+            },
+        )
+        .unwrap()
+    };
+
+    let count = rollup("Count", None, Aggregate::Count);
+    let total = rollup("Total", Some(&pages), Aggregate::Sum);
+    let average = rollup("Average", Some(&pages), Aggregate::Average);
+    let genres = rollup("Genres", Some(&genre), Aggregate::Distinct);
+
+    let mut book_ids = Vec::new();
+    for (book_pages, book_genre) in [(300, "Fantasy"), (120, "Poetry"), (180, "Fantasy")] {
+        let data = serde_json::from_str(&format!(
+            r#"
+            {{
+              "{pages}": {book_pages},
+              "{genre}": "{book_genre}"
+            }}
+            "#
+        ))
+        .unwrap();
+
+        book_ids.push(add_entity(&tx, book_schema, data).unwrap().to_string());
+    }
+
+    let data = serde_json::from_str(&format!(
+        r#"
+            {{
+              "{books}": ["{}", "{}", "{}"]
+            }}
+            "#,
+        book_ids[0], book_ids[1], book_ids[2]
+    ))
+    .unwrap();
+
+    let author = add_entity(&tx, author_schema, data).unwrap();
+
+    let data = serde_json::from_str("{}").unwrap();
+    let no_books = add_entity(&tx, author_schema, data).unwrap();
+
+    let request = EntityRequest {
+        0: vec![
+            EntityField::Attribute(count.clone()),
+            EntityField::Attribute(total.clone()),
+            EntityField::Attribute(average.clone()),
+            EntityField::Attribute(genres.clone()),
+        ],
+    };
+
+    let result = get(&tx, &author, &request).unwrap();
+    assert_eq!(result.get(&count.to_string()).unwrap(), &Value::from(3));
+    assert_eq!(result.get(&total.to_string()).unwrap(), &Value::from(600.0));
+    assert_eq!(
+        result.get(&average.to_string()).unwrap(),
+        &Value::from(200.0)
+    );
+    assert_eq!(
+        result.get(&genres.to_string()).unwrap(),
+        &serde_json::json!(["Fantasy", "Poetry"])
+    );
+
+    let result = get(&tx, &no_books, &request).unwrap();
+    assert_eq!(result.get(&count.to_string()).unwrap(), &Value::from(0));
+    assert_eq!(result.get(&total.to_string()).unwrap(), &Value::from(0.0));
+    assert_eq!(result.get(&average.to_string()).unwrap(), &Value::Null);
+    assert_eq!(
+        result.get(&genres.to_string()).unwrap(),
+        &serde_json::json!([])
+    );
+}
+
+#[test]
+fn rollup_invalid_error() {
+    let mut conn = setup();
+    let tx = conn.transaction().unwrap();
+    let book_schema = &ESD::default().name("Book").create(&tx);
+    let title = ASD::default().name("Title").create(&tx, book_schema);
+
+    let author_schema = &ESD::default().name("Author").create(&tx);
+    let books = RSD::default()
+        .name("Books")
+        .quantity(Quantity::List)
+        .create(&tx, author_schema, book_schema);
+    let name = ASD::default().name("Name").create(&tx, author_schema);
+
+    let result = create_rollup(
+        &tx,
+        author_schema,
+        "Total",
+        RollupAttribute {
+            reference: books.clone(),
+            attribute: Some(title.clone()),
+            aggregate: Aggregate::Sum,
+        },
+    );
+    assert_eq!(
+        result,
+        Err(Error::ModuleError(
+            "Sum cannot be used on attribute Title".to_string()
+        ))
+    );
+
+    let result = create_rollup(
+        &tx,
+        author_schema,
+        "Total",
+        RollupAttribute {
+            reference: name,
+            attribute: Some(title.clone()),
+            aggregate: Aggregate::Count,
+        },
+    );
+    assert_eq!(
+        result,
+        Err(Error::ModuleError(
+            "Rollup reference must be a reference attribute of the same schema".to_string()
+        ))
+    );
+
+    let result = create_rollup(
+        &tx,
+        book_schema,
+        "Total",
+        RollupAttribute {
+            reference: books,
+            attribute: Some(title),
+            aggregate: Aggregate::Count,
+        },
+    );
+    assert_eq!(
+        result,
+        Err(Error::ModuleError(
+            "Rollup reference must be a reference attribute of the same schema".to_string()
+        ))
+    );
+}
@@ -25,6 +25,7 @@ fn build_attr(tx: &Transaction, name: &str, sql_type: &str, extra: &str) -> Resu
             schema BLOB NOT NULL,
             value {sql_type} NOT NULL,
             entity BLOB NOT NULL REFERENCES entity (id) ON DELETE CASCADE,
+            position INTEGER,
             {extra}
             FOREIGN KEY(schema) REFERENCES attribute_schema(id)
             "
@@ -64,6 +65,7 @@ fn build_attr(tx: &Transaction, name: &str, sql_type: &str, extra: &str) -> Resu
             BEFORE DELETE ON {name}_attribute
               WHEN EXISTS ( SELECT 1 FROM attribute_schema WHERE id = OLD.schema AND quantity = 'Required' )
               AND EXISTS (SELECT 1 FROM entity WHERE id = OLD.entity ) -- handle case where parent entity is being deleted
+              AND NOT EXISTS ( SELECT 1 FROM attribute_migration WHERE schema = OLD.schema ) -- values are being moved to another table
             BEGIN
               SELECT RAISE(FAIL, \"Cannot delete required field\");
             END;
@@ -72,6 +74,85 @@ fn build_attr(tx: &Transaction, name: &str, sql_type: &str, extra: &str) -> Resu
         (),
     )?;
 
+    // Backstops for the item count bounds of list fields, which are checked
+    // with a more descriptive error before writing. As with required fields,
+    // deletes cascading from the entity are allowed
+    tx.execute(
+        &format!(
+            "
+            CREATE TRIGGER IF NOT EXISTS {name}_max_items_check
+            BEFORE INSERT ON {name}_attribute
+              WHEN ( SELECT COUNT(*) FROM {name}_attribute WHERE entity = NEW.entity AND schema = NEW.schema )
+                >= ( SELECT json_extract(constraints, '$.max_items') FROM attribute_schema WHERE id = NEW.schema )
+            BEGIN
+              SELECT RAISE(ABORT, \"Attempted to add more entries than the list allows\");
+            END;
+            "
+        ),
+        (),
+    )?;
+
+    tx.execute(
+        &format!(
+            "
+            CREATE TRIGGER IF NOT EXISTS {name}_min_items_check
+            BEFORE DELETE ON {name}_attribute
+              WHEN ( SELECT COUNT(*) FROM {name}_attribute WHERE entity = OLD.entity AND schema = OLD.schema )
+                <= ( SELECT json_extract(constraints, '$.min_items') FROM attribute_schema WHERE id = OLD.schema )
+              AND EXISTS (SELECT 1 FROM entity WHERE id = OLD.entity )
+              AND NOT EXISTS ( SELECT 1 FROM attribute_migration WHERE schema = OLD.schema )
+            BEGIN
+              SELECT RAISE(ABORT, \"Cannot delete below the minimum number of entries\");
+            END;
+            "
+        ),
+        (),
+    )?;
+
+    // Values are appended to the end of their list unless placed explicitly
+    tx.execute(
+        &format!(
+            "
+            CREATE TRIGGER IF NOT EXISTS {name}_position_append
+            AFTER INSERT ON {name}_attribute
+              WHEN NEW.position IS NULL
+            BEGIN
+              UPDATE {name}_attribute SET position = (
+                SELECT COALESCE(MAX(position) + 1, 0) FROM {name}_attribute WHERE entity = NEW.entity AND schema = NEW.schema
+              ) WHERE id = NEW.id;
+            END;
+            "
+        ),
+        (),
+    )?;
+
+    // Looks up values for the unique checks
+    tx.execute(
+        &format!("CREATE INDEX IF NOT EXISTS idx_{name}_schema_value ON {name}_attribute (schema, value);"),
+        (),
+    )?;
+
+    // Prevents two entities holding the same value for a unique field. Writes
+    // through the attribute inserts look the holder up first so they can name
+    // it, which leaves these for anything bypassing them
+    for event in ["INSERT", "UPDATE OF value"] {
+        let trigger = event.split(' ').next().unwrap().to_lowercase();
+        tx.execute(
+            &format!(
+                "
+                CREATE TRIGGER IF NOT EXISTS {name}_unique_{trigger}_check
+                BEFORE {event} ON {name}_attribute
+                  WHEN EXISTS ( SELECT 1 FROM attribute_schema WHERE id = NEW.schema AND is_unique = 1 )
+                  AND EXISTS ( SELECT 1 FROM {name}_attribute WHERE schema = NEW.schema AND value = NEW.value AND entity != NEW.entity )
+                BEGIN
+                  SELECT RAISE(ABORT, \"Attempted to add a duplicate value to a unique field\");
+                END;
+                "
+            ),
+            (),
+        )?;
+    }
+
     Ok(())
 }
 
@@ -80,7 +161,10 @@ fn initial(tx: &Transaction) -> Result<()> {
         &tx,
         "entity_schema",
         "
-        name TEXT NOT NULL UNIQUE        
+        name TEXT NOT NULL UNIQUE,
+        parent BLOB,
+        display TEXT,
+        FOREIGN KEY(parent) REFERENCES entity_schema(id)
         ",
     )?;
 
@@ -91,10 +175,20 @@ fn initial(tx: &Transaction) -> Result<()> {
       entity BLOB NOT NULL,
       name TEXT NOT NULL,
       type TEXT NOT NULL,
-      reference BLOB,
+      unit TEXT,
+      expression TEXT,
+      rollup_reference BLOB,
+      rollup_attribute BLOB,
+      aggregate TEXT,
+      backlink_reference BLOB,
       quantity TEXT NOT NULL,
+      constraints TEXT,
+      default_value TEXT,
+      is_unique INTEGER NOT NULL DEFAULT 0,
       UNIQUE(entity, name),
-      FOREIGN KEY(reference) REFERENCES entity_schema(id) ON DELETE CASCADE,
+      FOREIGN KEY(rollup_reference) REFERENCES attribute_schema(id) ON DELETE CASCADE,
+      FOREIGN KEY(rollup_attribute) REFERENCES attribute_schema(id) ON DELETE CASCADE,
+      FOREIGN KEY(backlink_reference) REFERENCES attribute_schema(id) ON DELETE CASCADE,
       FOREIGN KEY(entity) REFERENCES entity_schema(id) ON DELETE CASCADE
       ",
     )?;
@@ -104,6 +198,14 @@ fn initial(tx: &Transaction) -> Result<()> {
         (),
     )?;
 
+    // Holds attributes whose values are being moved between tables or removed
+    // with the attribute, which suspends the required and minimum item checks
+    // while the old rows go
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS attribute_migration (schema BLOB NOT NULL);",
+        (),
+    )?;
+
     create_table(
         tx,
         "entity",
@@ -126,12 +228,120 @@ fn initial(tx: &Transaction) -> Result<()> {
     build_attr(&tx, "text", "TEXT", "")?;
     build_attr(&tx, "integer", "INTEGER", "")?;
     build_attr(&tx, "number", "REAL", "")?;
+    build_attr(&tx, "date", "TEXT", "")?;
+    build_attr(&tx, "boolean", "INTEGER", "CHECK (value IN (0, 1)),")?;
+    build_attr(&tx, "url", "TEXT", "host TEXT,")?;
+
+    create_table(
+        tx,
+        "blob",
+        "
+        hash TEXT NOT NULL UNIQUE,
+        size INTEGER NOT NULL
+      ",
+    )?;
+
+    build_attr(
+        &tx,
+        "attachment",
+        "TEXT",
+        "filename TEXT NOT NULL,
+        mime TEXT NOT NULL,
+        FOREIGN KEY(value) REFERENCES blob(hash),",
+    )?;
+
+    // The value keeps the original offset, the timestamp is the instant in
+    // UTC milliseconds and is what range queries run against
+    build_attr(&tx, "datetime", "TEXT", "timestamp INTEGER NOT NULL,")?;
+
+    // The value is the latitude, both coordinates are in degrees
+    build_attr(
+        &tx,
+        "location",
+        "REAL",
+        "longitude REAL NOT NULL,
+        label TEXT,
+        CHECK (value BETWEEN -90 AND 90),
+        CHECK (longitude BETWEEN -180 AND 180),",
+    )?;
+
+    tx.execute(
+        "CREATE INDEX IF NOT EXISTS idx_location_schema_coordinates ON location_attribute (schema, value, longitude);",
+        (),
+    )?;
+
+    tx.execute(
+        "CREATE INDEX IF NOT EXISTS idx_date_schema_value ON date_attribute (schema, value);",
+        (),
+    )?;
+
+    tx.execute(
+        "CREATE INDEX IF NOT EXISTS idx_url_host ON url_attribute (host);",
+        (),
+    )?;
+
+    tx.execute(
+        "CREATE INDEX IF NOT EXISTS idx_datetime_schema_timestamp ON datetime_attribute (schema, timestamp);",
+        (),
+    )?;
+
+    create_table(
+        tx,
+        "select_option",
+        "
+        schema BLOB NOT NULL,
+        value TEXT NOT NULL,
+        position INTEGER NOT NULL,
+        UNIQUE(schema, value),
+        FOREIGN KEY(schema) REFERENCES attribute_schema(id) ON DELETE CASCADE
+      ",
+    )?;
+
+    // Values reference their option by name, so renaming an option cascades
+    // to every stored value
+    build_attr(
+        &tx,
+        "select",
+        "TEXT",
+        "UNIQUE(entity, schema, value),
+        FOREIGN KEY(schema, value) REFERENCES select_option(schema, value) ON UPDATE CASCADE,",
+    )?;
+
+    // The schemas a reference may point at, with none meaning any entity
+    create_table(
+        tx,
+        "reference_target",
+        "
+        schema BLOB NOT NULL,
+        target BLOB NOT NULL,
+        position INTEGER NOT NULL,
+        UNIQUE(schema, target),
+        FOREIGN KEY(schema) REFERENCES attribute_schema(id) ON DELETE CASCADE,
+        FOREIGN KEY(target) REFERENCES entity_schema(id)
+      ",
+    )?;
+
+    // Properties each link of the reference carries, stored on the link as a
+    // JSON object
+    create_table(
+        tx,
+        "reference_edge",
+        "
+        schema BLOB NOT NULL,
+        name TEXT NOT NULL,
+        type TEXT NOT NULL,
+        position INTEGER NOT NULL,
+        UNIQUE(schema, name),
+        FOREIGN KEY(schema) REFERENCES attribute_schema(id) ON DELETE CASCADE
+      ",
+    )?;
 
     build_attr(
         &tx,
         "reference",
         "BLOB",
-        "FOREIGN KEY(value) REFERENCES entity(id),",
+        "properties TEXT,
+        FOREIGN KEY(value) REFERENCES entity(id),",
     )?;
 
     build_attr(
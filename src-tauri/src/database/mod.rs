@@ -3,12 +3,14 @@ use rusqlite::Transaction;
 pub mod attribute;
 pub mod attribute_schema;
 pub mod attribute_type;
+pub mod blob;
 pub mod entity;
 pub mod entity_schema;
 #[cfg(test)]
 mod entity_test;
 pub mod migration;
 mod response_map;
+pub mod schema_document;
 mod test;
 
 pub trait New<T> {
@@ -0,0 +1,659 @@
+use rusqlite::{params, Error, OptionalExtension, Result, Transaction};
+
+use crate::{
+    database::{
+        attribute_schema::{GetSchemaMap, RawAttributeSchema, SchemaMap},
+        Get, New,
+    },
+    models::{
+        attribute_schema::{AttributeSchema, AttributeSchemaId, CreateAttributeSchema},
+        attribute_type::{
+            AttributeType, BacklinkAttribute, CreateAttributeType, CreateReferenceAttribute,
+            RollupAttribute,
+        },
+        entity_schema::{CreateEntitySchema, EntitySchema, EntitySchemaId},
+        schema_document::{
+            AttributeDefinition, DocumentFormat, ImportReport, SchemaDefinition, SchemaDocument,
+            TypeDefinition,
+        },
+    },
+    utils::get_timestamp,
+};
+
+fn schema_id(tx: &Transaction, name: &str) -> Result<Option<EntitySchemaId>> {
+    tx.query_row(
+        "SELECT id FROM entity_schema WHERE name = ?1",
+        params![name],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+fn require_schema(tx: &Transaction, name: &str) -> Result<EntitySchemaId> {
+    schema_id(tx, name)?.ok_or_else(|| Error::ModuleError(format!("Unknown schema {name}")))
+}
+
+fn find_attribute(schema: &SchemaMap, name: &str) -> Result<AttributeSchemaId> {
+    schema
+        .values()
+        .find(|attribute| attribute.name == name)
+        .map(|attribute| attribute.id.clone())
+        .ok_or_else(|| Error::ModuleError(format!("Unknown attribute {name}")))
+}
+
+// The schema and name of an attribute
+fn attribute_path(tx: &Transaction, id: &AttributeSchemaId) -> Result<(String, String)> {
+    tx.query_row(
+        "SELECT s.name, a.name FROM attribute_schema a INNER JOIN entity_schema s ON a.entity = s.id
+          WHERE a.id = ?1",
+        params![id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+}
+
+fn definition(tx: &Transaction, schema: &RawAttributeSchema) -> Result<AttributeDefinition> {
+    let attr_type = match &schema.attr_type {
+        AttributeType::Simple(simple) => TypeDefinition::Simple(*simple),
+        AttributeType::Reference(reference) => TypeDefinition::Reference {
+            targets: reference
+                .targets
+                .iter()
+                .map(|target| target.name.clone())
+                .collect(),
+            edges: reference.edges.clone(),
+        },
+        AttributeType::Select(select) => TypeDefinition::Select {
+            options: select.options.clone(),
+        },
+        AttributeType::Measure(measure) => TypeDefinition::Measure(measure.clone()),
+        AttributeType::Computed(computed) => TypeDefinition::Computed(computed.clone()),
+        AttributeType::Rollup(rollup) => TypeDefinition::Rollup {
+            reference: attribute_path(tx, &rollup.reference)?.1,
+            attribute: match &rollup.attribute {
+                Some(attribute) => Some(attribute_path(tx, attribute)?.1),
+                None => None,
+            },
+            aggregate: rollup.aggregate,
+        },
+        AttributeType::Backlink(backlink) => {
+            let (schema, reference) = attribute_path(tx, &backlink.reference)?;
+            TypeDefinition::Backlink { schema, reference }
+        }
+    };
+
+    Ok(AttributeDefinition {
+        name: schema.name.clone(),
+        quantity: schema.quantity.clone(),
+        constraints: schema.constraints.clone(),
+        default: schema.default.clone(),
+        unique: schema.unique,
+        attr_type,
+    })
+}
+
+// The attributes a schema defines itself, leaving out inherited ones
+fn own_attributes(tx: &Transaction, schema: &EntitySchemaId) -> Result<Vec<RawAttributeSchema>> {
+    let mut statement =
+        tx.prepare("SELECT id FROM attribute_schema WHERE entity = ?1 ORDER BY name")?;
+    let ids = statement
+        .query_map(params![schema], |row| row.get(0))?
+        .collect::<Result<Vec<AttributeSchemaId>>>()?;
+
+    ids.iter()
+        .map(|id| RawAttributeSchema::get(tx, id))
+        .collect()
+}
+
+// Schemas and attributes are sorted by name so exports from different vaults
+// line up and diff cleanly
+pub fn export_schemas(tx: &Transaction) -> Result<SchemaDocument> {
+    let mut statement = tx.prepare(
+        "SELECT s.id, s.name, p.name FROM entity_schema s LEFT JOIN entity_schema p ON s.parent = p.id
+          ORDER BY s.name",
+    )?;
+    let schemas = statement
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, EntitySchemaId>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>>>()?;
+
+    let schemas = schemas
+        .into_iter()
+        .map(|(id, name, parent)| {
+            let attributes = own_attributes(tx, &id)?
+                .iter()
+                .map(|attribute| definition(tx, attribute))
+                .collect::<Result<_>>()?;
+
+            Ok(SchemaDefinition {
+                name,
+                parent,
+                display: id.display(tx)?,
+                attributes,
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(SchemaDocument { schemas })
+}
+
+fn create_attribute(
+    tx: &Transaction,
+    schema: &EntitySchemaId,
+    attribute: &AttributeDefinition,
+) -> Result<CreateAttributeSchema> {
+    let attr_type = match &attribute.attr_type {
+        TypeDefinition::Simple(simple) => CreateAttributeType::Simple(*simple),
+        TypeDefinition::Reference { targets, edges } => {
+            CreateAttributeType::Reference(CreateReferenceAttribute {
+                targets: targets
+                    .iter()
+                    .map(|target| require_schema(tx, target))
+                    .collect::<Result<_>>()?,
+                inverse: None,
+                edges: edges.clone(),
+            })
+        }
+        TypeDefinition::Select { options } => CreateAttributeType::Select {
+            options: options.clone(),
+        },
+        TypeDefinition::Measure(measure) => CreateAttributeType::Measure(measure.clone()),
+        TypeDefinition::Computed(computed) => CreateAttributeType::Computed(computed.clone()),
+        TypeDefinition::Rollup {
+            reference,
+            attribute,
+            aggregate,
+        } => {
+            let reference = find_attribute(&RawAttributeSchema::get_map(tx, schema)?, reference)?;
+            let attribute = match attribute {
+                Some(attribute) => Some(rollup_attribute(tx, &reference, attribute)?),
+                None => None,
+            };
+
+            CreateAttributeType::Rollup(RollupAttribute {
+                reference,
+                attribute,
+                aggregate: *aggregate,
+            })
+        }
+        TypeDefinition::Backlink { schema, reference } => {
+            let schema = require_schema(tx, schema)?;
+            CreateAttributeType::Backlink(BacklinkAttribute {
+                reference: find_attribute(&RawAttributeSchema::get_map(tx, &schema)?, reference)?,
+            })
+        }
+    };
+
+    Ok(CreateAttributeSchema {
+        entity: schema.clone(),
+        name: attribute.name.clone(),
+        attr_type,
+        quantity: attribute.quantity.clone(),
+        constraints: attribute.constraints.clone(),
+        default: attribute.default.clone(),
+        unique: attribute.unique,
+    })
+}
+
+// Rollups name the aggregated attribute as seen from the reference's targets
+fn rollup_attribute(
+    tx: &Transaction,
+    reference: &AttributeSchemaId,
+    name: &str,
+) -> Result<AttributeSchemaId> {
+    let mut statement =
+        tx.prepare("SELECT target FROM reference_target WHERE schema = ?1 ORDER BY position")?;
+    let targets = statement
+        .query_map(params![reference], |row| row.get(0))?
+        .collect::<Result<Vec<EntitySchemaId>>>()?;
+
+    for target in targets {
+        if let Ok(id) = find_attribute(&RawAttributeSchema::get_map(tx, &target)?, name) {
+            return Ok(id);
+        }
+    }
+
+    Err(Error::ModuleError(format!("Unknown attribute {name}")))
+}
+
+// Attributes are created once everything they can point at exists
+fn phase(attr_type: &TypeDefinition) -> usize {
+    match attr_type {
+        TypeDefinition::Simple(..)
+        | TypeDefinition::Reference { .. }
+        | TypeDefinition::Select { .. }
+        | TypeDefinition::Measure(..) => 0,
+        TypeDefinition::Backlink { .. } => 1,
+        TypeDefinition::Rollup { .. } => 2,
+        TypeDefinition::Computed(..) => 3,
+    }
+}
+
+// Applies the changes to an existing attribute that leave its values alone,
+// which are new or reordered select options and a different default
+fn update_attribute(
+    tx: &Transaction,
+    id: &AttributeSchemaId,
+    path: &str,
+    wanted: &AttributeDefinition,
+    report: &mut ImportReport,
+) -> Result<()> {
+    let current = definition(tx, &RawAttributeSchema::get(tx, id)?)?;
+    if &current == wanted {
+        return Ok(());
+    }
+
+    let mut updated = false;
+    let mut destructive = |reason: &str| report.destructive.push(format!("{path}: {reason}"));
+
+    match (&current.attr_type, &wanted.attr_type) {
+        (TypeDefinition::Select { options: have }, TypeDefinition::Select { options: want })
+            if have.iter().all(|option| want.contains(option)) =>
+        {
+            for option in want.iter().filter(|option| !have.contains(option)) {
+                id.add_option(tx, option)?;
+                updated = true;
+            }
+
+            // New options are appended, so the order is only set when the
+            // document places them elsewhere or moves existing ones
+            let appended = have
+                .iter()
+                .chain(want.iter().filter(|option| !have.contains(option)));
+            if !appended.eq(want.iter()) {
+                id.reorder_options(tx, want)?;
+                updated = true;
+            }
+        }
+        (TypeDefinition::Select { .. }, TypeDefinition::Select { .. }) => {
+            destructive("select options removed")
+        }
+        (have, want) if have != want => destructive("type differs"),
+        _ => {}
+    }
+
+    if current.quantity != wanted.quantity {
+        destructive("quantity differs");
+    }
+
+    if current.constraints != wanted.constraints {
+        destructive("constraints differ");
+    }
+
+    if current.unique != wanted.unique {
+        destructive("unique differs");
+    }
+
+    if current.default != wanted.default {
+        let mut schema = RawAttributeSchema::get(tx, id)?;
+        schema.default = wanted.default.clone();
+
+        if let Some(default) = &schema.default {
+            default.check_schema(&schema.attr_type, &current.quantity, schema.unique)?;
+        }
+
+        let owner: EntitySchemaId = tx.query_row(
+            "SELECT entity FROM attribute_schema WHERE id = ?1",
+            params![id],
+            |row| row.get(0),
+        )?;
+        schema.check_default(tx, &owner)?;
+
+        tx.execute(
+            "UPDATE attribute_schema SET default_value = ?1, updated = ?2 WHERE id = ?3",
+            params![wanted.default, get_timestamp(), id],
+        )?;
+        updated = true;
+    }
+
+    if updated {
+        report.updated.push(path.to_string());
+    }
+
+    Ok(())
+}
+
+// Brings the database in line with the document. Missing schemas and
+// attributes are created and safe changes applied, while anything that could
+// lose data is only reported
+pub fn import_schemas(tx: &Transaction, document: &SchemaDocument) -> Result<ImportReport> {
+    let mut report = ImportReport::default();
+
+    // Parents are handled before the schemas extending them
+    let mut pending: Vec<&SchemaDefinition> = document.schemas.iter().collect();
+    while !pending.is_empty() {
+        let (ready, waiting): (Vec<_>, Vec<_>) = pending.clone().into_iter().partition(|schema| {
+            schema
+                .parent
+                .as_ref()
+                .is_none_or(|parent| !pending.iter().any(|pending| &pending.name == parent))
+        });
+
+        if ready.is_empty() {
+            return Err(Error::ModuleError(
+                "Schema parents form a cycle".to_string(),
+            ));
+        }
+
+        for schema in ready {
+            let parent = match &schema.parent {
+                Some(parent) => Some(require_schema(tx, parent)?),
+                None => None,
+            };
+
+            match schema_id(tx, &schema.name)? {
+                None => {
+                    EntitySchema::new(
+                        tx,
+                        CreateEntitySchema {
+                            name: schema.name.clone(),
+                            parent,
+                        },
+                    )?;
+                    report.created.push(schema.name.clone());
+                }
+                Some(id) => {
+                    let current: Option<EntitySchemaId> = tx.query_row(
+                        "SELECT parent FROM entity_schema WHERE id = ?1",
+                        params![id],
+                        |row| row.get(0),
+                    )?;
+
+                    if current != parent {
+                        report
+                            .destructive
+                            .push(format!("{}: parent differs", schema.name));
+                    }
+                }
+            }
+        }
+
+        pending = waiting;
+    }
+
+    for phase_index in 0..4 {
+        for schema in &document.schemas {
+            let schema_id = require_schema(tx, &schema.name)?;
+
+            for attribute in &schema.attributes {
+                if phase(&attribute.attr_type) != phase_index {
+                    continue;
+                }
+
+                let path = format!("{}.{}", schema.name, attribute.name);
+                let existing: Option<AttributeSchemaId> = tx
+                    .query_row(
+                        "SELECT id FROM attribute_schema WHERE entity = ?1 AND name = ?2",
+                        params![schema_id, attribute.name],
+                        |row| row.get(0),
+                    )
+                    .optional()?;
+
+                match existing {
+                    Some(id) => update_attribute(tx, &id, &path, attribute, &mut report)?,
+                    None => {
+                        AttributeSchema::new(tx, create_attribute(tx, &schema_id, attribute)?)?;
+                        report.created.push(path);
+                    }
+                }
+            }
+        }
+    }
+
+    // Displays name attributes, so are set once every attribute exists
+    for schema in &document.schemas {
+        let schema_id = require_schema(tx, &schema.name)?;
+        let current = schema_id.display(tx)?;
+
+        if current == schema.display {
+            continue;
+        }
+
+        match (&current, &schema.display) {
+            (Some(..), None) => report
+                .destructive
+                .push(format!("{}: display not in the document", schema.name)),
+            _ => {
+                schema_id.set_display(tx, schema.display.clone())?;
+                if !report.created.contains(&schema.name) {
+                    report.updated.push(schema.name.clone());
+                }
+            }
+        }
+    }
+
+    for current in export_schemas(tx)?.schemas {
+        let Some(schema) = document
+            .schemas
+            .iter()
+            .find(|schema| schema.name == current.name)
+        else {
+            report
+                .destructive
+                .push(format!("{}: not in the document", current.name));
+            continue;
+        };
+
+        for attribute in current.attributes {
+            if !schema
+                .attributes
+                .iter()
+                .any(|wanted| wanted.name == attribute.name)
+            {
+                report.destructive.push(format!(
+                    "{}.{}: not in the document",
+                    current.name, attribute.name
+                ));
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+impl SchemaDocument {
+    pub fn to_text(&self, format: DocumentFormat) -> Result<String> {
+        let text = match format {
+            DocumentFormat::Json => serde_json::to_string_pretty(self)
+                .map(|text| text + "\n")
+                .map_err(|err| err.to_string()),
+            DocumentFormat::Toml => toml::to_string_pretty(self).map_err(|err| err.to_string()),
+        };
+
+        text.map_err(Error::ModuleError)
+    }
+
+    pub fn from_text(text: &str, format: DocumentFormat) -> Result<Self> {
+        let document = match format {
+            DocumentFormat::Json => serde_json::from_str(text).map_err(|err| err.to_string()),
+            DocumentFormat::Toml => toml::from_str(text).map_err(|err| err.to_string()),
+        };
+
+        document.map_err(Error::ModuleError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Error;
+
+    use crate::{
+        database::test::test_util::setup,
+        models::schema_document::{DocumentFormat, ImportReport, SchemaDocument, TypeDefinition},
+    };
+
+    use super::{export_schemas, import_schemas};
+
+    const DOCUMENT: &str = r#"
+[[schemas]]
+name = "Person"
+display = { Attribute = "Name" }
+
+[[schemas.attributes]]
+name = "Name"
+quantity = "Required"
+unique = true
+attr_type = { Simple = "Text" }
+
+[[schemas]]
+name = "Author"
+parent = "Person"
+
+[[schemas.attributes]]
+name = "Books"
+quantity = "List"
+attr_type = { Backlink = { schema = "Book", reference = "Writer" } }
+
+[[schemas]]
+name = "Book"
+
+[[schemas.attributes]]
+name = "Genre"
+quantity = "Optional"
+attr_type = { Select = { options = ["History", "Fiction"] } }
+
+[[schemas.attributes]]
+name = "Pages"
+quantity = "Optional"
+constraints = { min = 1.0 }
+attr_type = { Simple = "Integer" }
+
+[[schemas.attributes]]
+name = "Spread"
+quantity = "Optional"
+attr_type = { Computed = { expression = "Pages * 2" } }
+
+[[schemas.attributes]]
+name = "Writer"
+quantity = "Optional"
+attr_type = { Reference = { targets = ["Author"], edges = [{ name = "role", attr_type = "Text" }] } }
+
+[[schemas.attributes]]
+name = "Writer names"
+quantity = "Optional"
+attr_type = { Rollup = { reference = "Writer", attribute = "Name", aggregate = "Distinct" } }
+"#;
+
+    fn document() -> SchemaDocument {
+        SchemaDocument::from_text(DOCUMENT, DocumentFormat::Toml).unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        let report = import_schemas(&tx, &document()).unwrap();
+        assert_eq!(report.created.len(), 10);
+        assert!(report.destructive.is_empty());
+
+        let exported = export_schemas(&tx).unwrap();
+        let toml = exported.to_text(DocumentFormat::Toml).unwrap();
+        let json = exported.to_text(DocumentFormat::Json).unwrap();
+
+        // Importing into another vault exports the same text
+        let mut other = setup();
+        let other_tx = other.transaction().unwrap();
+        import_schemas(
+            &other_tx,
+            &SchemaDocument::from_text(&json, DocumentFormat::Json).unwrap(),
+        )
+        .unwrap();
+        let reexported = export_schemas(&other_tx).unwrap();
+        assert_eq!(reexported.to_text(DocumentFormat::Toml).unwrap(), toml);
+        assert_eq!(reexported.to_text(DocumentFormat::Json).unwrap(), json);
+
+        // Nothing to do when the database already matches
+        assert_eq!(
+            import_schemas(
+                &tx,
+                &SchemaDocument::from_text(&toml, DocumentFormat::Toml).unwrap()
+            ),
+            Ok(ImportReport::default())
+        );
+    }
+
+    #[test]
+    fn safe_and_destructive_changes() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        import_schemas(&tx, &document()).unwrap();
+
+        let changed = DOCUMENT
+            .replace(r#"["History", "Fiction"]"#, r#"["Poetry", "Fiction", "History"]"#)
+            .replace(
+                r#"display = { Attribute = "Name" }"#,
+                r#"display = { Template = "Name: {Name}" }"#,
+            )
+            .replace(
+                "name = \"Pages\"\nquantity = \"Optional\"",
+                "name = \"Pages\"\nquantity = \"List\"",
+            )
+            .replace(
+                "[[schemas.attributes]]\nname = \"Spread\"\nquantity = \"Optional\"\nattr_type = { Computed = { expression = \"Pages * 2\" } }\n",
+                "",
+            )
+            + "\n[[schemas]]\nname = \"Series\"\n";
+
+        assert_eq!(
+            import_schemas(
+                &tx,
+                &SchemaDocument::from_text(&changed, DocumentFormat::Toml).unwrap()
+            ),
+            Ok(ImportReport {
+                created: vec!["Series".to_string()],
+                updated: vec!["Book.Genre".to_string(), "Person".to_string()],
+                destructive: vec![
+                    "Book.Pages: quantity differs".to_string(),
+                    "Book.Spread: not in the document".to_string(),
+                ],
+            })
+        );
+
+        let exported = export_schemas(&tx).unwrap();
+        let genre = exported
+            .schemas
+            .iter()
+            .flat_map(|schema| &schema.attributes)
+            .find(|attribute| attribute.name == "Genre")
+            .unwrap();
+        assert_eq!(
+            genre.attr_type,
+            TypeDefinition::Select {
+                options: vec![
+                    "Poetry".to_string(),
+                    "Fiction".to_string(),
+                    "History".to_string()
+                ]
+            }
+        );
+    }
+
+    #[test]
+    fn invalid_default() {
+        let mut conn = setup();
+        let tx = conn.transaction().unwrap();
+
+        import_schemas(&tx, &document()).unwrap();
+
+        let changed = DOCUMENT.replace(
+            "constraints = { min = 1.0 }",
+            "constraints = { min = 1.0 }\ndefault = { Value = 0 }",
+        );
+        assert_eq!(
+            import_schemas(
+                &tx,
+                &SchemaDocument::from_text(&changed, DocumentFormat::Toml).unwrap()
+            ),
+            Err(Error::ModuleError(
+                "Invalid default for Pages: Attribute Pages failed min: must be at least 1"
+                    .to_string()
+            ))
+        );
+    }
+}
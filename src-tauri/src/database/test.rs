@@ -7,12 +7,12 @@ pub mod test_util {
         database::{migration::migrate, New},
         models::{
             attribute_schema::{
-                AttributeSchema, AttributeSchemaId, CreateAttributeSchema, Quantity,
+                AttributeSchema, AttributeSchemaId, Constraints, CreateAttributeSchema,
+                DefaultValue, Quantity,
             },
             attribute_type::{CreateAttributeType, CreateReferenceAttribute, SimpleAttributeType},
             entity_schema::{CreateEntitySchema, EntitySchema, EntitySchemaId},
         },
-        utils::get_timestamp,
     };
 
     pub fn setup() -> Connection {
@@ -27,20 +27,28 @@ pub mod test_util {
 
     pub struct ESD {
         pub name: String,
+        pub parent: Option<EntitySchemaId>,
     }
 
     impl Default for ESD {
         fn default() -> Self {
             Self {
                 name: "Foo".to_string(),
+                parent: None,
             }
         }
     }
 
     impl ESD {
         pub fn create(self, tx: &Transaction) -> EntitySchemaId {
-            let schema = EntitySchema::new(&tx, CreateEntitySchema { name: self.name })
-                .expect("Unable to create entity");
+            let schema = EntitySchema::new(
+                &tx,
+                CreateEntitySchema {
+                    name: self.name,
+                    parent: self.parent,
+                },
+            )
+            .expect("Unable to create entity");
 
             schema.id
         }
@@ -50,6 +58,12 @@ pub mod test_util {
             self
         }
 
+        #[allow(unused)]
+        pub fn parent(mut self, parent: &EntitySchemaId) -> Self {
+            self.parent = Some(parent.clone());
+            self
+        }
+
         pub fn create_default(tx: &Transaction) -> EntitySchemaId {
             let def = Self::default();
             def.create(&tx)
@@ -60,6 +74,9 @@ pub mod test_util {
         name: String,
         attr_type: SimpleAttributeType,
         quantity: Quantity,
+        constraints: Constraints,
+        default: Option<DefaultValue>,
+        unique: bool,
     }
 
     impl ASD {
@@ -70,6 +87,9 @@ pub mod test_util {
                     entity: entity.clone(),
                     name: self.name,
                     quantity: self.quantity,
+                    constraints: self.constraints,
+                    default: self.default,
+                    unique: self.unique,
                     attr_type: CreateAttributeType::Simple(self.attr_type),
                 },
             )
@@ -98,6 +118,24 @@ pub mod test_util {
             self.quantity = quantity;
             self
         }
+
+        #[allow(unused)]
+        pub fn constraints(mut self, constraints: Constraints) -> Self {
+            self.constraints = constraints;
+            self
+        }
+
+        #[allow(unused)]
+        pub fn default_value(mut self, default: DefaultValue) -> Self {
+            self.default = Some(default);
+            self
+        }
+
+        #[allow(unused)]
+        pub fn unique(mut self) -> Self {
+            self.unique = true;
+            self
+        }
     }
 
     impl Default for ASD {
@@ -106,6 +144,9 @@ pub mod test_util {
                 name: "Foo".to_string(),
                 attr_type: SimpleAttributeType::Text,
                 quantity: Quantity::Required,
+                constraints: Constraints::default(),
+                default: None,
+                unique: false,
             }
         }
     }
@@ -152,43 +193,25 @@ pub mod test_util {
             parent: &EntitySchemaId,
             child: &EntitySchemaId,
         ) -> AttributeSchemaId {
-            {
-            let tx: &Transaction = &tx;
-            let data = CreateAttributeSchema {
+            let new_attribute = AttributeSchema::new(
+                &tx,
+                CreateAttributeSchema {
                     entity: parent.clone(),
                     name: self.name,
                     quantity: self.quantity,
-                    attr_type: CreateAttributeType::Reference(
-                        CreateReferenceAttribute { id: child.clone() },
-                    ),
-                };
-            let reference = data.attr_type.get_ref();
-
-            let new_attribute = AttributeSchema {
-                id: AttributeSchemaId::new(),
-                name: data.name,
-                quantity: data.quantity,
-                attr_type: data.attr_type.get_full(tx).unwrap(),
-            };
-
-            let created_at = get_timestamp();
-
-            tx.execute(
-                "INSERT INTO attribute_schema (id, entity, name, type, reference, quantity, created, updated) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7)",
-                (
-                    &new_attribute.id,
-                    data.entity,
-                    &new_attribute.name,
-                    &data.attr_type,
-                    &reference,
-                    &new_attribute.quantity,
-                    created_at
-                ),
-            ).unwrap();
-
-            new_attribute
-        }
-        .id
+                    constraints: Constraints::default(),
+                    default: None,
+                    unique: false,
+                    attr_type: CreateAttributeType::Reference(CreateReferenceAttribute {
+                        targets: vec![child.clone()],
+                        inverse: None,
+                        edges: Vec::new(),
+                    }),
+                },
+            )
+            .expect("Failed to create reference");
+
+            new_attribute.id
         }
     }
 
@@ -0,0 +1,547 @@
+use std::{collections::HashMap, iter::Peekable, str::Chars};
+
+use rusqlite::Error;
+use serde_json::Value;
+
+// A small expression language for computed attributes. Attributes of the
+// same entity are referenced by name, either bare (`pages`) or quoted with
+// backticks when the name contains spaces (`Page count`)
+#[derive(Debug, PartialEq)]
+pub enum Expr {
+    Text(String),
+    Number(f64),
+    Attribute(String),
+    Negate(Box<Expr>),
+    Binary(Operator, Box<Expr>, Box<Expr>),
+    Call(Function, Vec<Expr>),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Function {
+    Concat,
+    Upper,
+    Lower,
+    Round,
+    Abs,
+    Min,
+    Max,
+    Coalesce,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ValueType {
+    Text,
+    Number,
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Text(String),
+    Number(f64),
+    Name(String),
+    Operator(Operator),
+    Open,
+    Close,
+    Comma,
+}
+
+fn invalid(message: &str) -> Error {
+    Error::ModuleError(format!("Invalid expression: {message}"))
+}
+
+fn tokenize(source: &str) -> rusqlite::Result<Vec<Token>> {
+    let mut chars = source.chars().peekable();
+    let mut tokens = Vec::new();
+
+    while let Some(&c) = chars.peek() {
+        let token = match c {
+            c if c.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            '0'..='9' | '.' => {
+                let mut number = String::new();
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_ascii_digit() || c == '.') {
+                        break;
+                    }
+                    number.push(c);
+                    chars.next();
+                }
+
+                match number.parse() {
+                    Ok(number) => Token::Number(number),
+                    Err(_) => return Err(invalid(&format!("{number} is not a number"))),
+                }
+            }
+            '"' => {
+                chars.next();
+                Token::Text(read_until(&mut chars, '"')?)
+            }
+            '`' => {
+                chars.next();
+                Token::Name(read_until(&mut chars, '`')?)
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut name = String::new();
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_alphanumeric() || c == '_') {
+                        break;
+                    }
+                    name.push(c);
+                    chars.next();
+                }
+                Token::Name(name)
+            }
+            _ => {
+                chars.next();
+                match c {
+                    '+' => Token::Operator(Operator::Add),
+                    '-' => Token::Operator(Operator::Subtract),
+                    '*' => Token::Operator(Operator::Multiply),
+                    '/' => Token::Operator(Operator::Divide),
+                    '(' => Token::Open,
+                    ')' => Token::Close,
+                    ',' => Token::Comma,
+                    c => return Err(invalid(&format!("unexpected character {c}"))),
+                }
+            }
+        };
+
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+fn read_until(chars: &mut Peekable<Chars>, end: char) -> rusqlite::Result<String> {
+    let mut value = String::new();
+    loop {
+        match chars.next() {
+            Some('\\') => match chars.next() {
+                Some(c) => value.push(c),
+                None => return Err(invalid("unterminated escape")),
+            },
+            Some(c) if c == end => return Ok(value),
+            Some(c) => value.push(c),
+            None => return Err(invalid(&format!("missing closing {end}"))),
+        }
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<&Token> {
+        self.position += 1;
+        self.tokens.get(self.position - 1)
+    }
+
+    fn expect(&mut self, token: Token) -> rusqlite::Result<()> {
+        match self.next() {
+            Some(next) if next == &token => Ok(()),
+            _ => Err(invalid(&format!("expected {token:?}"))),
+        }
+    }
+
+    fn expression(&mut self) -> rusqlite::Result<Expr> {
+        let mut left = self.term()?;
+        while let Some(Token::Operator(op @ (Operator::Add | Operator::Subtract))) = self.peek() {
+            let op = *op;
+            self.next();
+            left = Expr::Binary(op, Box::new(left), Box::new(self.term()?));
+        }
+        Ok(left)
+    }
+
+    fn term(&mut self) -> rusqlite::Result<Expr> {
+        let mut left = self.unary()?;
+        while let Some(Token::Operator(op @ (Operator::Multiply | Operator::Divide))) = self.peek()
+        {
+            let op = *op;
+            self.next();
+            left = Expr::Binary(op, Box::new(left), Box::new(self.unary()?));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> rusqlite::Result<Expr> {
+        if let Some(Token::Operator(Operator::Subtract)) = self.peek() {
+            self.next();
+            return Ok(Expr::Negate(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> rusqlite::Result<Expr> {
+        match self.next() {
+            Some(Token::Number(number)) => Ok(Expr::Number(*number)),
+            Some(Token::Text(text)) => Ok(Expr::Text(text.clone())),
+            Some(Token::Open) => {
+                let expr = self.expression()?;
+                self.expect(Token::Close)?;
+                Ok(expr)
+            }
+            Some(Token::Name(name)) => {
+                let name = name.clone();
+                if self.peek() != Some(&Token::Open) {
+                    return Ok(Expr::Attribute(name));
+                }
+
+                self.next();
+                let function = Function::from_name(&name)?;
+
+                let mut args = Vec::new();
+                if self.peek() == Some(&Token::Close) {
+                    self.next();
+                } else {
+                    loop {
+                        args.push(self.expression()?);
+                        match self.next() {
+                            Some(Token::Comma) => continue,
+                            Some(Token::Close) => break,
+                            _ => return Err(invalid("expected , or )")),
+                        }
+                    }
+                }
+
+                Ok(Expr::Call(function, args))
+            }
+            Some(token) => Err(invalid(&format!("unexpected {token:?}"))),
+            None => Err(invalid("unexpected end of expression")),
+        }
+    }
+}
+
+pub fn parse(source: &str) -> rusqlite::Result<Expr> {
+    let mut parser = Parser {
+        tokens: tokenize(source)?,
+        position: 0,
+    };
+
+    let expr = parser.expression()?;
+
+    match parser.peek() {
+        None => Ok(expr),
+        Some(token) => Err(invalid(&format!("unexpected {token:?}"))),
+    }
+}
+
+impl Function {
+    fn from_name(name: &str) -> rusqlite::Result<Self> {
+        match name {
+            "concat" => Ok(Function::Concat),
+            "upper" => Ok(Function::Upper),
+            "lower" => Ok(Function::Lower),
+            "round" => Ok(Function::Round),
+            "abs" => Ok(Function::Abs),
+            "min" => Ok(Function::Min),
+            "max" => Ok(Function::Max),
+            "coalesce" => Ok(Function::Coalesce),
+            _ => Err(invalid(&format!("unknown function {name}"))),
+        }
+    }
+
+    fn check(&self, args: &[ValueType]) -> rusqlite::Result<ValueType> {
+        let count = |min: usize, max: usize| {
+            if args.len() < min || args.len() > max {
+                Err(invalid(&format!("wrong number of arguments to {self:?}")))
+            } else {
+                Ok(())
+            }
+        };
+
+        let all = |expected: ValueType| {
+            if args.iter().all(|arg| arg == &expected) {
+                Ok(expected)
+            } else {
+                Err(invalid(&format!("{self:?} expects {expected:?} arguments")))
+            }
+        };
+
+        match self {
+            Function::Concat => {
+                count(1, usize::MAX)?;
+                Ok(ValueType::Text)
+            }
+            Function::Upper | Function::Lower => {
+                count(1, 1)?;
+                all(ValueType::Text)
+            }
+            Function::Round => {
+                count(1, 2)?;
+                all(ValueType::Number)
+            }
+            Function::Abs => {
+                count(1, 1)?;
+                all(ValueType::Number)
+            }
+            Function::Min | Function::Max => {
+                count(1, usize::MAX)?;
+                all(ValueType::Number)
+            }
+            Function::Coalesce => {
+                count(1, usize::MAX)?;
+                all(args[0])
+            }
+        }
+    }
+}
+
+impl Expr {
+    // Names of the attributes the expression reads
+    pub fn attributes(&self) -> Vec<&str> {
+        match self {
+            Expr::Text(..) | Expr::Number(..) => vec![],
+            Expr::Attribute(name) => vec![name],
+            Expr::Negate(expr) => expr.attributes(),
+            Expr::Binary(_, left, right) => {
+                let mut names = left.attributes();
+                names.extend(right.attributes());
+                names
+            }
+            Expr::Call(_, args) => args.iter().flat_map(|arg| arg.attributes()).collect(),
+        }
+    }
+
+    // Checks the expression is well typed, given the type of each attribute
+    pub fn check<F>(&self, attribute_type: &F) -> rusqlite::Result<ValueType>
+    where
+        F: Fn(&str) -> rusqlite::Result<ValueType>,
+    {
+        match self {
+            Expr::Text(..) => Ok(ValueType::Text),
+            Expr::Number(..) => Ok(ValueType::Number),
+            Expr::Attribute(name) => attribute_type(name),
+            Expr::Negate(expr) => match expr.check(attribute_type)? {
+                ValueType::Number => Ok(ValueType::Number),
+                ValueType::Text => Err(invalid("cannot negate text")),
+            },
+            Expr::Binary(op, left, right) => {
+                let left = left.check(attribute_type)?;
+                let right = right.check(attribute_type)?;
+
+                match (left, right) {
+                    (ValueType::Number, ValueType::Number) => Ok(ValueType::Number),
+                    _ => Err(invalid(&format!(
+                        "{op:?} expects numbers, use concat to join text"
+                    ))),
+                }
+            }
+            Expr::Call(function, args) => {
+                let mut types = Vec::new();
+                for arg in args {
+                    types.push(arg.check(attribute_type)?);
+                }
+                function.check(&types)
+            }
+        }
+    }
+
+    // Evaluates a checked expression. Missing values are null, and
+    // arithmetic on null gives null
+    pub fn evaluate(&self, values: &HashMap<&str, Value>) -> Value {
+        match self {
+            Expr::Text(text) => Value::String(text.clone()),
+            Expr::Number(number) => Value::from(*number),
+            Expr::Attribute(name) => values.get(name.as_str()).cloned().unwrap_or(Value::Null),
+            Expr::Negate(expr) => match expr.evaluate(values).as_f64() {
+                Some(value) => Value::from(-value),
+                None => Value::Null,
+            },
+            Expr::Binary(op, left, right) => {
+                let (Some(left), Some(right)) = (
+                    left.evaluate(values).as_f64(),
+                    right.evaluate(values).as_f64(),
+                ) else {
+                    return Value::Null;
+                };
+
+                let result = match op {
+                    Operator::Add => left + right,
+                    Operator::Subtract => left - right,
+                    Operator::Multiply => left * right,
+                    Operator::Divide => left / right,
+                };
+
+                number(result)
+            }
+            Expr::Call(function, args) => {
+                let args: Vec<Value> = args.iter().map(|arg| arg.evaluate(values)).collect();
+                function.evaluate(args)
+            }
+        }
+    }
+}
+
+impl Function {
+    fn evaluate(&self, args: Vec<Value>) -> Value {
+        match self {
+            Function::Concat => {
+                let mut result = String::new();
+                for arg in args {
+                    match arg {
+                        Value::String(text) => result.push_str(&text),
+                        Value::Number(value) => {
+                            result.push_str(&format_number(value.as_f64().unwrap_or_default()))
+                        }
+                        _ => {}
+                    }
+                }
+                Value::String(result)
+            }
+            Function::Upper => match &args[0] {
+                Value::String(text) => Value::String(text.to_uppercase()),
+                _ => Value::Null,
+            },
+            Function::Lower => match &args[0] {
+                Value::String(text) => Value::String(text.to_lowercase()),
+                _ => Value::Null,
+            },
+            Function::Round => {
+                let Some(value) = args[0].as_f64() else {
+                    return Value::Null;
+                };
+                let digits = args
+                    .get(1)
+                    .and_then(|digits| digits.as_f64())
+                    .unwrap_or(0.0);
+                let scale = 10f64.powi(digits as i32);
+                number((value * scale).round() / scale)
+            }
+            Function::Abs => match args[0].as_f64() {
+                Some(value) => number(value.abs()),
+                None => Value::Null,
+            },
+            Function::Min | Function::Max => {
+                let values = args.iter().filter_map(|arg| arg.as_f64());
+                let result = match self {
+                    Function::Min => values.reduce(f64::min),
+                    _ => values.reduce(f64::max),
+                };
+                result.map(number).unwrap_or(Value::Null)
+            }
+            Function::Coalesce => args
+                .into_iter()
+                .find(|arg| !arg.is_null())
+                .unwrap_or(Value::Null),
+        }
+    }
+}
+
+// Division by zero gives null rather than infinity, which JSON can't hold
+fn number(value: f64) -> Value {
+    if value.is_finite() {
+        Value::from(value)
+    } else {
+        Value::Null
+    }
+}
+
+fn format_number(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < 1e15 {
+        format!("{}", value as i64)
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn types(name: &str) -> rusqlite::Result<ValueType> {
+        match name {
+            "first" | "last" | "Full name" => Ok(ValueType::Text),
+            "pages" | "pages_read" => Ok(ValueType::Number),
+            _ => Err(Error::ModuleError(format!("Unknown attribute {name}"))),
+        }
+    }
+
+    #[test]
+    fn parse_precedence() {
+        assert_eq!(
+            parse("1 + 2 * -x").unwrap(),
+            Expr::Binary(
+                Operator::Add,
+                Box::new(Expr::Number(1.0)),
+                Box::new(Expr::Binary(
+                    Operator::Multiply,
+                    Box::new(Expr::Number(2.0)),
+                    Box::new(Expr::Negate(Box::new(Expr::Attribute("x".to_string()))))
+                ))
+            )
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(parse("concat(first,").is_err());
+        assert!(parse("pages pages").is_err());
+        assert!(parse("\"unterminated").is_err());
+        assert_eq!(
+            parse("sum(pages)"),
+            Err(Error::ModuleError(
+                "Invalid expression: unknown function sum".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn check_types() {
+        let expr = parse("concat(first, \" \", last, \" \", pages)").unwrap();
+        assert_eq!(expr.check(&types).unwrap(), ValueType::Text);
+
+        let expr = parse("round((pages - pages_read) / pages * 100)").unwrap();
+        assert_eq!(expr.check(&types).unwrap(), ValueType::Number);
+
+        let expr = parse("first + last").unwrap();
+        assert!(expr.check(&types).is_err());
+
+        let expr = parse("upper(missing)").unwrap();
+        assert_eq!(
+            expr.check(&types),
+            Err(Error::ModuleError("Unknown attribute missing".to_string()))
+        );
+    }
+
+    #[test]
+    fn evaluate() {
+        let values = HashMap::from([
+            ("first", json!("Ada")),
+            ("last", json!("Lovelace")),
+            ("pages", json!(300)),
+            ("pages_read", json!(120)),
+            ("Full name", Value::Null),
+        ]);
+
+        let expr = parse("concat(upper(first), \" \", last)").unwrap();
+        assert_eq!(expr.evaluate(&values), json!("ADA Lovelace"));
+
+        let expr = parse("pages - pages_read").unwrap();
+        assert_eq!(expr.evaluate(&values), json!(180.0));
+
+        let expr = parse("coalesce(`Full name`, first)").unwrap();
+        assert_eq!(expr.evaluate(&values), json!("Ada"));
+
+        let expr = parse("pages / (pages_read - 120)").unwrap();
+        assert_eq!(expr.evaluate(&values), Value::Null);
+    }
+}
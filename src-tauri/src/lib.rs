@@ -1,5 +1,7 @@
 pub mod database;
+mod formula;
 mod macros;
 pub mod models;
 pub mod setup;
+mod units;
 mod utils;
@@ -19,7 +19,7 @@ pub mod macros {
                 type Error = &'static str;
 
                 fn try_from(s: &str) -> Result<$id_name, Self::Error> {
-                    let uuid = uuid::Uuid::parse_str(&s).unwrap();
+                    let uuid = uuid::Uuid::parse_str(&s).map_err(|_| "Invalid id")?;
                     Ok($id_name {
                         0: uuid.as_bytes().to_vec(),
                     })
@@ -30,7 +30,7 @@ pub mod macros {
                 type Error = &'static str;
 
                 fn try_from(s: &String) -> Result<$id_name, Self::Error> {
-                    let uuid = uuid::Uuid::parse_str(&s).unwrap();
+                    let uuid = uuid::Uuid::parse_str(&s).map_err(|_| "Invalid id")?;
                     Ok($id_name {
                         0: uuid.as_bytes().to_vec(),
                     })
@@ -41,7 +41,7 @@ pub mod macros {
                 type Error = &'static str;
 
                 fn try_from(s: String) -> Result<$id_name, Self::Error> {
-                    let uuid = uuid::Uuid::parse_str(&s).unwrap();
+                    let uuid = uuid::Uuid::parse_str(&s).map_err(|_| "Invalid id")?;
                     Ok($id_name {
                         0: uuid.as_bytes().to_vec(),
                     })
@@ -108,7 +108,7 @@ pub mod macros {
                     D: serde::Deserializer<'de>,
                 {
                     let s = String::deserialize(deserializer)?;
-                    let uuid = uuid::Uuid::parse_str(&s).unwrap();
+                    let uuid = uuid::Uuid::parse_str(&s).map_err(serde::de::Error::custom)?;
                     Ok($id_name {
                         0: uuid.as_bytes().to_vec(),
                     })
@@ -0,0 +1,19 @@
+use serde::{Deserialize, Serialize};
+
+use crate::macros::macros::create_id;
+
+create_id!(BlobId);
+create_id!(AttachmentAttributeId);
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Blob {
+    pub hash: String,
+    pub size: u64,
+}
+
+#[derive(Deserialize)]
+pub struct CreateAttachment {
+    pub hash: String,
+    pub filename: String,
+    pub mime: String,
+}
@@ -1,9 +1,11 @@
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 use crate::macros::macros::create_id;
 
 use super::{
     attribute_type::{AttributeType, CreateAttributeType},
+    entity::EntityId,
     entity_schema::EntitySchemaId,
 };
 
@@ -15,6 +17,9 @@ pub struct AttributeSchema {
     pub name: String,
     pub attr_type: AttributeType,
     pub quantity: Quantity,
+    pub constraints: Constraints,
+    pub default: Option<DefaultValue>,
+    pub unique: bool,
 }
 
 #[derive(Deserialize)]
@@ -23,6 +28,67 @@ pub struct CreateAttributeSchema {
     pub name: String,
     pub attr_type: CreateAttributeType,
     pub quantity: Quantity,
+    #[serde(default)]
+    pub constraints: Constraints,
+    #[serde(default)]
+    pub default: Option<DefaultValue>,
+    #[serde(default)]
+    pub unique: bool,
+}
+
+// Changes to an existing attribute. Values are converted when the type or
+// quantity changes, and text is matched against the lookup attribute of the
+// target schema when converting to a reference
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct UpdateAttributeSchema {
+    pub name: Option<String>,
+    pub attr_type: Option<CreateAttributeType>,
+    pub quantity: Option<Quantity>,
+    pub lookup: Option<AttributeSchemaId>,
+}
+
+// Values that could not be carried over by an update. Failures affecting a
+// whole entity, such as a missing required value, have a null value
+#[derive(Serialize, Debug, PartialEq, Default)]
+pub struct MigrationReport {
+    pub failures: Vec<MigrationFailure>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct MigrationFailure {
+    pub entity: EntityId,
+    pub value: Value,
+    pub reason: String,
+}
+
+// Validation rules checked whenever a value is written. Lengths are in
+// characters, and item counts only apply to List attributes
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(default)]
+pub struct Constraints {
+    pub min_length: Option<usize>,
+    pub max_length: Option<usize>,
+    pub pattern: Option<String>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub min_items: Option<usize>,
+    pub max_items: Option<usize>,
+}
+
+// Filled in when an entity is created without a value for the attribute,
+// either as a fixed value or generated at the time of creation
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub enum DefaultValue {
+    Value(Value),
+    Generator(Generator),
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum Generator {
+    Now,
+    EmptyList,
+    Uuid,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
@@ -1,17 +1,32 @@
-use rusqlite::types::{FromSqlResult, ValueRef};
+use rusqlite::{types::FromSqlError, Row};
 use serde::{Deserialize, Serialize};
 
-use crate::macros::macros::create_id;
+use crate::{macros::macros::create_id, units};
 
-use super::entity_schema::EntitySchemaId;
+use super::{attribute_schema::AttributeSchemaId, entity_schema::EntitySchemaId};
 
 create_id!(ReferenceAttributeId);
 create_id!(TextAttributeId);
+create_id!(IntegerAttributeId);
+create_id!(NumberAttributeId);
+create_id!(DateAttributeId);
+create_id!(DateTimeAttributeId);
+create_id!(BooleanAttributeId);
+create_id!(UrlAttributeId);
+create_id!(SelectAttributeId);
+create_id!(SelectOptionId);
+create_id!(ReferenceTargetId);
+create_id!(ReferenceEdgeId);
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub enum AttributeType {
     Simple(SimpleAttributeType),
     Reference(ReferenceAttribute),
+    Select(SelectAttribute),
+    Measure(MeasureAttribute),
+    Computed(ComputedAttribute),
+    Rollup(RollupAttribute),
+    Backlink(BacklinkAttribute),
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
@@ -19,44 +34,171 @@ pub enum SimpleAttributeType {
     Text,
     RichText,
     Longform,
+    Integer,
+    Number,
+    Date,
+    DateTime,
+    Boolean,
+    Url,
+    Attachment,
+    Location,
 }
 
+// A reference with no targets may point at any entity
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct ReferenceAttribute {
+    pub targets: Vec<ReferenceTarget>,
+    pub edges: Vec<EdgeProperty>,
+}
+
+// A property stored on each link of a reference rather than on either entity
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct EdgeProperty {
+    pub name: String,
+    pub attr_type: SimpleAttributeType,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ReferenceTarget {
     pub id: EntitySchemaId,
     pub name: String,
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct SelectAttribute {
+    pub options: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum Dimension {
+    Length,
+    Mass,
+    Volume,
+    Duration,
+    Currency,
+}
+
+// A number in a physical dimension, stored in its canonical unit
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct MeasureAttribute {
+    pub dimension: Dimension,
+    pub unit: String,
+}
+
+// Evaluated from other attributes of the same entity when requested, and
+// never stored
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct ComputedAttribute {
+    pub expression: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum Aggregate {
+    Count,
+    Sum,
+    Min,
+    Max,
+    Average,
+    Distinct,
+}
+
+// Aggregates an attribute of the entities linked by one of the schema's
+// reference attributes. Without an attribute, Count counts the entities
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct RollupAttribute {
+    pub reference: AttributeSchemaId,
+    pub attribute: Option<AttributeSchemaId>,
+    pub aggregate: Aggregate,
+}
+
+// The entities pointing at this one through a reference attribute, read from
+// the reference's values and never stored
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct BacklinkAttribute {
+    pub reference: AttributeSchemaId,
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub enum CreateAttributeType {
     Simple(SimpleAttributeType),
     Reference(CreateReferenceAttribute),
+    Select { options: Vec<String> },
+    Measure(MeasureAttribute),
+    Computed(ComputedAttribute),
+    Rollup(RollupAttribute),
+    Backlink(BacklinkAttribute),
 }
 
+// An inverse name adds a backlink of that name to every target
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct CreateReferenceAttribute {
-    pub id: EntitySchemaId,
+    pub targets: Vec<EntitySchemaId>,
+    #[serde(default)]
+    pub inverse: Option<String>,
+    #[serde(default)]
+    pub edges: Vec<EdgeProperty>,
 }
 
 impl AttributeType {
-    pub fn columns_result(
-        type_column: ValueRef<'_>,
-        id_column: ValueRef<'_>,
-        name_column: ValueRef<'_>,
-    ) -> FromSqlResult<Self> {
-        let value = type_column.as_str()?;
+    // Reads the type from the columns listed in TYPE_COLUMNS, starting at
+    // the given index
+    pub fn columns_result(row: &Row, start: usize) -> rusqlite::Result<Self> {
+        let value = row.get_ref(start)?.as_str()?;
         match value {
             "Reference" => {
-                let name = name_column.as_str()?;
-                let id = EntitySchemaId::column_result_manual(id_column)?;
+                // Targets are read as pairs of hex encoded id and name
+                let targets = row.get_ref(start + 1)?.as_str()?;
+                let targets: Vec<(String, String)> = serde_json::from_str(targets)
+                    .map_err(|err| FromSqlError::Other(Box::new(err)))?;
 
-                let reference = ReferenceAttribute {
-                    id,
-                    name: name.into(),
-                };
+                let targets = targets
+                    .into_iter()
+                    .map(|(id, name)| match id.try_into() {
+                        Ok(id) => Ok(ReferenceTarget { id, name }),
+                        Err(_) => Err(FromSqlError::InvalidType),
+                    })
+                    .collect::<Result<_, _>>()?;
+
+                let edges = row.get_ref(start + 9)?.as_str()?;
+                let edges = serde_json::from_str(edges)
+                    .map_err(|err| FromSqlError::Other(Box::new(err)))?;
+
+                Ok(AttributeType::Reference(ReferenceAttribute {
+                    targets,
+                    edges,
+                }))
+            }
+            "Select" => {
+                let options = row.get_ref(start + 3)?.as_str()?;
+                let options = serde_json::from_str(options)
+                    .map_err(|err| FromSqlError::Other(Box::new(err)))?;
+
+                Ok(AttributeType::Select(SelectAttribute { options }))
+            }
+            "Measure" => {
+                let unit = row.get_ref(start + 2)?.as_str()?;
+                let dimension = units::dimension_of(unit).ok_or(FromSqlError::InvalidType)?;
+
+                Ok(AttributeType::Measure(MeasureAttribute {
+                    dimension,
+                    unit: unit.into(),
+                }))
+            }
+            "Computed" => {
+                let expression = row.get_ref(start + 4)?.as_str()?;
 
-                Ok(AttributeType::Reference(reference))
+                Ok(AttributeType::Computed(ComputedAttribute {
+                    expression: expression.into(),
+                }))
             }
+            "Rollup" => Ok(AttributeType::Rollup(RollupAttribute {
+                reference: row.get(start + 5)?,
+                attribute: row.get(start + 6)?,
+                aggregate: row.get(start + 7)?,
+            })),
+            "Backlink" => Ok(AttributeType::Backlink(BacklinkAttribute {
+                reference: row.get(start + 8)?,
+            })),
             simple => Ok(AttributeType::Simple(SimpleAttributeType::from_sql(
                 simple,
             )?)),
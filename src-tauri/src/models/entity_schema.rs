@@ -1,4 +1,4 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use super::attribute_schema::AttributeSchema;
 use crate::macros::macros::create_id;
@@ -9,9 +9,56 @@ create_id!(EntitySchemaId);
 pub struct EntitySchema {
     pub id: EntitySchemaId,
     pub name: String,
+    pub parent: Option<EntitySchemaId>,
+    pub display: Option<EntityDisplay>,
     pub attributes: Vec<AttributeSchema>,
 }
 
+// How an entity is labelled in lists and links: by the value of one attribute,
+// or by a template naming attributes in braces such as "{last}, {first}".
+// Schemas without one use the display of the schema they extend
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub enum EntityDisplay {
+    Attribute(String),
+    Template(String),
+}
+
+// Restrict refuses to delete a schema while it holds data, Cascade removes the
+// data along with it
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum DeleteMode {
+    Restrict,
+    Cascade,
+}
+
+// What deleting an entity or attribute schema would remove. Schemas are those
+// extending the deleted one, references count links from surviving entities to
+// deleted ones, and dependents are attributes elsewhere that go with it.
+// Blocking counts surviving entities that would lose a link they need, which
+// stops the deletion
+#[derive(Serialize, Debug, PartialEq, Default)]
+pub struct DeletePreview {
+    pub schemas: Vec<String>,
+    pub entities: usize,
+    pub values: usize,
+    pub references: usize,
+    pub blocking: usize,
+    pub dependents: Vec<String>,
+}
+
+// What moving a schema to another parent would remove: the attributes it no
+// longer inherits, with the values of them held by its entities and those of
+// the schemas extending it
+#[derive(Serialize, Debug, PartialEq, Default)]
+pub struct ParentPreview {
+    pub attributes: Vec<String>,
+    pub entities: usize,
+    pub values: usize,
+}
+
+#[derive(Deserialize)]
 pub struct CreateEntitySchema {
     pub name: String,
+    #[serde(default)]
+    pub parent: Option<EntitySchemaId>,
 }
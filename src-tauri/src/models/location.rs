@@ -0,0 +1,24 @@
+use serde::{Deserialize, Serialize};
+
+use crate::macros::macros::create_id;
+
+create_id!(LocationAttributeId);
+
+// A point on the earth in degrees, with an optional human readable label
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Location {
+    pub latitude: f64,
+    pub longitude: f64,
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+// An area bounded by two latitudes and two longitudes. If west is greater
+// than east the box crosses the antimeridian
+#[derive(Deserialize, Debug, PartialEq, Clone)]
+pub struct BoundingBox {
+    pub south: f64,
+    pub west: f64,
+    pub north: f64,
+    pub east: f64,
+}
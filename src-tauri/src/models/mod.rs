@@ -1,6 +1,9 @@
+pub mod attachment;
 pub mod attribute;
 pub mod attribute_schema;
 pub mod attribute_type;
 pub mod entity;
 pub mod entity_schema;
+pub mod location;
 pub mod longform;
+pub mod schema_document;
@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+    attribute_schema::{Constraints, DefaultValue, Quantity},
+    attribute_type::{
+        Aggregate, ComputedAttribute, EdgeProperty, MeasureAttribute, SimpleAttributeType,
+    },
+    entity_schema::EntityDisplay,
+};
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum DocumentFormat {
+    Json,
+    Toml,
+}
+
+// Every entity schema written by name rather than id, so the same document
+// can be applied to several vaults and kept under version control
+#[derive(Serialize, Deserialize, Debug, PartialEq, Default)]
+pub struct SchemaDocument {
+    #[serde(default)]
+    pub schemas: Vec<SchemaDefinition>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct SchemaDefinition {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display: Option<EntityDisplay>,
+    #[serde(default)]
+    pub attributes: Vec<AttributeDefinition>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct AttributeDefinition {
+    pub name: String,
+    pub quantity: Quantity,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub constraints: Constraints,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<DefaultValue>,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub unique: bool,
+    pub attr_type: TypeDefinition,
+}
+
+// Mirrors CreateAttributeType with schemas and attributes named. Rollups name
+// a reference of the same schema and an attribute of its targets, backlinks
+// the schema and name of the reference pointing here
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub enum TypeDefinition {
+    Simple(SimpleAttributeType),
+    Reference {
+        #[serde(default)]
+        targets: Vec<String>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        edges: Vec<EdgeProperty>,
+    },
+    Select {
+        options: Vec<String>,
+    },
+    Measure(MeasureAttribute),
+    Computed(ComputedAttribute),
+    Rollup {
+        reference: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        attribute: Option<String>,
+        aggregate: Aggregate,
+    },
+    Backlink {
+        schema: String,
+        reference: String,
+    },
+}
+
+// The outcome of importing a document. Destructive changes are reported
+// rather than applied, as `Schema` or `Schema.attribute` with the reason
+#[derive(Serialize, Debug, PartialEq, Default)]
+pub struct ImportReport {
+    pub created: Vec<String>,
+    pub updated: Vec<String>,
+    pub destructive: Vec<String>,
+}
+
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    value == &T::default()
+}
@@ -0,0 +1,162 @@
+use rusqlite::Error;
+
+use crate::models::attribute_type::Dimension;
+
+struct Unit {
+    symbol: &'static str,
+    dimension: Dimension,
+    // Size of the unit in the base unit of its dimension
+    factor: f64,
+}
+
+const fn unit(symbol: &'static str, dimension: Dimension, factor: f64) -> Unit {
+    Unit {
+        symbol,
+        dimension,
+        factor,
+    }
+}
+
+const UNITS: [Unit; 28] = [
+    unit("mm", Dimension::Length, 0.001),
+    unit("cm", Dimension::Length, 0.01),
+    unit("m", Dimension::Length, 1.0),
+    unit("km", Dimension::Length, 1000.0),
+    unit("in", Dimension::Length, 0.0254),
+    unit("ft", Dimension::Length, 0.3048),
+    unit("yd", Dimension::Length, 0.9144),
+    unit("mi", Dimension::Length, 1609.344),
+    unit("mg", Dimension::Mass, 0.001),
+    unit("g", Dimension::Mass, 1.0),
+    unit("kg", Dimension::Mass, 1000.0),
+    unit("t", Dimension::Mass, 1_000_000.0),
+    unit("oz", Dimension::Mass, 28.349523125),
+    unit("lb", Dimension::Mass, 453.59237),
+    unit("ml", Dimension::Volume, 0.001),
+    unit("cl", Dimension::Volume, 0.01),
+    unit("dl", Dimension::Volume, 0.1),
+    unit("l", Dimension::Volume, 1.0),
+    unit("tsp", Dimension::Volume, 0.005),
+    unit("tbsp", Dimension::Volume, 0.015),
+    unit("cup", Dimension::Volume, 0.25),
+    unit("ms", Dimension::Duration, 0.001),
+    unit("s", Dimension::Duration, 1.0),
+    unit("min", Dimension::Duration, 60.0),
+    unit("h", Dimension::Duration, 3600.0),
+    unit("d", Dimension::Duration, 86400.0),
+    unit("wk", Dimension::Duration, 604800.0),
+    unit("yr", Dimension::Duration, 31557600.0),
+];
+
+// Currencies are any ISO 4217 style code, and are only convertible to
+// themselves since exchange rates change over time
+fn is_currency(unit: &str) -> bool {
+    unit.len() == 3 && unit.chars().all(|c| c.is_ascii_uppercase())
+}
+
+fn find(unit: &str) -> Option<&'static Unit> {
+    UNITS.iter().find(|candidate| candidate.symbol == unit)
+}
+
+pub fn dimension_of(unit: &str) -> Option<Dimension> {
+    if is_currency(unit) {
+        return Some(Dimension::Currency);
+    }
+
+    find(unit).map(|unit| unit.dimension)
+}
+
+pub fn check_unit(dimension: &Dimension, unit: &str) -> rusqlite::Result<()> {
+    match dimension_of(unit) {
+        Some(found) if &found == dimension => Ok(()),
+        Some(_) => Err(Error::ModuleError(format!(
+            "Unit {unit} is not a unit of {dimension:?}"
+        ))),
+        None => Err(Error::ModuleError(format!("Unknown unit {unit}"))),
+    }
+}
+
+pub fn convert(value: f64, from: &str, to: &str) -> rusqlite::Result<f64> {
+    if from == to {
+        return Ok(value);
+    }
+
+    let (Some(from_unit), Some(to_unit)) = (find(from), find(to)) else {
+        return Err(Error::ModuleError(format!(
+            "Cannot convert from {from} to {to}"
+        )));
+    };
+
+    if from_unit.dimension != to_unit.dimension {
+        return Err(Error::ModuleError(format!(
+            "Cannot convert from {from} to {to}"
+        )));
+    }
+
+    Ok(value * from_unit.factor / to_unit.factor)
+}
+
+// Parses a value such as "3.5 km" or "250g" and converts it to the given unit
+pub fn parse_measure(value: &str, to: &str) -> rusqlite::Result<f64> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+' || c == 'e'))
+        .unwrap_or(value.len());
+
+    let (number, unit) = value.split_at(split);
+
+    let number: f64 = match number.trim().parse() {
+        Ok(number) => Ok(number),
+        Err(_) => Err(Error::ModuleError(format!(
+            "Provided value is not a measurement: {value}"
+        ))),
+    }?;
+
+    let unit = unit.trim();
+
+    // A bare number is taken to already be in the canonical unit
+    if unit.is_empty() {
+        return Ok(number);
+    }
+
+    convert(number, unit, to)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_with_unit() {
+        assert_eq!(parse_measure("3.5 km", "m").unwrap(), 3500.0);
+        assert_eq!(parse_measure("250g", "kg").unwrap(), 0.25);
+        assert_eq!(parse_measure("90 min", "h").unwrap(), 1.5);
+    }
+
+    #[test]
+    fn parse_without_unit() {
+        assert_eq!(parse_measure("12", "km").unwrap(), 12.0);
+    }
+
+    #[test]
+    fn parse_wrong_dimension() {
+        assert_eq!(
+            parse_measure("3 kg", "m"),
+            Err(Error::ModuleError(
+                "Cannot convert from kg to m".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn currencies() {
+        assert_eq!(dimension_of("EUR"), Some(Dimension::Currency));
+        assert_eq!(parse_measure("4.99 EUR", "EUR").unwrap(), 4.99);
+        assert_eq!(
+            parse_measure("4.99 USD", "EUR"),
+            Err(Error::ModuleError(
+                "Cannot convert from USD to EUR".to_string()
+            ))
+        );
+    }
+}
@@ -8,3 +8,8 @@ pub fn get_timestamp() -> u64 {
         .try_into()
         .expect("SystemTime reported time ridiculously far into the future")
 }
+
+// Parameter list for an IN clause over count values
+pub fn placeholders(count: usize) -> String {
+    vec!["?"; count].join(", ")
+}